@@ -0,0 +1,83 @@
+//! Times reading Z-strings and attributes from `minizork.z3`, comparing the library's plain shifts
+//! and masks with the same reads made through bit slices, as they used to be. Run with
+//! `cargo bench` from the directory that the story is in.
+
+use megaboz::*;
+use std::time::{Duration, Instant};
+
+/// Runs `f` repeatedly for about a second and prints the average time it took. Returns what it
+/// returned, so that the work can't be optimized away.
+fn bench<T>(name: &str, mut f: impl FnMut() -> T) -> T {
+    let mut result = f();
+    let mut iterations = 0u32;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) {
+        result = f();
+        iterations += 1;
+    }
+    let nanos = start.elapsed().as_nanos() as f64 / iterations as f64;
+    println!("{:<36} {:>12.0} ns/iter", name, nanos);
+    result
+}
+
+/// Returns the address of each dictionary word's Z-string.
+fn dictionary_words(z: &ZMachine) -> Vec<ByteAddress> {
+    let base = z.dictionary_base();
+    let separators = z[base] as usize;
+    let entry_size = z[base + 1 + separators] as usize;
+    let start = base + separators + 4;
+    (0..z.dictionary_len())
+        .map(|x| start + x * entry_size)
+        .collect()
+}
+
+/// Returns the address of each object's attributes.
+fn object_attributes(z: &ZMachine) -> Vec<ByteAddress> {
+    let entry_size = if z.version() <= Version::V3 { 9 } else { 14 };
+    let start = z.object_table_base() + z.object_property_count() * 2;
+    (0..z.objects_count())
+        .map(|x| start + x * entry_size)
+        .collect()
+}
+
+fn main() {
+    let z = ZMachine::from_file("minizork.z3").expect("minizork.z3 should be in this directory");
+    let words = dictionary_words(&z);
+    let shifted = bench("read_zstring, shifts (dictionary)", || {
+        words
+            .iter()
+            .map(|&addr| z.read_zstring(addr))
+            .collect::<Vec<_>>()
+    });
+    let sliced = bench("read_zstring, BEBitSlice (dictionary)", || {
+        words
+            .iter()
+            .map(|&addr| z.read_zstring_bitslice(addr))
+            .collect::<Vec<_>>()
+    });
+    assert_eq!(shifted, sliced);
+
+    let objects = object_attributes(&z);
+    let count = z.object_attribute_count();
+    let indexed = bench("attributes, BitAddress (all objects)", || {
+        objects
+            .iter()
+            .flat_map(|&addr| (0..count).map(move |x| BitAddress::from(addr) + x))
+            .filter(|&bit| z[bit])
+            .count()
+    });
+    let sliced = bench("attributes, ZBitSlice (all objects)", || {
+        objects
+            .iter()
+            .map(|&addr| {
+                let start = BitAddress::from(addr);
+                z.bit_range(start..(start + count))
+                    .iter()
+                    .by_vals()
+                    .filter(|&bit| bit)
+                    .count()
+            })
+            .sum::<usize>()
+    });
+    assert_eq!(indexed, sliced);
+}
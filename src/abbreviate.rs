@@ -0,0 +1,197 @@
+use crate::*;
+use std::collections::{BinaryHeap, HashMap};
+use thiserror::Error;
+
+/// The longest abbreviation considered, in characters.
+const MAX_ABBREVIATION_LEN: usize = 20;
+
+/// Chooses abbreviations that make a corpus of strings as small as possible once encoded, including
+/// the space taken by the abbreviations themselves. At most `count` abbreviations are returned,
+/// fewer if the version doesn't allow that many or if no more would save space, ordered by how
+/// much each saves.
+///
+/// Abbreviations are chosen greedily: each is the substring that saves the most given the ones
+/// already chosen, which can't be used in the middle of it.
+pub fn optimise_abbreviations(
+    encoder: &ZStringEncoder,
+    corpus: &[impl AsRef<str>],
+    count: usize,
+) -> Vec<String> {
+    let count = count.min(encoder.max_abbreviations());
+    let mut segments: Vec<String> = corpus
+        .iter()
+        .map(|string| string.as_ref().to_string())
+        .collect();
+    let mut occurrences = HashMap::new();
+    for segment in &segments {
+        let starts = segment.char_indices().map(|(x, _)| x);
+        for start in starts {
+            let ends = segment[start..]
+                .char_indices()
+                .map(|(x, ch)| start + x + ch.len_utf8())
+                .skip(1)
+                .take(MAX_ABBREVIATION_LEN - 1);
+            for end in ends {
+                *occurrences.entry(&segment[start..end]).or_insert(0) += 1;
+            }
+        }
+    }
+    let mut candidates: BinaryHeap<_> = occurrences
+        .into_iter()
+        .filter(|&(_, occurrences)| occurrences > 1)
+        .map(|(text, occurrences)| (savings(encoder, text, occurrences), text.to_string()))
+        .filter(|&(savings, _)| savings > 0)
+        .collect();
+    let mut abbreviations = Vec::with_capacity(count);
+    // savings only go down as abbreviations are chosen, so a candidate that still beats the
+    // next best estimate after being recounted is the best there is
+    while abbreviations.len() < count {
+        let (_, text) = match candidates.pop() {
+            Some(candidate) => candidate,
+            None => break,
+        };
+        let occurrences = segments
+            .iter()
+            .map(|segment| segment.matches(&text[..]).count())
+            .sum();
+        let savings = savings(encoder, &text, occurrences);
+        if savings <= 0 {
+            continue;
+        }
+        if candidates.peek().is_some_and(|&(next, _)| next > savings) {
+            candidates.push((savings, text));
+            continue;
+        }
+        segments = segments
+            .iter()
+            .flat_map(|segment| segment.split(&text[..]))
+            .filter(|segment| segment.len() > 1)
+            .map(str::to_string)
+            .collect();
+        abbreviations.push(text);
+    }
+    abbreviations
+}
+
+/// Returns the number of Z-characters saved by abbreviating a string that occurs a number of times.
+fn savings(encoder: &ZStringEncoder, text: &str, occurrences: usize) -> i64 {
+    let cost = encoder.encode_zchars(text).len() as i64;
+    let stored = (cost + 2) / 3 * 3;
+    occurrences as i64 * (cost - 2) - stored
+}
+
+/// An error from [`ZMachine::rewrite_abbreviations`].
+#[derive(Debug, Error)]
+pub enum AbbreviationError {
+    /// There are more abbreviations than the version allows.
+    #[error("Too many abbreviations (was {0}, must be at most {1})")]
+    TooMany(usize, usize),
+    /// The new abbreviations don't fit where the old ones were stored.
+    #[error("Abbreviations don't fit (need {0} bytes, only {1} available)")]
+    NoRoom(usize, usize),
+    /// A string would be longer with the new abbreviations than it was with the old ones.
+    #[error("String at {0:#x} doesn't fit with the new abbreviations")]
+    StringTooLong(usize),
+}
+
+impl ZMachine {
+    /// Returns the addresses of the strings in the abbreviation table, leaving out entries that are
+    /// 0.
+    fn abbreviation_locations(&self) -> Vec<ByteAddress> {
+        let table = self.abbreviations_table_base();
+        (0..self.zstring_encoder().max_abbreviations())
+            .map(|idx| ByteAddress(self.word(table + idx * 2) as usize * 2))
+            .filter(|&addr| addr != ByteAddress::ZERO)
+            .collect()
+    }
+    /// Replaces the story's abbreviations, re-encoding the strings at the given addresses to use
+    /// the new ones. Every string that uses abbreviations must be included, or it will decode
+    /// wrongly afterwards. Addresses of the old abbreviations are ignored.
+    ///
+    /// The new abbreviations are stored where the old ones were, and each string is rewritten in
+    /// place, padded to the same length so that code around inline strings doesn't move. This
+    /// doesn't make the story any smaller: it only changes which abbreviations are used, e.g. to
+    /// leave room for longer strings from [`patch_strings`](ZMachine::patch_strings).
+    ///
+    /// The checksum in the header is updated to match, and afterwards the rewritten story replaces
+    /// the original, as if it had been loaded. Nothing is changed if anything doesn't fit.
+    pub fn rewrite_abbreviations(
+        &mut self,
+        abbreviations: &[impl AsRef<str>],
+        strings: &[ByteAddress],
+    ) -> Result<(), AbbreviationError> {
+        let max = self.zstring_encoder().max_abbreviations();
+        if abbreviations.len() > max {
+            return Err(AbbreviationError::TooMany(abbreviations.len(), max));
+        }
+        if max == 0 {
+            return Ok(());
+        }
+        let old = self.abbreviation_locations();
+        let start = old.iter().copied().min().unwrap_or(ByteAddress::ZERO);
+        let end = old
+            .iter()
+            .map(|&addr| addr + self.read_zstring(addr).1)
+            .max()
+            .unwrap_or(ByteAddress::ZERO);
+        let strings: Vec<_> = strings
+            .iter()
+            .copied()
+            .filter(|&addr| addr < start || addr >= end)
+            .collect();
+        let texts: Vec<_> = strings
+            .iter()
+            .map(|&addr| self.read_zstring(addr))
+            .collect();
+        let encoder = self.zstring_encoder();
+        let mut table = Vec::new();
+        for text in abbreviations.iter().map(AsRef::as_ref).chain(Some("")) {
+            table.push(encoder.encode(text));
+        }
+        let needed = table.iter().map(Vec::len).sum();
+        if needed > end - start {
+            return Err(AbbreviationError::NoRoom(needed, end - start));
+        }
+        let abbreviations = abbreviations.iter().enumerate().map(|(idx, text)| {
+            let abbrv = ZStringAbbrv::new(idx as u8).unwrap();
+            (abbrv, text.as_ref().to_string())
+        });
+        let encoder = encoder.abbreviations(abbreviations);
+        let mut encoded = Vec::with_capacity(strings.len());
+        for (&addr, (text, len)) in strings.iter().zip(&texts) {
+            let mut zchars = encoder.encode_zchars(text);
+            if zchars.len() > len / 2 * 3 {
+                return Err(AbbreviationError::StringTooLong(addr.0));
+            }
+            zchars.resize(len / 2 * 3, 5);
+            encoded.push(pack_zchars(&zchars));
+        }
+        let mut story = self.memory.clone();
+        let mut addr = start;
+        let mut locations = Vec::with_capacity(table.len());
+        for bytes in &table {
+            story[addr.0..(addr.0 + bytes.len())].copy_from_slice(bytes);
+            locations.push(addr);
+            addr += bytes.len();
+        }
+        let empty = locations.pop().unwrap();
+        let base = self.abbreviations_table_base();
+        for idx in 0..max {
+            let location = locations.get(idx).copied().unwrap_or(empty);
+            let entry = base + idx * 2;
+            let word = ((location.0 / 2) as Word).to_be_bytes();
+            story[entry.0..(entry.0 + 2)].copy_from_slice(&word);
+        }
+        for (&addr, bytes) in strings.iter().zip(&encoded) {
+            story[addr.0..(addr.0 + bytes.len())].copy_from_slice(bytes);
+        }
+        let sum = checksum(&story, self.header().file_length()).to_be_bytes();
+        story[ByteAddress::FILE_CHECKSUM.0..(ByteAddress::FILE_CHECKSUM.0 + 2)]
+            .copy_from_slice(&sum);
+        self.memory = story.clone();
+        self.original = story;
+        self.decode_cache.clear();
+        self.reset_execution();
+        Ok(())
+    }
+}
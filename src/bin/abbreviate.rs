@@ -0,0 +1,137 @@
+use anyhow::{bail, format_err, Error};
+use megaboz::*;
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "\
+Usage: abbreviate [OPTIONS] [CORPUS]...
+
+Chooses abbreviations for a corpus of strings, read one per line from each CORPUS file, and prints
+them as Inform Abbreviate directives.
+
+Options:
+    --count N       How many abbreviations to choose (default: as many as the version allows)
+    --version N     The story version to encode for (default: 3, or the story's version)
+    --story FILE    Encode for a story, adding the strings found in it to the corpus
+    --output FILE   Rewrite the story to use the abbreviations and save it here. Strings are
+                    padded to their old lengths, so the story doesn't get any smaller, and only
+                    the strings found in the story are rewritten";
+
+#[derive(Default)]
+struct Args {
+    count: Option<usize>,
+    version: Option<u8>,
+    story: Option<String>,
+    output: Option<String>,
+    corpus: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, Error> {
+    let mut args = Args::default();
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| format_err!("Missing value for {}", arg))
+        };
+        match &arg[..] {
+            "--count" => args.count = Some(value()?.parse()?),
+            "--version" => args.version = Some(value()?.parse()?),
+            "--story" => args.story = Some(value()?),
+            "--output" => args.output = Some(value()?),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with("--") => bail!("Unknown option {}", arg),
+            _ => args.corpus.push(arg),
+        }
+    }
+    if args.story.is_none() && args.output.is_some() {
+        bail!("--output needs a story");
+    }
+    Ok(args)
+}
+
+/// Quotes a string the way Inform does, with `^` for newlines and `~` for double quotes.
+fn inform_string(text: &str) -> String {
+    let escaped: String = text
+        .chars()
+        .map(|ch| match ch {
+            '\n' => '^',
+            '"' => '~',
+            ch => ch,
+        })
+        .collect();
+    format!("\"{}\"", escaped)
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let mut corpus = Vec::new();
+    for path in &args.corpus {
+        corpus.extend(fs::read_to_string(path)?.lines().map(str::to_string));
+    }
+    let mut z_machine = match &args.story {
+        Some(path) => Some(ZMachine::from_file_with_options(
+            path,
+            LoadOptions::new().lenient(true),
+        )?),
+        None => None,
+    };
+    let mut strings = Vec::new();
+    if let Some(z_machine) = &z_machine {
+        for string in z_machine.strings() {
+            if let StringSource::Abbreviation(_) = string.source {
+                continue;
+            }
+            strings.push(string.addr);
+            corpus.push(string.text);
+        }
+    }
+    let abbreviations = match &z_machine {
+        Some(z_machine) => {
+            let encoder = z_machine.zstring_encoder();
+            let count = args.count.unwrap_or_else(|| encoder.max_abbreviations());
+            optimise_abbreviations(&encoder, &corpus, count)
+        }
+        None => {
+            let number = args.version.unwrap_or(3);
+            let version = Version::from_number(number)
+                .ok_or_else(|| format_err!("Invalid version {}", number))?;
+            let encoder = ZStringEncoder::new(
+                version,
+                Alphabet::for_version(version),
+                UnicodeTable::default(),
+            );
+            let count = args.count.unwrap_or_else(|| encoder.max_abbreviations());
+            optimise_abbreviations(&encoder, &corpus, count)
+        }
+    };
+    for text in &abbreviations {
+        println!("Abbreviate {};", inform_string(text));
+    }
+    if let (Some(z_machine), Some(path)) = (&mut z_machine, &args.output) {
+        // strings are found by scanning the story, so any elsewhere (e.g. in arrays) are missed
+        eprintln!(
+            "abbreviate: warning: rewriting the {} strings found; any others that use \
+             abbreviations will decode wrongly",
+            strings.len()
+        );
+        z_machine.rewrite_abbreviations(&abbreviations, &strings)?;
+        let len = z_machine.len_bytes();
+        fs::write(path, &z_machine[ByteAddress::ZERO..ByteAddress(len)])?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|error| {
+        eprintln!("abbreviate: {}\n\n{}", error, USAGE);
+        process::exit(2);
+    });
+    if let Err(error) = run(args) {
+        eprintln!("abbreviate: {}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,378 @@
+use anyhow::{bail, format_err, Error};
+use megaboz::*;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+const USAGE: &str = "\
+Usage: megaboz-dbg [OPTIONS] STORY
+
+Runs a story under a debugger. Type `help` at the prompt for a list of commands.
+
+Options:
+    --debug-info FILE  Read names and source locations from Inform's debugging information
+    --seed SEED        How to generate random numbers: random (the default), sequential, or a
+                       number to seed with";
+
+const COMMANDS: &str = "\
+Commands (numbers are decimal, or hex with 0x or $; with debugging information, routines and
+globals can be given by name):
+    break ADDR              Stop before the instruction at ADDR
+    break routine ROUTINE   Stop when a routine is called
+    watch global GLOBAL     Stop when a global variable (numbered from 16) changes
+    watch memory ADDR LEN   Stop when any of LEN bytes from ADDR change
+    watch parent|sibling|child OBJ
+                            Stop when one of an object's relations changes
+    delete ID               Remove a breakpoint or watchpoint
+    info                    List breakpoints and watchpoints
+    step, s                 Execute one instruction, stepping into calls
+    next, n                 Execute one instruction, stepping over calls
+    finish                  Run until the current routine returns
+    continue, c             Run until something stops execution
+    backtrace, bt           Show the call stack
+    locals [FRAME]          Show a frame's local variables (0 is the current frame)
+    stack [FRAME]           Show a frame's part of the evaluation stack
+    global GLOBAL           Show a global variable
+    x ADDR [LEN]            Show memory
+    disassemble [ADDR] [N]  Show N instructions from ADDR (default: 5 from the current one)
+    quit, q                 Stop debugging";
+
+/// Plays the story through standard input and output.
+struct Terminal;
+
+impl Frontend for Terminal {
+    fn print(&mut self, text: &str) {
+        print!("{}", text);
+        let _ = io::stdout().flush();
+    }
+    fn read_line(&mut self, max_len: usize) -> Option<String> {
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end().chars().take(max_len).collect()),
+        }
+    }
+    fn warn(&mut self, message: &str) {
+        eprintln!("[warning: {}]", message);
+    }
+}
+
+struct Args {
+    mode: RandomMode,
+    debug_info: Option<String>,
+    story: String,
+}
+
+fn parse_args() -> Result<Args, Error> {
+    let mut mode = RandomMode::Random;
+    let mut debug_info = None;
+    let mut story = None;
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match &arg[..] {
+            "--seed" => {
+                let seed = iter
+                    .next()
+                    .ok_or_else(|| format_err!("Missing value for --seed"))?;
+                mode = seed
+                    .parse()
+                    .map_err(|_| format_err!("Invalid seed {}", seed))?;
+            }
+            "--debug-info" => {
+                debug_info = Some(
+                    iter.next()
+                        .ok_or_else(|| format_err!("Missing value for --debug-info"))?,
+                );
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with("--") => bail!("Unknown option {}", arg),
+            _ if story.is_none() => story = Some(arg),
+            _ => bail!("Only one story can be given"),
+        }
+    }
+    let story = story.ok_or_else(|| format_err!("Missing story"))?;
+    Ok(Args {
+        mode,
+        debug_info,
+        story,
+    })
+}
+
+fn parse_number(text: Option<&str>) -> Result<usize, Error> {
+    let text = text.ok_or_else(|| format_err!("Missing number"))?;
+    let number = if let Some(hex) = text.strip_prefix("0x") {
+        usize::from_str_radix(hex, 16)
+    } else if let Some(hex) = text.strip_prefix('$') {
+        usize::from_str_radix(hex, 16)
+    } else {
+        text.parse()
+    };
+    number.map_err(|_| format_err!("Invalid number {}", text))
+}
+
+/// Reads a routine's address, or its name from the debugging information.
+fn routine(z: &ZMachine, text: Option<&str>) -> Result<ByteAddress, Error> {
+    if let Ok(addr) = parse_number(text) {
+        return Ok(ByteAddress(addr));
+    }
+    z.debug_info()
+        .and_then(|info| {
+            info.routines
+                .iter()
+                .find(|routine| Some(&routine.name[..]) == text)
+        })
+        .map(|routine| routine.addr)
+        .ok_or_else(|| format_err!("Unknown routine {}", text.unwrap_or("")))
+}
+
+/// Reads a global variable's number, or its name from the debugging information.
+fn global(z: &ZMachine, text: Option<&str>) -> Result<u8, Error> {
+    let var = match parse_number(text) {
+        Ok(var) => var,
+        Err(_) => {
+            let addr = z
+                .debug_info()
+                .and_then(|info| {
+                    info.globals
+                        .iter()
+                        .find(|(_, name)| Some(&name[..]) == text)
+                })
+                .map(|(&addr, _)| addr)
+                .ok_or_else(|| format_err!("Unknown global {}", text.unwrap_or("")))?;
+            let base = ByteAddress(z.word(ByteAddress::GLOBAL_VARIABLE_TABLE_LOCATION) as usize);
+            (addr - base) / 2 + 16
+        }
+    };
+    if !(16..=255).contains(&var) {
+        bail!("Global variables are numbered 16 to 255");
+    }
+    Ok(var as u8)
+}
+
+fn disassemble(z: &ZMachine, mut addr: ByteAddress, count: usize) {
+    for _ in 0..count {
+        match z.decode_instruction(addr) {
+            Ok(decoded) => {
+                println!("{}", z.format_instruction(&decoded));
+                addr = decoded.next;
+            }
+            Err(error) => {
+                println!("{:#07x}  {}", addr.0, error);
+                break;
+            }
+        }
+    }
+}
+
+fn frame<'a>(z: &'a ZMachine, arg: Option<&str>) -> Result<CallFrame<'a>, Error> {
+    let depth = match arg {
+        Some(_) => parse_number(arg)?,
+        None => 0,
+    };
+    let mut frames = z.call_stack();
+    if depth >= frames.len() {
+        bail!("There are only {} frames", frames.len());
+    }
+    Ok(frames.remove(frames.len() - 1 - depth))
+}
+
+fn format_words(words: &[Word]) -> String {
+    let words: Vec<_> = words.iter().map(|word| format!("{:#06x}", word)).collect();
+    words.join(" ")
+}
+
+fn watchpoint(z: &ZMachine, words: &[&str]) -> Result<Watchpoint, Error> {
+    let relation = |relation| {
+        let id = parse_number(words.get(1).copied())?;
+        let count = z.objects_count();
+        if id == 0 || id > count {
+            bail!("Objects are numbered 1 to {}", count);
+        }
+        Ok(Watchpoint::Relation(id, relation))
+    };
+    match words.first().copied() {
+        Some("global") => Ok(Watchpoint::Global(global(z, words.get(1).copied())?)),
+        Some("memory") => {
+            let addr = ByteAddress(parse_number(words.get(1).copied())?);
+            let len = parse_number(words.get(2).copied())?;
+            Ok(Watchpoint::Memory(addr..(addr + len)))
+        }
+        Some("parent") => relation(Relation::Parent),
+        Some("sibling") => relation(Relation::Sibling),
+        Some("child") => relation(Relation::Child),
+        _ => bail!("Expected global, memory, parent, sibling or child"),
+    }
+}
+
+/// Reports why execution stopped. Returns false if the story has quit, which ends debugging.
+fn report(z: &ZMachine, stop: Result<Stop, RuntimeError>) -> bool {
+    match stop {
+        Ok(Stop::Step) => {}
+        Ok(Stop::Breakpoint(id)) => println!("Breakpoint {}", id),
+        Ok(Stop::Watchpoint { id, old, new }) => {
+            println!("Watchpoint {}: {:02x?} -> {:02x?}", id, old, new)
+        }
+        Ok(Stop::Input) => println!("The story is waiting for input"),
+        Ok(Stop::Quit) => {
+            println!("The story has quit");
+            return false;
+        }
+        Err(error) => println!("Error: {}", z.describe_error(&error)),
+    }
+    disassemble(z, z.pc(), 1);
+    true
+}
+
+/// Runs a debugger command. Returns false to stop debugging.
+fn command(z: &mut ZMachine, debugger: &mut Debugger, line: &str) -> Result<bool, Error> {
+    let words: Vec<_> = line.split_whitespace().collect();
+    let arg = |x: usize| words.get(x).copied();
+    match words.first().copied().unwrap_or("") {
+        "" => {}
+        "break" | "b" => {
+            let breakpoint = if arg(1) == Some("routine") {
+                Breakpoint::Routine(routine(z, arg(2))?)
+            } else {
+                Breakpoint::Address(ByteAddress(parse_number(arg(1))?))
+            };
+            println!("Breakpoint {}", debugger.add_breakpoint(breakpoint));
+        }
+        "watch" => {
+            let watchpoint = watchpoint(z, &words[1..])?;
+            if let Watchpoint::Memory(range) = &watchpoint {
+                if range.end.0 > z.len_bytes() {
+                    bail!("The story is only {:#x} bytes long", z.len_bytes());
+                }
+            }
+            println!("Watchpoint {}", debugger.add_watchpoint(z, watchpoint)?);
+        }
+        "delete" => {
+            if !debugger.remove(parse_number(arg(1))?) {
+                bail!("No such breakpoint or watchpoint");
+            }
+        }
+        "info" => {
+            for (id, breakpoint) in debugger.breakpoints() {
+                println!("{}: {:x?}", id, breakpoint);
+            }
+            for (id, watchpoint) in debugger.watchpoints() {
+                println!("{}: {:x?}", id, watchpoint);
+            }
+        }
+        "step" | "s" | "next" | "n" | "finish" | "continue" | "c" => {
+            let stop = match words[0] {
+                "step" | "s" => debugger.step(z),
+                "next" | "n" => debugger.step_over(z),
+                "finish" => debugger.step_out(z),
+                _ => debugger.resume(z),
+            };
+            return Ok(report(z, stop));
+        }
+        "backtrace" | "bt" => {
+            let frames = z.call_stack();
+            // each frame is at the call in the frame above it, except the current one
+            let mut at = z.pc();
+            for (x, frame) in frames.iter().rev().enumerate() {
+                println!(
+                    "#{} {} ({})",
+                    x,
+                    z.describe_routine(frame.routine, at),
+                    format_words(&frame.locals[..frame.arg_count.min(frame.locals.len())])
+                );
+                at = frame.return_pc;
+            }
+        }
+        "locals" => {
+            let frame = frame(z, arg(1))?;
+            for (x, local) in frame.locals.iter().enumerate() {
+                println!(
+                    "{} = {:#06x} ({})",
+                    z.variable_name(x as u8 + 1, frame.routine),
+                    local,
+                    *local as i16
+                );
+            }
+        }
+        "stack" => println!("{}", format_words(frame(z, arg(1))?.stack)),
+        "global" => {
+            let var = global(z, arg(1))?;
+            let value = z.peek_variable(var);
+            println!(
+                "{} = {:#06x} ({})",
+                z.variable_name(var, z.pc()),
+                value,
+                value as i16
+            );
+        }
+        "x" => {
+            let addr = parse_number(arg(1))?;
+            let len = match arg(2) {
+                Some(_) => parse_number(arg(2))?,
+                None => 16,
+            };
+            let end = (addr + len).min(z.len_bytes());
+            for start in (addr..end).step_by(16) {
+                let row = &z[ByteAddress(start)..ByteAddress((start + 16).min(end))];
+                println!("{:#07x}  {:02x?}", start, row);
+            }
+        }
+        "disassemble" | "disas" => {
+            let addr = match arg(1) {
+                Some(_) => ByteAddress(parse_number(arg(1))?),
+                None => z.pc(),
+            };
+            let count = match arg(2) {
+                Some(_) => parse_number(arg(2))?,
+                None => 5,
+            };
+            disassemble(z, addr, count);
+        }
+        "help" | "h" => println!("{}", COMMANDS),
+        "quit" | "q" => return Ok(false),
+        word => bail!("Unknown command {}; try help", word),
+    }
+    Ok(true)
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let mut z_machine =
+        ZMachine::from_file_with_options(&args.story, LoadOptions::new().lenient(true))?;
+    z_machine.set_random_mode(args.mode);
+    if let Some(path) = &args.debug_info {
+        let info = DebugInfo::from_xml(&fs::read_to_string(path)?)
+            .ok_or_else(|| format_err!("{} isn't Inform debugging information", path))?;
+        z_machine.set_debug_info(info);
+    }
+    z_machine.set_frontend(Terminal);
+    let mut debugger = Debugger::new();
+    disassemble(&z_machine, z_machine.pc(), 1);
+    let stdin = io::stdin();
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        match command(&mut z_machine, &mut debugger, &line) {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(error) => println!("{}", error),
+        }
+    }
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|error| {
+        eprintln!("megaboz-dbg: {}\n\n{}", error, USAGE);
+        process::exit(2);
+    });
+    if let Err(error) = run(args) {
+        eprintln!("megaboz-dbg: {}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::{bail, format_err, Error};
+use megaboz::*;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::process;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+Usage: zbench [OPTIONS] STORY SCRIPT
+
+Plays a story without a screen, typing each line of SCRIPT as a command, and reports how long it
+took. The story stops when the script runs out.
+
+Options:
+    --repeat N    Play the script N times, starting the story afresh each time (default 1)
+    --no-cache    Don't cache decoded instructions
+    --seed SEED   How to generate random numbers: a number to seed with (default 0), random or
+                  sequential
+    --output      Print what the story prints, to check that the script does what was meant";
+
+/// Types the commands in a script, and throws the story's output away unless asked to print it.
+struct Script {
+    commands: VecDeque<String>,
+    output: bool,
+}
+
+impl Frontend for Script {
+    fn print(&mut self, text: &str) {
+        if self.output {
+            print!("{}", text);
+        }
+    }
+    fn read_line(&mut self, max_len: usize) -> Option<String> {
+        let command = self.commands.pop_front()?;
+        if self.output {
+            println!("{}", command);
+        }
+        Some(command.chars().take(max_len).collect())
+    }
+    fn warn(&mut self, message: &str) {
+        eprintln!("[warning: {}]", message);
+    }
+}
+
+struct Args {
+    repeat: usize,
+    cache: bool,
+    mode: RandomMode,
+    output: bool,
+    story: String,
+    script: String,
+}
+
+fn parse_args() -> Result<Args, Error> {
+    let mut repeat = 1;
+    let mut cache = true;
+    let mut mode = RandomMode::Seeded(0);
+    let mut output = false;
+    let mut paths = Vec::new();
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match &arg[..] {
+            "--repeat" => {
+                let count = iter
+                    .next()
+                    .ok_or_else(|| format_err!("Missing value for --repeat"))?;
+                repeat = count
+                    .parse()
+                    .map_err(|_| format_err!("Invalid count {}", count))?;
+            }
+            "--no-cache" => cache = false,
+            "--seed" => {
+                let seed = iter
+                    .next()
+                    .ok_or_else(|| format_err!("Missing value for --seed"))?;
+                mode = seed
+                    .parse()
+                    .map_err(|_| format_err!("Invalid seed {}", seed))?;
+            }
+            "--output" => output = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with("--") => bail!("Unknown option {}", arg),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        bail!("Expected a story and a script");
+    }
+    let script = paths.pop().unwrap();
+    let story = paths.pop().unwrap();
+    Ok(Args {
+        repeat,
+        cache,
+        mode,
+        output,
+        story,
+        script,
+    })
+}
+
+/// What one play of the script took.
+struct Play {
+    instructions: u64,
+    turns: usize,
+    time: Duration,
+}
+
+/// Plays the script once, until the story quits or the script runs out.
+fn play(story: &[u8], commands: &[String], args: &Args) -> Result<Play, Error> {
+    let mut z_machine = ZMachine::new_with_options(story, LoadOptions::new().lenient(true))?;
+    z_machine.set_random_mode(args.mode);
+    z_machine.set_decode_cache(args.cache);
+    z_machine.set_frontend(Script {
+        commands: commands.iter().cloned().collect(),
+        output: args.output,
+    });
+    let mut instructions = 0;
+    let start = Instant::now();
+    loop {
+        z_machine
+            .step()
+            .map_err(|error| format_err!("{}", z_machine.describe_error(&error)))?;
+        instructions += 1;
+        if !z_machine.is_running() {
+            break;
+        }
+    }
+    Ok(Play {
+        instructions,
+        turns: commands.len(),
+        time: start.elapsed(),
+    })
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let story = fs::read(&args.story)?;
+    let commands: Vec<String> = fs::read_to_string(&args.script)?
+        .lines()
+        .map(|line| line.trim_end().to_string())
+        .collect();
+    let mut total = Duration::default();
+    let mut instructions = 0;
+    let mut turns = 0;
+    for x in 0..args.repeat {
+        let play = play(&story, &commands, &args)?;
+        println!(
+            "play {}: {} instructions in {:.3}s",
+            x + 1,
+            play.instructions,
+            play.time.as_secs_f64()
+        );
+        total += play.time;
+        instructions += play.instructions;
+        turns += play.turns;
+    }
+    let secs = total.as_secs_f64();
+    println!(
+        "total: {} instructions, {} turns in {:.3}s",
+        instructions, turns, secs
+    );
+    if secs > 0.0 {
+        println!(
+            "{:.0} instructions/s, {:.1}us/turn",
+            instructions as f64 / secs,
+            secs * 1e6 / turns.max(1) as f64
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|error| {
+        eprintln!("zbench: {}\n\n{}", error, USAGE);
+        process::exit(2);
+    });
+    if let Err(error) = run(args) {
+        eprintln!("zbench: {}", error);
+        process::exit(1);
+    }
+}
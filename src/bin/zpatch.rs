@@ -0,0 +1,203 @@
+use anyhow::{bail, format_err, Error};
+use megaboz::*;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "\
+Usage: zpatch STORY TRANSLATIONS --output FILE
+
+Replaces strings in a story with translations, and saves the patched story. TRANSLATIONS is either
+a PO file, as written by `zstrings --format po`, or the text format written by `zstrings`, with the
+text in the last column changed. Untranslated strings are left alone.
+
+Options:
+    --output FILE  Where to save the patched story";
+
+struct Args {
+    story: String,
+    translations: String,
+    output: String,
+}
+
+fn parse_args() -> Result<Args, Error> {
+    let mut paths = Vec::new();
+    let mut output = None;
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match &arg[..] {
+            "--output" => {
+                output = Some(
+                    iter.next()
+                        .ok_or_else(|| format_err!("Missing value for --output"))?,
+                )
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with("--") => bail!("Unknown option {}", arg),
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        bail!("Expected a story and a translations file");
+    }
+    let translations = paths.pop().unwrap();
+    let story = paths.pop().unwrap();
+    let output = output.ok_or_else(|| format_err!("Missing --output"))?;
+    Ok(Args {
+        story,
+        translations,
+        output,
+    })
+}
+
+fn parse_address(text: &str) -> Result<ByteAddress, Error> {
+    let hex = text.trim_start_matches("0x");
+    let addr =
+        usize::from_str_radix(hex, 16).map_err(|_| format_err!("Invalid address {:?}", text))?;
+    Ok(ByteAddress(addr))
+}
+
+/// Reads a quoted PO string, undoing the escapes `zstrings` writes.
+fn po_string(line: usize, text: &str) -> Result<String, Error> {
+    let text = text.trim();
+    if text.len() < 2 || !text.starts_with('"') || !text.ends_with('"') {
+        bail!("Line {}: expected a quoted string", line);
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text[1..(text.len() - 1)].chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('"') => unescaped.push('"'),
+            Some('\\') => unescaped.push('\\'),
+            Some(digit @ '0'..='7') => {
+                // up to three octal digits
+                let mut code = digit.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.clone().next().and_then(|ch| ch.to_digit(8)) {
+                        Some(digit) => {
+                            code = code * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                unescaped.push(char::from_u32(code).unwrap());
+            }
+            Some(ch) => bail!("Line {}: unknown escape \\{}", line, ch),
+            None => bail!("Line {}: unfinished escape", line),
+        }
+    }
+    Ok(unescaped)
+}
+
+/// Reads translations from a PO file, keyed by the addresses in each entry's `msgctxt`.
+fn read_po(text: &str) -> Result<BTreeMap<ByteAddress, String>, Error> {
+    let mut translations = BTreeMap::new();
+    let mut context = None;
+    let mut msgstr: Option<String> = None;
+    let mut finish = |context: &mut Option<ByteAddress>, msgstr: &mut Option<String>| {
+        if let (Some(addr), Some(text)) = (context.take(), msgstr.take()) {
+            if !text.is_empty() {
+                translations.insert(addr, text);
+            }
+        }
+    };
+    // which string continuation lines belong to
+    let mut in_msgstr = false;
+    for (x, line) in text.lines().enumerate() {
+        let number = x + 1;
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("msgctxt ") {
+            finish(&mut context, &mut msgstr);
+            context = Some(parse_address(&po_string(number, rest)?)?);
+            in_msgstr = false;
+        } else if let Some(rest) = line.strip_prefix("msgstr ") {
+            msgstr = Some(po_string(number, rest)?);
+            in_msgstr = true;
+        } else if line.starts_with('"') {
+            if in_msgstr {
+                if let Some(msgstr) = &mut msgstr {
+                    msgstr.push_str(&po_string(number, line)?);
+                }
+            }
+        } else if line.is_empty() {
+            finish(&mut context, &mut msgstr);
+            in_msgstr = false;
+        } else {
+            in_msgstr = false;
+        }
+    }
+    finish(&mut context, &mut msgstr);
+    Ok(translations)
+}
+
+/// Reads translations from `zstrings`' text format: an address, a source and the text, separated
+/// by tabs, with `^` for newlines.
+fn read_text(text: &str) -> Result<BTreeMap<ByteAddress, String>, Error> {
+    let mut translations = BTreeMap::new();
+    for (x, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.splitn(3, '\t');
+        let addr = fields.next().unwrap();
+        let text = match (fields.next(), fields.next()) {
+            (Some(_), Some(text)) => text,
+            _ => bail!("Line {}: expected three tab-separated fields", x + 1),
+        };
+        translations.insert(parse_address(addr)?, text.replace('^', "\n"));
+    }
+    Ok(translations)
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let mut z_machine =
+        ZMachine::from_file_with_options(&args.story, LoadOptions::new().lenient(true))?;
+    let text = fs::read_to_string(&args.translations)?;
+    let mut translations = if args.translations.ends_with(".po") {
+        read_po(&text)?
+    } else {
+        read_text(&text)?
+    };
+    // strings that weren't changed don't need patching
+    for string in z_machine.strings() {
+        if translations.get(&string.addr) == Some(&string.text) {
+            translations.remove(&string.addr);
+        }
+    }
+    let guessed = z_machine.patch_strings(&translations)?;
+    for location in guessed {
+        eprintln!(
+            "zpatch: warning: guessed that the number at {:#x} was a string's address, and updated it",
+            location.0
+        );
+    }
+    let len = z_machine.len_bytes();
+    fs::write(
+        &args.output,
+        &z_machine[ByteAddress::ZERO..ByteAddress(len)],
+    )?;
+    eprintln!("Patched {} strings", translations.len());
+    Ok(())
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|error| {
+        eprintln!("zpatch: {}\n\n{}", error, USAGE);
+        process::exit(2);
+    });
+    if let Err(error) = run(args) {
+        eprintln!("zpatch: {}", error);
+        process::exit(1);
+    }
+}
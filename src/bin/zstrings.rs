@@ -0,0 +1,132 @@
+use anyhow::{bail, format_err, Error};
+use megaboz::*;
+use std::env;
+use std::process;
+
+const USAGE: &str = "\
+Usage: zstrings [OPTIONS] STORY
+
+Prints every string found in a story with its address, for proofreading or translation.
+
+Options:
+    --format FORMAT  How to print the strings: text (the default), json or po";
+
+enum Format {
+    Text,
+    Json,
+    Po,
+}
+
+fn parse_args() -> Result<(Format, String), Error> {
+    let mut format = Format::Text;
+    let mut story = None;
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match &arg[..] {
+            "--format" => {
+                format = match iter.next().as_ref().map(|format| &format[..]) {
+                    Some("text") => Format::Text,
+                    Some("json") => Format::Json,
+                    Some("po") => Format::Po,
+                    Some(format) => bail!("Unknown format {}", format),
+                    None => bail!("Missing value for --format"),
+                }
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with("--") => bail!("Unknown option {}", arg),
+            _ if story.is_none() => story = Some(arg),
+            _ => bail!("Only one story can be given"),
+        }
+    }
+    let story = story.ok_or_else(|| format_err!("Missing story"))?;
+    Ok((format, story))
+}
+
+/// Describes where a string was found.
+fn source(source: StringSource) -> String {
+    match source {
+        StringSource::Abbreviation(abbrv) => format!("abbreviation {}", abbrv.idx()),
+        StringSource::ObjectName(id) => format!("object {}", id),
+        StringSource::Inline(addr) => format!("inline at {:#x}", addr.0),
+        StringSource::Static => "static".to_string(),
+    }
+}
+
+/// Escapes a string with backslashes, as JSON and gettext both do. They differ only for other
+/// control characters, which JSON writes as `\uXXXX` and gettext as octal.
+fn escape(text: &str, format: &Format) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => match format {
+                Format::Json => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+                _ => escaped.push_str(&format!("\\{:03o}", ch as u32)),
+            },
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn run(format: Format, story: &str) -> Result<(), Error> {
+    let z_machine = ZMachine::from_file_with_options(story, LoadOptions::new().lenient(true))?;
+    let strings = z_machine.strings();
+    match format {
+        Format::Text => {
+            for string in &strings {
+                println!(
+                    "{:#07x}\t{}\t{}",
+                    string.addr.0,
+                    source(string.source),
+                    string.text.replace('\n', "^")
+                );
+            }
+        }
+        Format::Json => {
+            println!("[");
+            for (x, string) in strings.iter().enumerate() {
+                let comma = if x + 1 < strings.len() { "," } else { "" };
+                println!(
+                    "  {{\"address\": {}, \"source\": \"{}\", \"text\": \"{}\"}}{}",
+                    string.addr.0,
+                    source(string.source),
+                    escape(&string.text, &format),
+                    comma
+                );
+            }
+            println!("]");
+        }
+        Format::Po => {
+            println!("msgid \"\"");
+            println!("msgstr \"\"");
+            println!("\"Content-Type: text/plain; charset=UTF-8\\n\"");
+            for string in strings.iter().filter(|string| !string.text.is_empty()) {
+                println!();
+                println!("#: {:#x}", string.addr.0);
+                println!("#. {}", source(string.source));
+                println!("msgctxt \"{:#x}\"", string.addr.0);
+                println!("msgid \"{}\"", escape(&string.text, &format));
+                println!("msgstr \"\"");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let (format, story) = parse_args().unwrap_or_else(|error| {
+        eprintln!("zstrings: {}\n\n{}", error, USAGE);
+        process::exit(2);
+    });
+    if let Err(error) = run(format, &story) {
+        eprintln!("zstrings: {}", error);
+        process::exit(1);
+    }
+}
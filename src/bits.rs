@@ -1,17 +1,16 @@
 use std::ops::{Add, AddAssign, Index, Range, RangeInclusive, Sub, SubAssign};
 
-use bitvec::cursor::BigEndian;
+use bitvec::order::Msb0;
 use bitvec::slice::BitSlice;
 
 mod consts;
-pub use self::consts::*;
 
 use crate::*;
 
 pub type Word = u16;
 
 pub type ZBitSlice = BEBitSlice<u8>;
-pub type BEBitSlice<T> = BitSlice<BigEndian, T>;
+pub type BEBitSlice<T> = BitSlice<T, Msb0>;
 
 /// Wrapper type for a [`usize`] representing a byte address.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -38,6 +37,11 @@ impl BitAddress {
     pub fn addr(&self) -> usize {
         self.0
     }
+    /// Returns the byte that this bit is in, and the mask that selects it. Bits count from the
+    /// most significant bit of each byte.
+    pub(crate) fn byte_and_mask(self) -> (ByteAddress, u8) {
+        (ByteAddress(self.0 / 8), 0b1000_0000 >> (self.0 % 8))
+    }
 }
 
 impl From<Word> for ByteAddress {
@@ -55,7 +59,12 @@ impl From<Word> for BitAddress {
 impl Index<BitAddress> for ZMachine {
     type Output = bool;
     fn index(&self, index: BitAddress) -> &Self::Output {
-        &ZBitSlice::from_slice(&self.memory)[index.0]
+        let (byte, mask) = index.byte_and_mask();
+        if self.memory[byte.0] & mask != 0 {
+            &true
+        } else {
+            &false
+        }
     }
 }
 
@@ -99,6 +108,11 @@ impl ZMachine {
         let word = &self.memory[addr.0..=(addr + 1).0];
         Word::from_be_bytes([word[0], word[1]])
     }
+    /// Reads a word, or returns [`None`] if it's past the end of memory.
+    pub(crate) fn checked_word(&self, addr: ByteAddress) -> Option<Word> {
+        let word = self.memory.get(addr.0..addr.0.checked_add(2)?)?;
+        Some(Word::from_be_bytes([word[0], word[1]]))
+    }
 }
 
 impl From<BitAddress> for ByteAddress {
@@ -188,4 +202,27 @@ impl ZMachine {
             Version::V8 => 8 * packed,
         })
     }
+    /// Converts a byte address to a packed address, the reverse of
+    /// [`resolve_packed_address`](ZMachine::resolve_packed_address). Returns [`None`] if the
+    /// address isn't aligned for packing or is out of range.
+    pub fn pack_address(&self, addr: ByteAddress, routine: bool) -> Option<Word> {
+        let (offset, divisor) = match self.version() {
+            Version::V1 | Version::V2 | Version::V3 => (0, 2),
+            Version::V4 | Version::V5 => (0, 4),
+            Version::V6 | Version::V7 => {
+                let offset = self.word(if routine {
+                    ByteAddress::ROUTINES_OFFSET
+                } else {
+                    ByteAddress::STATIC_STRINGS_OFFSET
+                });
+                (8 * offset as usize, 4)
+            }
+            Version::V8 => (0, 8),
+        };
+        let relative = addr.0.checked_sub(offset)?;
+        if relative % divisor != 0 || relative / divisor > Word::MAX as usize {
+            return None;
+        }
+        Some((relative / divisor) as Word)
+    }
 }
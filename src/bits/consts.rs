@@ -1,32 +1,36 @@
 use super::*;
 
 impl BitAddress {
-    pub const STATUS_LINE_AVAILABLE: Self = Self(0xC);
-    pub const SCREEN_SPLIT_AVAILABLE: Self = Self(0xD);
-    pub const VARIABLE_PITCH_FONT_DEFAULT: Self = Self(0xE);
-    pub const COLORS_AVAILABLE: Self = Self(0x8);
-    pub const PICTURES_AVAILABLE: Self = Self(0x9);
-    pub const BOLD_AVAILABLE: Self = Self(0xA);
-    pub const ITALIC_AVAILABLE: Self = Self(0xB);
-    pub const FIXED_SPACE_AVAILABLE: Self = Self(0xC);
-    pub const SOUND_EFFECT_AVAILABLE: Self = Self(0xD);
-    pub const TIMED_KEYBOARD_AVAILABLE: Self = Self(0xF);
-    pub const TRANSCRIPTING_ON: Self = Self(0x70);
-    pub const FORCE_FIXED_PITCH: Self = Self(0x71);
-    pub const SCREEN_REDRAW_REQUESTED: Self = Self(0x72);
-    pub const PICTURES_DESIRED: Self = Self(0x73);
-    pub const UNDO_DESIRED: Self = Self(0x74);
-    pub const MOUSE_DESIRED: Self = Self(0x75);
-    pub const COLORS_DESIRED: Self = Self(0x76);
-    pub const SOUNDS_DESIRED: Self = Self(0x77);
-    pub const MENUS_DESIRED: Self = Self(0x78);
-    pub const HEADER_EXT_TRANSPARENCY_DESIRED: Self = Self(0x40);
-    pub const STATUS_LINE: Self = Self(0x9);
-    pub const TWO_DISKS: Self = Self(0xA);
+    pub const STATUS_LINE_AVAILABLE: Self = Self(0xB);
+    pub const SCREEN_SPLIT_AVAILABLE: Self = Self(0xA);
+    pub const VARIABLE_PITCH_FONT_DEFAULT: Self = Self(0x9);
+    pub const COLORS_AVAILABLE: Self = Self(0xF);
+    pub const PICTURES_AVAILABLE: Self = Self(0xE);
+    pub const BOLD_AVAILABLE: Self = Self(0xD);
+    pub const ITALIC_AVAILABLE: Self = Self(0xC);
+    pub const FIXED_SPACE_AVAILABLE: Self = Self(0xB);
+    pub const SOUND_EFFECT_AVAILABLE: Self = Self(0xA);
+    pub const TIMED_KEYBOARD_AVAILABLE: Self = Self(0x8);
+    pub const TRANSCRIPTING_ON: Self = Self(0x8F);
+    pub const FORCE_FIXED_PITCH: Self = Self(0x8E);
+    pub const SCREEN_REDRAW_REQUESTED: Self = Self(0x8D);
+    pub const PICTURES_DESIRED: Self = Self(0x8C);
+    pub const UNDO_DESIRED: Self = Self(0x8B);
+    pub const MOUSE_DESIRED: Self = Self(0x8A);
+    pub const COLORS_DESIRED: Self = Self(0x89);
+    pub const SOUNDS_DESIRED: Self = Self(0x88);
+    pub const MENUS_DESIRED: Self = Self(0x87);
+    pub const HEADER_EXT_TRANSPARENCY_DESIRED: Self = Self(0x4F);
+    pub const STATUS_LINE: Self = Self(0xE);
+    pub const TWO_DISKS: Self = Self(0xD);
 }
 
 impl ByteAddress {
     pub const STORY_VERSION: Self = Self(0x0);
+    pub const FLAGS1: Self = Self(0x1);
+    pub const RELEASE_NUMBER: Self = Self(0x2);
+    pub const FLAGS2: Self = Self(0x10);
+    pub const SERIAL_CODE: Self = Self(0x12);
     pub const INTERPRETER_NUMBER: Self = Self(0x1E);
     pub const INTERPRETER_VERSION: Self = Self(0x1F);
     pub const SCREEN_HEIGHT: Self = Self(0x20);
@@ -62,10 +66,11 @@ impl ByteAddress {
     pub const HEADER_EXT_MOUSE_X: usize = 0x2;
     pub const HEADER_EXT_MOUSE_Y: usize = 0x4;
     pub const HEADER_EXT_UNICODE_TRANSLATION_TABLE_LOCATION: usize = 0x6;
+    pub const HEADER_EXT_FLAGS3: usize = 0x8;
     pub const HEADER_EXT_DEFAULT_FOREGROUND_COLOR: usize = 0xA;
     pub const HEADER_EXT_DEFAULT_BACKGROUND_COLOR: usize = 0xC;
 }
 
 impl BitAddress {
-    pub const HEADER_EXT_UNUSED_FLAGS3: Range<usize> = 0x41..0x48;
+    pub const HEADER_EXT_UNUSED_FLAGS3: Range<usize> = 0x40..0x4F;
 }
@@ -0,0 +1,294 @@
+use crate::*;
+use std::convert::TryInto;
+use thiserror::Error;
+
+/// A parsed [Blorb](https://eblong.com/zarf/blorb/blorb.html) resource file.
+///
+/// A Blorb file bundles a story together with its pictures, sounds and metadata. The story itself
+/// can be loaded with [`ZMachine::from_blorb`].
+#[derive(Debug, Clone)]
+pub struct Blorb {
+    data: Vec<u8>,
+    chunks: Vec<ChunkEntry>,
+    resources: Vec<Resource>,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct ChunkEntry {
+    kind: [u8; 4],
+    start: usize,
+    len: usize,
+}
+
+/// An entry in a Blorb file's resource index.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Resource {
+    /// What the resource is used for.
+    pub usage: ResourceUsage,
+    /// The resource number, as used by the story.
+    pub number: u32,
+    /// The offset of the resource's chunk from the start of the file.
+    pub offset: usize,
+}
+
+/// What a Blorb resource is used for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResourceUsage {
+    /// A picture (`Pict`).
+    Picture,
+    /// A sound (`Snd `).
+    Sound,
+    /// The story executable (`Exec`).
+    Executable,
+    /// A data file (`Data`).
+    Data,
+}
+
+impl ResourceUsage {
+    fn from_id(id: &[u8]) -> Option<Self> {
+        match id {
+            b"Pict" => Some(ResourceUsage::Picture),
+            b"Snd " => Some(ResourceUsage::Sound),
+            b"Exec" => Some(ResourceUsage::Executable),
+            b"Data" => Some(ResourceUsage::Data),
+            _ => None,
+        }
+    }
+}
+
+/// A chunk of a Blorb file.
+#[derive(Debug, Copy, Clone)]
+pub struct BlorbChunk<'a> {
+    /// The chunk's type, e.g. `ZCOD`, `PNG ` or `OGGV`.
+    pub kind: [u8; 4],
+    /// The contents of the chunk.
+    pub data: &'a [u8],
+    /// The contents of the chunk including its 8-byte header. For embedded IFF files such as
+    /// AIFF sounds (type `FORM`) this is the complete file.
+    pub raw: &'a [u8],
+}
+
+/// The `Reso` chunk, describing how pictures should be scaled in version 6.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    /// The standard window size, as `(width, height)`.
+    pub standard: (u32, u32),
+    /// The minimum window size, as `(width, height)`.
+    pub min: (u32, u32),
+    /// The maximum window size, as `(width, height)`.
+    pub max: (u32, u32),
+    /// The scaling ratios of each picture.
+    pub pictures: Vec<PictureResolution>,
+}
+
+/// The scaling ratios of a picture in the `Reso` chunk, each stored as `(numerator, denominator)`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PictureResolution {
+    /// The picture number.
+    pub number: u32,
+    /// The standard scaling ratio.
+    pub standard: (u32, u32),
+    /// The minimum scaling ratio.
+    pub min: (u32, u32),
+    /// The maximum scaling ratio.
+    pub max: (u32, u32),
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+impl Blorb {
+    /// Returns whether some data looks like a Blorb file.
+    pub fn is_blorb(data: &[u8]) -> bool {
+        data.len() >= 12 && &data[0..4] == b"FORM" && &data[8..12] == b"IFRS"
+    }
+    /// Parses a Blorb file.
+    pub fn new(data: impl Into<Vec<u8>>) -> BlorbResult<Self> {
+        let data = data.into();
+        if !Self::is_blorb(&data) {
+            return Err(BlorbError::NotBlorb);
+        }
+        let form_len = read_u32(&data, 4).unwrap() as usize;
+        let end = form_len
+            .checked_add(8)
+            .filter(|&end| end <= data.len())
+            .ok_or(BlorbError::Truncated)?;
+        let mut chunks = Vec::new();
+        let mut pos = 12;
+        while pos + 8 <= end {
+            let mut kind = [0; 4];
+            kind.copy_from_slice(&data[pos..pos + 4]);
+            let len = read_u32(&data, pos + 4).unwrap() as usize;
+            if pos + 8 + len > end {
+                return Err(BlorbError::Truncated);
+            }
+            chunks.push(ChunkEntry {
+                kind,
+                start: pos,
+                len,
+            });
+            pos += 8 + len + (len & 1);
+        }
+        let index = match chunks.first() {
+            Some(chunk) if &chunk.kind == b"RIdx" => *chunk,
+            _ => return Err(BlorbError::MissingResourceIndex),
+        };
+        let index_data = &data[(index.start + 8)..(index.start + 8 + index.len)];
+        let count = read_u32(index_data, 0).ok_or(BlorbError::Truncated)? as usize;
+        let mut resources = Vec::with_capacity(count);
+        for x in 0..count {
+            let entry = index_data
+                .get((4 + x * 12)..(16 + x * 12))
+                .ok_or(BlorbError::Truncated)?;
+            let usage = ResourceUsage::from_id(&entry[0..4])
+                .ok_or_else(|| BlorbError::InvalidResource(read_u32(entry, 8).unwrap() as usize))?;
+            let number = read_u32(entry, 4).unwrap();
+            let offset = read_u32(entry, 8).unwrap() as usize;
+            if !chunks.iter().any(|chunk| chunk.start == offset) {
+                return Err(BlorbError::InvalidResource(offset));
+            }
+            resources.push(Resource {
+                usage,
+                number,
+                offset,
+            });
+        }
+        Ok(Self {
+            data,
+            chunks,
+            resources,
+        })
+    }
+    fn chunk_at(&self, entry: &ChunkEntry) -> BlorbChunk<'_> {
+        BlorbChunk {
+            kind: entry.kind,
+            data: &self.data[(entry.start + 8)..(entry.start + 8 + entry.len)],
+            raw: &self.data[entry.start..(entry.start + 8 + entry.len)],
+        }
+    }
+    /// Returns every entry in the resource index.
+    pub fn resources(&self) -> &[Resource] {
+        &self.resources
+    }
+    /// Returns the chunk of a particular resource, or [`None`] if there is no such resource.
+    pub fn resource(&self, usage: ResourceUsage, number: u32) -> Option<BlorbChunk<'_>> {
+        let resource = self
+            .resources
+            .iter()
+            .find(|res| res.usage == usage && res.number == number)?;
+        let entry = self
+            .chunks
+            .iter()
+            .find(|chunk| chunk.start == resource.offset)?;
+        Some(self.chunk_at(entry))
+    }
+    /// Returns the story executable, or [`None`] if this file doesn't contain one.
+    pub fn executable(&self) -> Option<BlorbChunk<'_>> {
+        self.resource(ResourceUsage::Executable, 0)
+    }
+    /// Returns a picture (usually a `PNG ` or `JPEG` chunk).
+    pub fn picture(&self, number: u32) -> Option<BlorbChunk<'_>> {
+        self.resource(ResourceUsage::Picture, number)
+    }
+    /// Returns a sound (usually an AIFF `FORM` or an `OGGV` chunk).
+    pub fn sound(&self, number: u32) -> Option<BlorbChunk<'_>> {
+        self.resource(ResourceUsage::Sound, number)
+    }
+    /// Returns the first chunk of a particular type, whether or not it is a resource.
+    pub fn chunk(&self, kind: &[u8; 4]) -> Option<BlorbChunk<'_>> {
+        self.chunks
+            .iter()
+            .find(|chunk| &chunk.kind == kind)
+            .map(|entry| self.chunk_at(entry))
+    }
+    /// Returns every chunk in the file, in order.
+    pub fn chunks(&self) -> impl Iterator<Item = BlorbChunk<'_>> {
+        self.chunks.iter().map(move |entry| self.chunk_at(entry))
+    }
+    /// Returns the iFiction metadata (`IFmd`) as XML, or [`None`] if there isn't any.
+    pub fn metadata(&self) -> Option<&str> {
+        std::str::from_utf8(self.chunk(b"IFmd")?.data).ok()
+    }
+    /// Returns the picture number of the frontispiece (`Fspc`), or [`None`] if there isn't one.
+    pub fn frontispiece(&self) -> Option<u32> {
+        read_u32(self.chunk(b"Fspc")?.data, 0)
+    }
+    /// Returns the picture resolution information (`Reso`), or [`None`] if there isn't any.
+    pub fn resolution(&self) -> Option<Resolution> {
+        let data = self.chunk(b"Reso")?.data;
+        let pair = |offset| Some((read_u32(data, offset)?, read_u32(data, offset + 4)?));
+        let mut resolution = Resolution {
+            standard: pair(0)?,
+            min: pair(8)?,
+            max: pair(16)?,
+            pictures: Vec::new(),
+        };
+        let mut offset = 24;
+        while offset + 28 <= data.len() {
+            resolution.pictures.push(PictureResolution {
+                number: read_u32(data, offset)?,
+                standard: pair(offset + 4)?,
+                min: pair(offset + 12)?,
+                max: pair(offset + 20)?,
+            });
+            offset += 28;
+        }
+        Some(resolution)
+    }
+}
+
+impl ZMachine {
+    /// Creates a new Z-machine instance from a Blorb file already loaded in memory, loading the
+    /// story executable it contains. The rest of the file stays available through
+    /// [`blorb`](ZMachine::blorb).
+    pub fn from_blorb(file: impl Into<Vec<u8>>) -> LoadResult<Self> {
+        Self::from_blorb_with_options(file, LoadOptions::new())
+    }
+    /// Creates a new Z-machine instance from a Blorb file already loaded in memory, validating the
+    /// story according to the provided options.
+    pub fn from_blorb_with_options(
+        file: impl Into<Vec<u8>>,
+        options: LoadOptions,
+    ) -> LoadResult<Self> {
+        let blorb = Blorb::new(file)?;
+        let exec = blorb.executable().ok_or(BlorbError::NoExecutable)?;
+        if &exec.kind != b"ZCOD" {
+            let kind = String::from_utf8_lossy(&exec.kind).into_owned();
+            return Err(BlorbError::UnsupportedExecutable(kind).into());
+        }
+        let mut z = Self::new_with_options(exec.data, options)?;
+        z.blorb = Some(blorb);
+        Ok(z)
+    }
+    /// Returns the Blorb file this story was loaded from, if it was loaded from one.
+    pub fn blorb(&self) -> Option<&Blorb> {
+        self.blorb.as_ref()
+    }
+}
+
+/// Errors that can occur when parsing a Blorb file.
+#[derive(Debug, Clone, Error)]
+pub enum BlorbError {
+    /// The file isn't an IFF `IFRS` form.
+    #[error("Not a Blorb file")]
+    NotBlorb,
+    /// The file, or one of its chunks, ends early.
+    #[error("Blorb file is truncated")]
+    Truncated,
+    /// The file doesn't start with a resource index.
+    #[error("Blorb file has no resource index")]
+    MissingResourceIndex,
+    /// A resource index entry is unknown or doesn't point at a chunk.
+    #[error("Invalid resource at offset {0:#x}")]
+    InvalidResource(usize),
+    /// The file doesn't contain a story.
+    #[error("Blorb file has no executable")]
+    NoExecutable,
+    /// The file contains a story for a different virtual machine, such as Glulx.
+    #[error("Unsupported executable type {0:?}")]
+    UnsupportedExecutable(String),
+}
+
+pub type BlorbResult<T> = Result<T, BlorbError>;
@@ -0,0 +1,218 @@
+use crate::*;
+use std::collections::BTreeMap;
+
+/// Symbols and source locations from the debugging information that Inform writes with `-k`
+/// (`gameinfo.dbg`). Only the XML format, written by Inform 6.33 and later, is understood.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DebugInfo {
+    /// The source files, by index.
+    pub sources: BTreeMap<usize, String>,
+    /// The names of global variables, by the address of their value.
+    pub globals: BTreeMap<ByteAddress, String>,
+    /// The names of objects, by ID.
+    pub objects: BTreeMap<usize, String>,
+    /// The names of attributes, by number.
+    pub attributes: BTreeMap<usize, String>,
+    /// The names of properties, by number.
+    pub properties: BTreeMap<usize, String>,
+    /// The routines, in address order.
+    pub routines: Vec<DebugRoutine>,
+}
+
+/// A routine described by debugging information.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugRoutine {
+    /// The routine's name.
+    pub name: String,
+    /// The address of the routine's header.
+    pub addr: ByteAddress,
+    /// The length of the routine in bytes.
+    pub len: usize,
+    /// Where the routine is defined.
+    pub location: Option<SourceLocation>,
+    /// The names of the local variables, starting with the first.
+    pub locals: Vec<String>,
+    /// The addresses of the instructions that begin each statement, with where the statement is,
+    /// in address order.
+    pub sequence_points: Vec<(ByteAddress, SourceLocation)>,
+}
+
+/// A position in a source file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The index of the file in [`DebugInfo::sources`].
+    pub file: usize,
+    /// The line, counting from 1.
+    pub line: usize,
+}
+
+impl DebugInfo {
+    /// Parses debugging information, returning [`None`] if it isn't well-formed XML or isn't
+    /// debugging information.
+    pub fn from_xml(xml: &str) -> Option<Self> {
+        let root = xml::parse(xml)?;
+        if root.name != "inform-story-file" {
+            return None;
+        }
+        let mut info = DebugInfo::default();
+        for element in root.elements() {
+            let identifier = element.child_text("identifier");
+            match &element.name[..] {
+                "source" => {
+                    let index = element.attribute("index")?.parse().ok()?;
+                    let path = element
+                        .child_text("given-path")
+                        .or_else(|| element.child_text("resolved-path"))?;
+                    info.sources.insert(index, path);
+                }
+                "global-variable" => {
+                    let addr = ByteAddress(number(element, "address")?);
+                    info.globals.insert(addr, identifier?);
+                }
+                "object" => {
+                    info.objects.insert(number(element, "value")?, identifier?);
+                }
+                "attribute" => {
+                    info.attributes
+                        .insert(number(element, "value")?, identifier?);
+                }
+                "property" => {
+                    info.properties
+                        .insert(number(element, "value")?, identifier?);
+                }
+                "routine" => info.routines.push(routine(element)?),
+                _ => {}
+            }
+        }
+        info.routines.sort_by_key(|routine| routine.addr);
+        Some(info)
+    }
+    /// Returns the routine that an address is in.
+    pub fn routine_at(&self, addr: ByteAddress) -> Option<&DebugRoutine> {
+        let idx = match self
+            .routines
+            .binary_search_by_key(&addr, |routine| routine.addr)
+        {
+            Ok(idx) => idx,
+            Err(0) => return None,
+            Err(idx) => idx - 1,
+        };
+        let routine = &self.routines[idx];
+        if addr < routine.addr + routine.len {
+            Some(routine)
+        } else {
+            None
+        }
+    }
+    /// Returns where the statement that an address is in comes from.
+    pub fn source_location(&self, addr: ByteAddress) -> Option<SourceLocation> {
+        let routine = self.routine_at(addr)?;
+        routine
+            .sequence_points
+            .iter()
+            .take_while(|(point, _)| *point <= addr)
+            .last()
+            .map(|&(_, location)| location)
+            .or(routine.location)
+    }
+    /// Describes a source location as `file:line`.
+    pub fn describe_location(&self, location: SourceLocation) -> String {
+        match self.sources.get(&location.file) {
+            Some(path) => format!("{}:{}", path, location.line),
+            None => format!("#{}:{}", location.file, location.line),
+        }
+    }
+}
+
+fn number(element: &xml::Element, name: &str) -> Option<usize> {
+    element.child_text(name)?.parse().ok()
+}
+
+fn source_location(element: &xml::Element) -> Option<SourceLocation> {
+    let location = element.child("source-code-location")?;
+    Some(SourceLocation {
+        file: number(location, "file-index")?,
+        line: number(location, "line")?,
+    })
+}
+
+fn routine(element: &xml::Element) -> Option<DebugRoutine> {
+    let mut locals = Vec::new();
+    for local in element.children_named("local-variable") {
+        let index = number(local, "index")?;
+        if index == 0 {
+            return None;
+        }
+        if locals.len() < index {
+            locals.resize(index, String::new());
+        }
+        locals[index - 1] = local.child_text("identifier")?;
+    }
+    let mut sequence_points = element
+        .children_named("sequence-point")
+        .map(|point| {
+            Some((
+                ByteAddress(number(point, "address")?),
+                source_location(point)?,
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    sequence_points.sort_by_key(|&(addr, _)| addr);
+    Some(DebugRoutine {
+        name: element.child_text("identifier")?,
+        addr: ByteAddress(number(element, "address")?),
+        len: number(element, "byte-count")?,
+        location: source_location(element),
+        locals,
+        sequence_points,
+    })
+}
+
+impl ZMachine {
+    /// Loads debugging information, which tools use to show names instead of numbers.
+    pub fn set_debug_info(&mut self, info: DebugInfo) {
+        self.debug_info = Some(info);
+    }
+    /// Returns the debugging information, if any has been loaded.
+    pub fn debug_info(&self) -> Option<&DebugInfo> {
+        self.debug_info.as_ref()
+    }
+    /// Names a variable: `sp`, or the name from the debugging information or else `L00`-`L0e`
+    /// or `G00`-`Gef`. Local variables are named as in the routine that `addr` is in.
+    pub fn variable_name(&self, var: u8, addr: ByteAddress) -> String {
+        let info = self.debug_info.as_ref();
+        let name = match var {
+            0 => return "sp".to_string(),
+            1..=15 => info
+                .and_then(|info| info.routine_at(addr))
+                .and_then(|routine| routine.locals.get(var as usize - 1))
+                .filter(|name| !name.is_empty()),
+            _ => info.and_then(|info| info.globals.get(&self.global_address(var))),
+        };
+        match (name, var) {
+            (Some(name), _) => name.clone(),
+            (None, 1..=15) => format!("L{:02x}", var - 1),
+            (None, _) => format!("G{:02x}", var - 16),
+        }
+    }
+    /// Describes the routine at an address as `routine Name (file:line)`, where the line is that
+    /// of the statement at `at`. Without debugging information the routine's address is given.
+    pub fn describe_routine(&self, routine: ByteAddress, at: ByteAddress) -> String {
+        let info = match &self.debug_info {
+            Some(info) => info,
+            None => return format!("routine {:#x}", routine.0),
+        };
+        let name = match info.routine_at(routine) {
+            Some(debug) if debug.addr == routine => &debug.name[..],
+            _ => return format!("routine {:#x}", routine.0),
+        };
+        let location = match info.routine_at(at) {
+            Some(debug) if debug.addr == routine => info.source_location(at),
+            _ => None,
+        };
+        match location {
+            Some(location) => format!("routine {} ({})", name, info.describe_location(location)),
+            None => format!("routine {}", name),
+        }
+    }
+}
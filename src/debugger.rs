@@ -0,0 +1,259 @@
+use crate::*;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use thiserror::Error;
+
+/// Where execution should stop.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Before the instruction at an address is executed.
+    Address(ByteAddress),
+    /// When the routine at an address is called, before its first instruction.
+    Routine(ByteAddress),
+}
+
+/// Memory that execution should stop after a change to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Watchpoint {
+    /// A global variable, numbered from 16 as instructions refer to them.
+    Global(u8),
+    /// A range of memory.
+    Memory(Range<ByteAddress>),
+    /// One of an object's relations, such as its parent.
+    Relation(usize, Relation),
+}
+
+/// An error from [`Debugger::add_watchpoint`].
+#[derive(Debug, Error)]
+pub enum WatchpointError {
+    /// The global variable isn't numbered from 16.
+    #[error("Global variables are numbered from 16 (was {0})")]
+    InvalidGlobal(u8),
+    /// The object isn't in the object table.
+    #[error("Object {0} doesn't exist")]
+    InvalidObject(usize),
+    /// The watched memory runs past the end of the story.
+    #[error("Watched memory at {0:#x} runs past the end of the story")]
+    OutOfRange(usize),
+}
+
+impl Watchpoint {
+    /// Checks that this watchpoint watches memory in the story.
+    fn check(&self, z: &ZMachine) -> Result<(), WatchpointError> {
+        match *self {
+            Watchpoint::Global(var) if var < 16 => return Err(WatchpointError::InvalidGlobal(var)),
+            Watchpoint::Relation(id, _) if id == 0 || id > z.objects_count() => {
+                return Err(WatchpointError::InvalidObject(id))
+            }
+            _ => {}
+        }
+        let range = self.range(z);
+        if range.start > range.end || range.end.0 > z.len_bytes() {
+            return Err(WatchpointError::OutOfRange(range.start.0));
+        }
+        Ok(())
+    }
+    /// Returns the memory that this watchpoint watches, once it's been checked.
+    fn range(&self, z: &ZMachine) -> Range<ByteAddress> {
+        match self {
+            Watchpoint::Global(var) => {
+                let addr = z.global_address(*var);
+                addr..(addr + 2)
+            }
+            Watchpoint::Memory(range) => range.clone(),
+            Watchpoint::Relation(id, relation) => z.relation_location(*id, *relation),
+        }
+    }
+}
+
+/// Why a [`Debugger`] stopped running the story.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    /// The step asked for finished.
+    Step,
+    /// A breakpoint was reached. Holds its ID.
+    Breakpoint(usize),
+    /// Watched memory changed. Holds the watchpoint's ID, and the memory before and after.
+    Watchpoint {
+        id: usize,
+        old: Vec<u8>,
+        new: Vec<u8>,
+    },
+    /// The story quit.
+    Quit,
+    /// The story is suspended waiting for input, which must be given with
+    /// [`ZMachine::resume_with`] or [`ZMachine::send_line`] before it can carry on.
+    Input,
+}
+
+/// One routine on the call stack, as seen by [`ZMachine::call_stack`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallFrame<'a> {
+    /// The routine's address, or where execution started for the main routine before version 6.
+    pub routine: ByteAddress,
+    /// Where execution carries on when the routine returns.
+    pub return_pc: ByteAddress,
+    /// The variable that the return value is stored in, if any.
+    pub store: Option<u8>,
+    /// The routine's local variables.
+    pub locals: &'a [Word],
+    /// How many arguments the routine was called with.
+    pub arg_count: usize,
+    /// The routine's part of the evaluation stack, with the top last.
+    pub stack: &'a [Word],
+}
+
+/// Runs a story with breakpoints, watchpoints and stepping. Breakpoints and watchpoints share
+/// their IDs, which count up from 1.
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    watchpoints: BTreeMap<usize, (Watchpoint, Vec<u8>)>,
+    next_id: usize,
+}
+
+impl Debugger {
+    /// Creates a debugger with no breakpoints or watchpoints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    fn next_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id
+    }
+    /// Adds a breakpoint, returning its ID.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        let id = self.next_id();
+        self.breakpoints.insert(id, breakpoint);
+        id
+    }
+    /// Adds a watchpoint, returning its ID. Changes are looked for from the story's current state.
+    /// Returns an error if the watchpoint's global, object or memory isn't in the story.
+    pub fn add_watchpoint(
+        &mut self,
+        z: &ZMachine,
+        watchpoint: Watchpoint,
+    ) -> Result<usize, WatchpointError> {
+        watchpoint.check(z)?;
+        let id = self.next_id();
+        let range = watchpoint.range(z);
+        let value = z[range].to_vec();
+        self.watchpoints.insert(id, (watchpoint, value));
+        Ok(id)
+    }
+    /// Removes a breakpoint or watchpoint. Returns whether there was one with that ID.
+    pub fn remove(&mut self, id: usize) -> bool {
+        self.breakpoints.remove(&id).is_some() || self.watchpoints.remove(&id).is_some()
+    }
+    /// Returns the breakpoints, with their IDs.
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, Breakpoint)> + '_ {
+        self.breakpoints
+            .iter()
+            .map(|(&id, &breakpoint)| (id, breakpoint))
+    }
+    /// Returns the watchpoints, with their IDs.
+    pub fn watchpoints(&self) -> impl Iterator<Item = (usize, &Watchpoint)> {
+        self.watchpoints
+            .iter()
+            .map(|(&id, (watchpoint, _))| (id, watchpoint))
+    }
+    /// Executes a single instruction, stepping into any routine it calls.
+    pub fn step(&mut self, z: &mut ZMachine) -> Result<Stop, RuntimeError> {
+        self.run_while(z, |_| false)
+    }
+    /// Executes a single instruction, running any routine it calls until it returns.
+    pub fn step_over(&mut self, z: &mut ZMachine) -> Result<Stop, RuntimeError> {
+        let depth = z.frames.len();
+        self.run_while(z, |z| z.frames.len() > depth)
+    }
+    /// Runs until the current routine returns.
+    pub fn step_out(&mut self, z: &mut ZMachine) -> Result<Stop, RuntimeError> {
+        let depth = z.frames.len();
+        self.run_while(z, |z| z.frames.len() >= depth)
+    }
+    /// Runs until a breakpoint or watchpoint is hit, or the story quits.
+    pub fn resume(&mut self, z: &mut ZMachine) -> Result<Stop, RuntimeError> {
+        self.run_while(z, |_| true)
+    }
+    /// Executes instructions until `running` returns false afterwards, or something stops
+    /// execution first. At least one instruction is always executed, so that execution can carry
+    /// on from a breakpoint.
+    fn run_while(
+        &mut self,
+        z: &mut ZMachine,
+        running: impl Fn(&ZMachine) -> bool,
+    ) -> Result<Stop, RuntimeError> {
+        if z.has_quit() {
+            return Ok(Stop::Quit);
+        }
+        loop {
+            let depth = z.frames.len();
+            z.step()?;
+            if z.has_quit() {
+                return Ok(Stop::Quit);
+            }
+            if z.waiting_for().is_some() {
+                return Ok(Stop::Input);
+            }
+            if let Some(stop) = self.check_watchpoints(z) {
+                return Ok(stop);
+            }
+            let called = if z.frames.len() > depth {
+                z.frames.last().map(|frame| frame.routine)
+            } else {
+                None
+            };
+            let hit = self
+                .breakpoints
+                .iter()
+                .find(|(_, &breakpoint)| match breakpoint {
+                    Breakpoint::Address(addr) => addr == z.pc,
+                    Breakpoint::Routine(addr) => Some(addr) == called,
+                });
+            if let Some((&id, _)) = hit {
+                return Ok(Stop::Breakpoint(id));
+            }
+            if !running(z) {
+                return Ok(Stop::Step);
+            }
+        }
+    }
+    fn check_watchpoints(&mut self, z: &ZMachine) -> Option<Stop> {
+        for (&id, (watchpoint, value)) in &mut self.watchpoints {
+            let new = &z[watchpoint.range(z)];
+            if new != &value[..] {
+                let old = std::mem::replace(value, new.to_vec());
+                return Some(Stop::Watchpoint {
+                    id,
+                    old,
+                    new: new.to_vec(),
+                });
+            }
+        }
+        None
+    }
+}
+
+impl ZMachine {
+    /// Returns the routines that are running, starting with the main routine.
+    pub fn call_stack(&self) -> Vec<CallFrame<'_>> {
+        self.frames
+            .iter()
+            .enumerate()
+            .map(|(x, frame)| {
+                let stack_end = self
+                    .frames
+                    .get(x + 1)
+                    .map_or(self.stack.len(), |next| next.stack_base);
+                CallFrame {
+                    routine: frame.routine,
+                    return_pc: frame.return_pc,
+                    store: frame.store,
+                    locals: &frame.locals,
+                    arg_count: frame.arg_count,
+                    stack: &self.stack[frame.stack_base..stack_end],
+                }
+            })
+            .collect()
+    }
+}
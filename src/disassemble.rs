@@ -0,0 +1,590 @@
+use crate::*;
+use opcodes::{ext, op0, op1, op2, var};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A decoded instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// The address of the instruction.
+    pub addr: ByteAddress,
+    /// The name of the instruction, as the standard gives it.
+    pub name: &'static str,
+    /// The instruction's operands.
+    pub operands: Vec<Operand>,
+    /// The variable that the instruction stores its result in, if it stores one.
+    pub store: Option<u8>,
+    /// Where the instruction branches to, if it branches.
+    pub branch: Option<Branch>,
+    /// The address of the Z-string printed by `print` and `print_ret`, which follows the opcode.
+    pub text: Option<ByteAddress>,
+    /// The address of the next instruction.
+    pub next: ByteAddress,
+}
+
+/// The branch of an instruction.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Branch {
+    /// Whether the branch is taken when the condition succeeds, rather than when it fails.
+    pub on: bool,
+    /// Where the branch goes.
+    pub target: BranchTarget,
+}
+
+/// Where a branch goes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BranchTarget {
+    /// The routine returns false.
+    ReturnFalse,
+    /// The routine returns true.
+    ReturnTrue,
+    /// Execution continues at an address.
+    Address(ByteAddress),
+}
+
+/// A routine found by disassembling a story.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Routine {
+    /// The address of the routine's header.
+    pub addr: ByteAddress,
+    /// The number of local variables.
+    pub locals: usize,
+    /// The instructions that can be reached from the start of the routine, in address order.
+    pub instructions: Vec<Instruction>,
+}
+
+impl Instruction {
+    /// Returns whether execution can carry on to the next instruction, which it can't after
+    /// returning, jumping, quitting or restarting.
+    pub fn falls_through(&self) -> bool {
+        !matches!(
+            self.name,
+            "rtrue"
+                | "rfalse"
+                | "print_ret"
+                | "restart"
+                | "ret_popped"
+                | "quit"
+                | "ret"
+                | "jump"
+                | "throw"
+        )
+    }
+}
+
+impl Routine {
+    /// Returns the address just past the routine's last instruction.
+    pub fn end(&self) -> ByteAddress {
+        self.instructions
+            .iter()
+            .map(|instruction| instruction.next)
+            .max()
+            .unwrap_or(self.addr)
+    }
+}
+
+/// What an opcode does, besides taking operands.
+#[derive(Debug, Copy, Clone)]
+struct OpcodeInfo {
+    name: &'static str,
+    store: bool,
+    branch: bool,
+    text: bool,
+}
+
+impl OpcodeInfo {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            store: false,
+            branch: false,
+            text: false,
+        }
+    }
+    fn store(self) -> Self {
+        Self {
+            store: true,
+            ..self
+        }
+    }
+    fn branch(self) -> Self {
+        Self {
+            branch: true,
+            ..self
+        }
+    }
+    fn text(self) -> Self {
+        Self { text: true, ..self }
+    }
+}
+
+/// Returns what an opcode does in a particular version, or [`None`] if it doesn't exist.
+fn opcode_info(version: Version, opcode: &DecodedOpcode) -> Option<OpcodeInfo> {
+    let info = OpcodeInfo::new;
+    let v = |min: Version, info: OpcodeInfo| Some(info).filter(|_| version >= min);
+    let early = version <= Version::V3;
+    match opcode.desc {
+        OperandsDesc::Op0 => match opcode.opcode {
+            op0::rtrue => Some(info("rtrue")),
+            op0::rfalse => Some(info("rfalse")),
+            op0::print => Some(info("print").text()),
+            op0::print_ret => Some(info("print_ret").text()),
+            op0::nop => Some(info("nop")),
+            op0::save if early => Some(info("save").branch()),
+            op0::save if version == Version::V4 => Some(info("save").store()),
+            op0::restore if early => Some(info("restore").branch()),
+            op0::restore if version == Version::V4 => Some(info("restore").store()),
+            op0::restart => Some(info("restart")),
+            op0::ret_popped => Some(info("ret_popped")),
+            op0::pop if version <= Version::V4 => Some(info("pop")),
+            op0::catch => Some(info("catch").store()),
+            op0::quit => Some(info("quit")),
+            op0::new_line => Some(info("new_line")),
+            op0::show_status => Some(info("show_status")),
+            op0::verify => Some(info("verify").branch()),
+            op0::piracy => v(Version::V5, info("piracy").branch()),
+            _ => None,
+        },
+        OperandsDesc::Op1 => match opcode.opcode {
+            op1::jz => Some(info("jz").branch()),
+            op1::get_sibling => Some(info("get_sibling").store().branch()),
+            op1::get_child => Some(info("get_child").store().branch()),
+            op1::get_parent => Some(info("get_parent").store()),
+            op1::get_prop_len => Some(info("get_prop_len").store()),
+            op1::inc => Some(info("inc")),
+            op1::dec => Some(info("dec")),
+            op1::print_addr => Some(info("print_addr")),
+            op1::call_1s => v(Version::V4, info("call_1s").store()),
+            op1::remove_obj => Some(info("remove_obj")),
+            op1::print_obj => Some(info("print_obj")),
+            op1::ret => Some(info("ret")),
+            op1::jump => Some(info("jump")),
+            op1::print_paddr => Some(info("print_paddr")),
+            op1::load => Some(info("load").store()),
+            op1::not if version <= Version::V4 => Some(info("not").store()),
+            op1::call_1n => Some(info("call_1n")),
+            _ => None,
+        },
+        OperandsDesc::Op2 => match opcode.opcode {
+            op2::je => Some(info("je").branch()),
+            op2::jl => Some(info("jl").branch()),
+            op2::jg => Some(info("jg").branch()),
+            op2::dec_chk => Some(info("dec_chk").branch()),
+            op2::inc_chk => Some(info("inc_chk").branch()),
+            op2::jin => Some(info("jin").branch()),
+            op2::test => Some(info("test").branch()),
+            op2::or => Some(info("or").store()),
+            op2::and => Some(info("and").store()),
+            op2::test_attr => Some(info("test_attr").branch()),
+            op2::set_attr => Some(info("set_attr")),
+            op2::clear_attr => Some(info("clear_attr")),
+            op2::store => Some(info("store")),
+            op2::insert_obj => Some(info("insert_obj")),
+            op2::loadw => Some(info("loadw").store()),
+            op2::loadb => Some(info("loadb").store()),
+            op2::get_prop => Some(info("get_prop").store()),
+            op2::get_prop_addr => Some(info("get_prop_addr").store()),
+            op2::get_next_prop => Some(info("get_next_prop").store()),
+            op2::add => Some(info("add").store()),
+            op2::sub => Some(info("sub").store()),
+            op2::mul => Some(info("mul").store()),
+            op2::div => Some(info("div").store()),
+            op2::_mod => Some(info("mod").store()),
+            op2::call_2s => v(Version::V4, info("call_2s").store()),
+            op2::call_2n => v(Version::V5, info("call_2n")),
+            op2::set_color => v(Version::V5, info("set_colour")),
+            op2::throw => v(Version::V5, info("throw")),
+            _ => None,
+        },
+        OperandsDesc::Var if opcode.form == OpcodeForm::Extended => match opcode.opcode {
+            ext::save => Some(info("save").store()),
+            ext::restore => Some(info("restore").store()),
+            ext::log_shift => Some(info("log_shift").store()),
+            ext::art_shift => Some(info("art_shift").store()),
+            ext::set_font => Some(info("set_font").store()),
+            ext::draw_picture => Some(info("draw_picture")),
+            ext::picture_data => Some(info("picture_data").branch()),
+            ext::erase_picture => Some(info("erase_picture")),
+            ext::set_margins => Some(info("set_margins")),
+            ext::save_undo => Some(info("save_undo").store()),
+            ext::restore_undo => Some(info("restore_undo").store()),
+            ext::print_unicode => Some(info("print_unicode")),
+            ext::check_unicode => Some(info("check_unicode").store()),
+            ext::set_true_color => Some(info("set_true_colour")),
+            ext::move_window => Some(info("move_window")),
+            ext::window_size => Some(info("window_size")),
+            ext::window_style => Some(info("window_style")),
+            ext::get_wind_prop => Some(info("get_wind_prop").store()),
+            ext::scroll_window => Some(info("scroll_window")),
+            ext::pop_stack => Some(info("pop_stack")),
+            ext::read_mouse => Some(info("read_mouse")),
+            ext::mouse_window => Some(info("mouse_window")),
+            ext::push_stack => Some(info("push_stack").branch()),
+            ext::put_wind_prop => Some(info("put_wind_prop")),
+            ext::print_form => Some(info("print_form")),
+            ext::make_menu => Some(info("make_menu").branch()),
+            ext::picture_table => Some(info("picture_table")),
+            ext::buffer_screen => Some(info("buffer_screen").store()),
+            _ => None,
+        },
+        OperandsDesc::Var => match opcode.opcode {
+            var::call_vs => Some(info(if early { "call" } else { "call_vs" }).store()),
+            var::storew => Some(info("storew")),
+            var::storeb => Some(info("storeb")),
+            var::put_prop => Some(info("put_prop")),
+            var::aread if version >= Version::V5 => Some(info("aread").store()),
+            var::sread => Some(info("sread")),
+            var::print_char => Some(info("print_char")),
+            var::print_num => Some(info("print_num")),
+            var::random => Some(info("random").store()),
+            var::push => Some(info("push")),
+            var::pull if version == Version::V6 => Some(info("pull").store()),
+            var::pull => Some(info("pull")),
+            var::split_window => v(Version::V3, info("split_window")),
+            var::set_window => v(Version::V3, info("set_window")),
+            var::call_vs2 => v(Version::V4, info("call_vs2").store()),
+            var::erase_window => v(Version::V4, info("erase_window")),
+            var::erase_line => v(Version::V4, info("erase_line")),
+            var::set_cursor => v(Version::V4, info("set_cursor")),
+            var::get_cursor => v(Version::V4, info("get_cursor")),
+            var::set_text_style => v(Version::V4, info("set_text_style")),
+            var::buffer_mode => v(Version::V4, info("buffer_mode")),
+            var::output_stream => v(Version::V3, info("output_stream")),
+            var::input_stream => v(Version::V3, info("input_stream")),
+            var::sound_effect => v(Version::V3, info("sound_effect")),
+            var::read_char => v(Version::V4, info("read_char").store()),
+            var::scan_table => v(Version::V4, info("scan_table").store().branch()),
+            var::not => v(Version::V5, info("not").store()),
+            var::call_vn => v(Version::V5, info("call_vn")),
+            var::call_vn2 => v(Version::V5, info("call_vn2")),
+            var::tokenize => v(Version::V5, info("tokenise")),
+            var::encode_text => v(Version::V5, info("encode_text")),
+            var::copy_table => v(Version::V5, info("copy_table")),
+            var::print_table => v(Version::V5, info("print_table")),
+            var::check_arg_count => v(Version::V5, info("check_arg_count").branch()),
+            _ => None,
+        },
+    }
+}
+
+/// Returns whether an instruction calls a routine given by its first operand.
+fn is_call(name: &str) -> bool {
+    name.starts_with("call")
+}
+
+/// A string found in a story, along with where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoryString {
+    /// The address of the Z-string.
+    pub addr: ByteAddress,
+    /// Where the string was found.
+    pub source: StringSource,
+    /// The decoded text.
+    pub text: String,
+}
+
+/// Where a [`StoryString`] was found.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StringSource {
+    /// An entry in the abbreviation table.
+    Abbreviation(ZStringAbbrv),
+    /// The short name of an object.
+    ObjectName(usize),
+    /// The text of a `print` or `print_ret` instruction at an address.
+    Inline(ByteAddress),
+    /// A string in high memory, referred to by a packed address.
+    Static,
+}
+
+impl ZMachine {
+    /// Decodes the instruction at an address. Returns an error if the opcode doesn't exist in the
+    /// story's version, or if the instruction runs past the end of the story.
+    pub fn decode_instruction(&self, addr: ByteAddress) -> Result<Instruction, ExecuteError> {
+        let invalid = |_| ExecuteError::InvalidInstructionFormat(addr.0);
+        let mut next = addr;
+        let opcode = self.decode_opcode(&mut next).map_err(invalid)?;
+        let info =
+            opcode_info(self.version(), &opcode).ok_or(ExecuteError::InvalidOpcode(self[addr]))?;
+        let store = if info.store {
+            Some(self.read_store(&mut next).map_err(invalid)?)
+        } else {
+            None
+        };
+        let branch = if info.branch {
+            let (on, offset) = self.read_branch(&mut next).map_err(invalid)?;
+            let target = match offset {
+                0 => BranchTarget::ReturnFalse,
+                1 => BranchTarget::ReturnTrue,
+                _ => BranchTarget::Address(
+                    jump_target(next, offset)
+                        .ok_or(ExecuteError::InvalidInstructionFormat(addr.0))?,
+                ),
+            };
+            Some(Branch { on, target })
+        } else {
+            None
+        };
+        let text = if info.text {
+            let text = next;
+            next = self
+                .checked_zstring_end(next)
+                .ok_or(ExecuteError::InvalidInstructionFormat(addr.0))?;
+            Some(text)
+        } else {
+            None
+        };
+        Ok(Instruction {
+            addr,
+            name: info.name,
+            operands: opcode.operands.to_vec(),
+            store,
+            branch,
+            text,
+            next,
+        })
+    }
+    /// Formats an instruction for people to read, e.g. `0x01234  add G00 #02 -> sp`. Variables
+    /// and called routines are named from the [debugging information](ZMachine::set_debug_info)
+    /// if it's loaded.
+    pub fn format_instruction(&self, instruction: &Instruction) -> String {
+        let mut text = format!("{:#07x}  {}", instruction.addr.0, instruction.name);
+        let call = instruction.name.starts_with("call");
+        for (x, operand) in instruction.operands.iter().enumerate() {
+            if let (Operand::LargeConstant(packed), Some(info)) = (*operand, self.debug_info()) {
+                let addr = self.resolve_packed_address(packed as usize, true);
+                match info.routine_at(addr) {
+                    Some(routine) if x == 0 && call && routine.addr == addr => {
+                        text.push(' ');
+                        text.push_str(&routine.name);
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            match *operand {
+                Operand::LargeConstant(value) => text.push_str(&format!(" #{:04x}", value)),
+                Operand::SmallConstant(value) => text.push_str(&format!(" #{:02x}", value)),
+                Operand::Variable(var) => {
+                    text.push(' ');
+                    text.push_str(&self.variable_name(var, instruction.addr));
+                }
+                Operand::Omitted => {}
+            }
+        }
+        if let Some(var) = instruction.store {
+            text.push_str(" -> ");
+            text.push_str(&self.variable_name(var, instruction.addr));
+        }
+        if let Some(branch) = instruction.branch {
+            text.push_str(if branch.on { " ?" } else { " ?~" });
+            match branch.target {
+                BranchTarget::ReturnFalse => text.push_str("rfalse"),
+                BranchTarget::ReturnTrue => text.push_str("rtrue"),
+                BranchTarget::Address(addr) => text.push_str(&format!("{:#x}", addr.0)),
+            }
+        }
+        if let Some(addr) = instruction.text {
+            text.push_str(&format!(" {:?}", self.read_zstring(addr).0));
+        }
+        text
+    }
+    /// Returns where an instruction can go next: the following instruction, and the targets of its
+    /// branch or jump.
+    fn successors(&self, instruction: &Instruction) -> Vec<ByteAddress> {
+        let mut successors = Vec::new();
+        if instruction.falls_through() {
+            successors.push(instruction.next);
+        }
+        if let Some(Branch {
+            target: BranchTarget::Address(target),
+            ..
+        }) = instruction.branch
+        {
+            successors.push(target);
+        }
+        if instruction.name == "jump" {
+            if let Some(&Operand::LargeConstant(offset)) = instruction.operands.first() {
+                successors.extend(jump_target(instruction.next, offset as i16));
+            }
+        }
+        successors
+    }
+    /// Disassembles the routine whose header is at an address, following every branch and jump.
+    /// Returns an error if any reachable instruction doesn't exist in the story's version.
+    pub fn disassemble_routine(&self, addr: ByteAddress) -> Result<Routine, ExecuteError> {
+        if addr.0 >= self.len_bytes() {
+            return Err(ExecuteError::InvalidInstructionFormat(addr.0));
+        }
+        let locals = self[addr] as usize;
+        if locals > 15 {
+            return Err(ExecuteError::InvalidInstructionFormat(addr.0));
+        }
+        let start = if self.version() < Version::V5 {
+            addr + 1 + locals * 2
+        } else {
+            addr + 1
+        };
+        let instructions = self.disassemble_from(start)?;
+        Ok(Routine {
+            addr,
+            locals,
+            instructions,
+        })
+    }
+    /// Disassembles the code reachable from an instruction, without leaving the routine.
+    fn disassemble_from(&self, start: ByteAddress) -> Result<Vec<Instruction>, ExecuteError> {
+        let mut instructions = BTreeMap::new();
+        let mut pending = vec![start];
+        while let Some(addr) = pending.pop() {
+            if instructions.contains_key(&addr) {
+                continue;
+            }
+            if addr.0 >= self.len_bytes() {
+                return Err(ExecuteError::InvalidInstructionFormat(addr.0));
+            }
+            let instruction = self.decode_instruction(addr)?;
+            if instruction.next.0 > self.len_bytes() {
+                return Err(ExecuteError::InvalidInstructionFormat(addr.0));
+            }
+            pending.extend(self.successors(&instruction));
+            instructions.insert(addr, instruction);
+        }
+        Ok(instructions.into_values().collect())
+    }
+    /// Disassembles every routine that can be found in the story, in address order.
+    ///
+    /// Routines are found by following calls from the start of the story, then by looking for more
+    /// routines packed after those, since routines called indirectly (e.g. through properties)
+    /// can't be found by following calls. The code of the initial routine before version 6, which
+    /// has no header, is returned as a routine with its address set to its first instruction.
+    pub fn disassemble(&self) -> Vec<Routine> {
+        let mut routines = BTreeMap::new();
+        let initial = self.word(ByteAddress::INITIAL_PC_LOCATION);
+        let mut pending = Vec::new();
+        if self.version() == Version::V6 {
+            pending.push(self.resolve_packed_address(initial as usize, true));
+        } else {
+            let start = ByteAddress::from(initial);
+            if let Ok(instructions) = self.disassemble_from(start) {
+                let routine = Routine {
+                    addr: start,
+                    locals: 0,
+                    instructions,
+                };
+                pending.extend(self.called_routines(&routine));
+                routines.insert(start, routine);
+            }
+        }
+        loop {
+            while let Some(addr) = pending.pop() {
+                if routines.contains_key(&addr) {
+                    continue;
+                }
+                if let Ok(routine) = self.disassemble_routine(addr) {
+                    pending.extend(self.called_routines(&routine));
+                    routines.insert(addr, routine);
+                }
+            }
+            // look for a routine straight after the last one found
+            let end = match routines.values().map(Routine::end).max() {
+                Some(end) => end,
+                None => break,
+            };
+            let next = self.align_packed(end);
+            match self.disassemble_routine(next) {
+                Ok(routine) if !routine.instructions.is_empty() => pending.push(next),
+                _ => break,
+            }
+        }
+        routines.into_values().collect()
+    }
+    /// Returns the addresses of the routines that a routine calls directly.
+    fn called_routines(&self, routine: &Routine) -> Vec<ByteAddress> {
+        routine
+            .instructions
+            .iter()
+            .filter(|instruction| is_call(instruction.name))
+            .filter_map(|instruction| match instruction.operands.first() {
+                Some(&Operand::LargeConstant(packed)) if packed != 0 => {
+                    Some(self.resolve_packed_address(packed as usize, true))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+    /// Rounds an address up to the next one that a packed address can refer to.
+    fn align_packed(&self, addr: ByteAddress) -> ByteAddress {
+        let align = match self.version() {
+            Version::V1 | Version::V2 | Version::V3 => 2,
+            Version::V4 | Version::V5 | Version::V6 | Version::V7 => 4,
+            Version::V8 => 8,
+        };
+        ByteAddress(addr.0.div_ceil(align) * align)
+    }
+    /// Returns every string that can be found in the story, in address order: the abbreviations,
+    /// object names, the inline text of `print` and `print_ret` instructions, and the strings in
+    /// high memory after the code.
+    ///
+    /// Static strings are found by decoding everything after the last routine that
+    /// [`disassemble`](ZMachine::disassemble) finds, plus any strings printed with `print_paddr`
+    /// that are elsewhere.
+    pub fn strings(&self) -> Vec<StoryString> {
+        let mut found = BTreeMap::new();
+        if self.version() >= Version::V2 {
+            for idx in 0..self.zstring_encoder().max_abbreviations() {
+                let abbrv = ZStringAbbrv::new(idx as u8).unwrap();
+                let addr = self.abbreviation_location(abbrv);
+                // unused entries are left as 0
+                if addr == ByteAddress::ZERO {
+                    continue;
+                }
+                found
+                    .entry(addr)
+                    .or_insert(StringSource::Abbreviation(abbrv));
+            }
+        }
+        for id in 1..=self.objects_count() {
+            if let Some(addr) = self.object_unchecked(id).name_location() {
+                found.entry(addr).or_insert(StringSource::ObjectName(id));
+            }
+        }
+        let routines = self.disassemble();
+        let mut packed = BTreeSet::new();
+        for instruction in routines.iter().flat_map(|routine| &routine.instructions) {
+            if let Some(text) = instruction.text {
+                found.insert(text, StringSource::Inline(instruction.addr));
+            }
+            if instruction.name == "print_paddr" {
+                if let Some(&Operand::LargeConstant(addr)) = instruction.operands.first() {
+                    packed.insert(self.resolve_packed_address(addr as usize, false));
+                }
+            }
+        }
+        let code_end = routines
+            .iter()
+            .map(Routine::end)
+            .max()
+            .unwrap_or_else(|| self.high_memory_range().start);
+        let mut addr = self.align_packed(code_end);
+        while addr.0 + 2 <= self.len_bytes() {
+            let end = match self.checked_zstring_end(addr) {
+                Some(end) => end,
+                None => break,
+            };
+            packed.insert(addr);
+            addr = self.align_packed(end);
+        }
+        for addr in packed {
+            if self.checked_zstring_end(addr).is_some() {
+                found.entry(addr).or_insert(StringSource::Static);
+            }
+        }
+        found
+            .into_iter()
+            .map(|(addr, source)| StoryString {
+                addr,
+                source,
+                text: self.read_zstring(addr).0,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,220 @@
+use crate::*;
+
+/// Encodes text as Z-strings, the reverse of [`ZMachine::read_zstring`].
+///
+/// Characters are taken from the alphabet where possible, shifting between alphabets as the
+/// version allows: versions 1 and 2 lock onto another alphabet for runs of characters in it, while
+/// later versions shift for one character at a time. Other characters are written as 10-bit ZSCII
+/// escapes, and characters with no ZSCII code at all are written as `?`.
+#[derive(Debug, Clone)]
+pub struct ZStringEncoder<'a> {
+    version: Version,
+    alphabet: Alphabet<'a>,
+    unicode_table: UnicodeTable<'a>,
+    abbreviations: Vec<(String, ZStringAbbrv)>,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Token {
+    Space,
+    Newline,
+    Abbreviation(ZStringAbbrv),
+    Letter(AlphabetMode, u8),
+    Escape(u16),
+}
+
+impl Token {
+    fn mode(self) -> Option<AlphabetMode> {
+        match self {
+            Token::Letter(mode, _) => Some(mode),
+            Token::Escape(_) => Some(AlphabetMode::Symbol),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> ZStringEncoder<'a> {
+    /// Creates an encoder for a particular version, alphabet and Unicode table, which doesn't use
+    /// abbreviations.
+    pub fn new(version: Version, alphabet: Alphabet<'a>, unicode_table: UnicodeTable<'a>) -> Self {
+        Self {
+            version,
+            alphabet,
+            unicode_table,
+            abbreviations: Vec::new(),
+        }
+    }
+    /// Sets the abbreviations to substitute into encoded text. The longest abbreviation that
+    /// matches is used. Abbreviations that aren't valid for the version are ignored, as are empty
+    /// ones.
+    pub fn abbreviations(
+        mut self,
+        abbreviations: impl IntoIterator<Item = (ZStringAbbrv, String)>,
+    ) -> Self {
+        let max = self.max_abbreviations() as u8;
+        self.abbreviations = abbreviations
+            .into_iter()
+            .filter(|(abbrv, text)| abbrv.idx() < max && !text.is_empty())
+            .map(|(abbrv, text)| (text, abbrv))
+            .collect();
+        self.abbreviations
+            .sort_by_key(|(text, _)| std::cmp::Reverse(text.len()));
+        self
+    }
+    /// Returns the number of abbreviations the version allows.
+    pub fn max_abbreviations(&self) -> usize {
+        match self.version {
+            Version::V1 => 0,
+            Version::V2 => 32,
+            _ => 96,
+        }
+    }
+    /// Converts a `char` to ZSCII, or returns [`None`] if it has no ZSCII code.
+    fn zscii(&self, ch: char) -> Option<u16> {
+        match ch {
+            '\n' => Some(13),
+            ' '..='~' => Some(ch as u16),
+            _ => self.unicode_table.char_to_zscii(ch).map(u16::from),
+        }
+    }
+    fn letter(&self, zscii: u16) -> Option<Token> {
+        // the first two symbols after version 1 are the escape and newline codes
+        let reserved = if self.version == Version::V1 { 1 } else { 2 };
+        for &mode in &AlphabetMode::VALUES {
+            let skip = if mode == AlphabetMode::Symbol {
+                reserved
+            } else {
+                0
+            };
+            let table = self.alphabet.table(mode);
+            if let Some(idx) = (skip..26).find(|&idx| table[idx] as u16 == zscii) {
+                return Some(Token::Letter(mode, idx as u8 + 6));
+            }
+        }
+        None
+    }
+    fn tokens(&self, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(ch) = rest.chars().next() {
+            if let Some((abbrv_text, abbrv)) = self
+                .abbreviations
+                .iter()
+                .find(|(abbrv_text, _)| rest.starts_with(&abbrv_text[..]))
+            {
+                tokens.push(Token::Abbreviation(*abbrv));
+                rest = &rest[abbrv_text.len()..];
+                continue;
+            }
+            rest = &rest[ch.len_utf8()..];
+            let zscii = self.zscii(ch).unwrap_or(b'?' as u16);
+            tokens.push(match zscii {
+                32 => Token::Space,
+                13 if self.version == Version::V1 => Token::Newline,
+                13 => Token::Letter(AlphabetMode::Symbol, 7),
+                _ => self.letter(zscii).unwrap_or(Token::Escape(zscii)),
+            });
+        }
+        tokens
+    }
+    /// Encodes text as a sequence of unpacked Z-characters.
+    pub fn encode_zchars(&self, text: &str) -> Vec<u8> {
+        let tokens = self.tokens(text);
+        let early = self.version <= Version::V2;
+        let mut zchars = Vec::with_capacity(tokens.len() * 2);
+        let mut lock = AlphabetMode::Lowercase;
+        for (x, &token) in tokens.iter().enumerate() {
+            if let Some(mode) = token.mode() {
+                if mode != lock {
+                    let up = rotate_up(lock) == mode;
+                    if !early {
+                        zchars.push(if up { 4 } else { 5 });
+                    } else if tokens.get(x + 1).and_then(|token| token.mode()) == Some(mode) {
+                        zchars.push(if up { 4 } else { 5 });
+                        lock = mode;
+                    } else {
+                        zchars.push(if up { 2 } else { 3 });
+                    }
+                }
+            }
+            match token {
+                Token::Space => zchars.push(0),
+                Token::Newline => zchars.push(1),
+                Token::Abbreviation(abbrv) => {
+                    zchars.push(abbrv.idx() / 32 + 1);
+                    zchars.push(abbrv.idx() % 32);
+                }
+                Token::Letter(_, zchar) => zchars.push(zchar),
+                Token::Escape(zscii) => {
+                    zchars.push(6);
+                    zchars.push((zscii >> 5) as u8 & 0b11111);
+                    zchars.push(zscii as u8 & 0b11111);
+                }
+            }
+        }
+        zchars
+    }
+    /// Encodes text as a Z-string, packed into words with the end bit set on the last one.
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        pack_zchars(&self.encode_zchars(text))
+    }
+    /// Encodes a word as it is stored in the dictionary: truncated or padded to 6 Z-characters (9
+    /// from version 4) and without abbreviations.
+    pub fn encode_dictionary_word(&self, text: &str) -> Vec<u8> {
+        let len = if self.version <= Version::V3 { 6 } else { 9 };
+        let encoder = Self::new(self.version, self.alphabet, self.unicode_table);
+        let mut zchars = encoder.encode_zchars(text);
+        zchars.resize(len, 5);
+        pack_zchars(&zchars)
+    }
+}
+
+fn rotate_up(mode: AlphabetMode) -> AlphabetMode {
+    match mode {
+        AlphabetMode::Lowercase => AlphabetMode::Uppercase,
+        AlphabetMode::Uppercase => AlphabetMode::Symbol,
+        AlphabetMode::Symbol => AlphabetMode::Lowercase,
+    }
+}
+
+/// Packs Z-characters three to a word, setting the end bit on the last word. The last word is
+/// padded with 5s, and nothing packs to one word of padding.
+pub fn pack_zchars(zchars: &[u8]) -> Vec<u8> {
+    let words = zchars.len().div_ceil(3).max(1);
+    let mut bytes = Vec::with_capacity(words * 2);
+    for x in 0..words {
+        let zchar = |i: usize| zchars.get(x * 3 + i).copied().unwrap_or(5) as u16 & 0b11111;
+        let end = if x + 1 == words { 0x8000 } else { 0 };
+        let word = end | zchar(0) << 10 | zchar(1) << 5 | zchar(2);
+        bytes.extend_from_slice(&word.to_be_bytes());
+    }
+    bytes
+}
+
+impl ZMachine {
+    /// Returns an encoder using this story's alphabet and Unicode table, which doesn't use
+    /// abbreviations.
+    pub fn zstring_encoder(&self) -> ZStringEncoder<'_> {
+        ZStringEncoder::new(self.version(), self.alphabet(), self.unicode_table())
+    }
+    /// Returns the abbreviations in this story's abbreviation table.
+    pub fn abbreviations(&self) -> Vec<(ZStringAbbrv, String)> {
+        let count = self.zstring_encoder().max_abbreviations() as u8;
+        (0..count)
+            .map(|idx| {
+                let abbrv = ZStringAbbrv::new(idx).unwrap();
+                (abbrv, self.read_abbrvd_zstring(abbrv))
+            })
+            .collect()
+    }
+    /// Encodes text as a Z-string for this story, optionally substituting the story's
+    /// abbreviations.
+    pub fn encode_zstring(&self, text: &str, abbreviate: bool) -> Vec<u8> {
+        let encoder = self.zstring_encoder();
+        if abbreviate {
+            encoder.abbreviations(self.abbreviations()).encode(text)
+        } else {
+            encoder.encode(text)
+        }
+    }
+}
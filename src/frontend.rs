@@ -0,0 +1,297 @@
+use crate::*;
+use std::mem;
+
+/// The screen and keyboard that a story is played through.
+pub trait Frontend {
+    /// Prints text to the screen. Newlines are printed as `'\n'`.
+    fn print(&mut self, text: &str);
+    /// Reads a line of input from the keyboard, of at most `max_len` characters. Returns [`None`]
+    /// if there is no more input, which stops the story.
+    fn read_line(&mut self, max_len: usize) -> Option<String>;
+    /// Reads a single key press. `'\n'` is the return key, `'\u{8}'` delete and `'\u{1b}'`
+    /// escape. Returns [`None`] if there is no more input, which stops the story.
+    fn read_char(&mut self) -> Option<char> {
+        self.read_line(1)
+            .map(|line| line.chars().next().unwrap_or('\n'))
+    }
+    /// Reports that the story did something dubious that the interpreter could carry on from.
+    fn warn(&mut self, _message: &str) {}
+    /// Shows the status line of a version 1-3 story.
+    fn show_status(&mut self, _status: &Status) {}
+    /// Returns whether a character can be printed to the screen.
+    fn can_print(&self, _ch: char) -> bool {
+        true
+    }
+    /// Returns whether a character can be typed on the keyboard.
+    fn can_read(&self, _ch: char) -> bool {
+        true
+    }
+    /// Splits the screen, giving the upper window a number of lines. 0 unsplits it.
+    fn split_window(&mut self, _lines: u16) {}
+    /// Selects the window that text is printed to.
+    fn set_window(&mut self, _window: Window) {}
+    /// Clears a window.
+    fn erase_window(&mut self, _window: Window) {}
+    /// Clears the current window from the cursor to the end of its line.
+    fn erase_line(&mut self) {}
+    /// Moves the upper window's cursor, to a line and column counted from 1.
+    fn set_cursor(&mut self, _line: u16, _column: u16) {}
+    /// Sets the style that text is printed in.
+    fn set_text_style(&mut self, _style: TextStyle) {}
+    /// Sets whether text in the lower window is buffered so that it can be word-wrapped.
+    fn set_buffered(&mut self, _buffered: bool) {}
+    /// Sets the text colors, as Z-machine color numbers from 1 (the default) to 12.
+    fn set_colors(&mut self, _foreground: u16, _background: u16) {}
+    /// Sets the text colors as 15-bit true colors, where -1 (`0xFFFF`) is the default and -2 keeps
+    /// the current color.
+    fn set_true_colors(&mut self, _foreground: u16, _background: u16) {}
+    /// Returns whether a font can be used: 1 is the normal font, 3 the character graphics font and
+    /// 4 a fixed-pitch font.
+    fn font_available(&self, font: u16) -> bool {
+        font == 1 || font == 4
+    }
+    /// Sets the font that text is printed in. Only called with fonts that are available.
+    fn set_font(&mut self, _font: u16) {}
+}
+
+/// The contents of a version 1-3 status line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    /// The name of the player's location.
+    pub location: String,
+    /// What to show on the right-hand side.
+    pub progress: StatusProgress,
+}
+
+/// The right-hand side of a status line, as chosen by [`ZMachine::status_line`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StatusProgress {
+    /// The score and the number of turns taken.
+    ScoreTurns(i16, u16),
+    /// The time of day, as hours (0-23) and minutes.
+    HoursMins(u16, u16),
+}
+
+impl ZMachine {
+    /// Sets the frontend used for text input and output.
+    pub fn set_frontend(&mut self, frontend: impl Frontend + Send + 'static) {
+        self.frontend = Some(Box::new(frontend));
+    }
+    /// Prints a string to the screen.
+    pub fn print(&mut self, string: &str) {
+        if self.print_to_memory(string) || !self.streams.screen {
+            return;
+        }
+        self.advance_cursor(string);
+        if let Some(text) = &mut self.captured_text {
+            text.push_str(string);
+        } else if let Some(frontend) = &mut self.frontend {
+            frontend.print(string);
+        }
+    }
+    /// Prints a Z-string to the screen, reusing a buffer rather than allocating each time.
+    pub fn print_zstring(&mut self, addr: ByteAddress) {
+        let mut buffer = mem::take(&mut self.print_buffer);
+        buffer.clear();
+        buffer.extend(self.zstring_chars(addr));
+        self.print(&buffer);
+        self.print_buffer = buffer;
+    }
+    /// Prints a newline to the screen.
+    pub fn print_newline(&mut self) {
+        self.print("\n");
+    }
+    /// Prints a ZSCII character, as `print_char` does. Returns an error if the character isn't
+    /// defined for output, and warns if it is only defined in version 6.
+    pub fn print_zscii(&mut self, zscii: u16) -> Result<(), ExecuteError> {
+        let output = self.zscii_output(zscii);
+        match output {
+            ZsciiOutput::Invalid => return Err(ExecuteError::InvalidZscii(zscii)),
+            ZsciiOutput::Tab | ZsciiOutput::SentenceSpace if self.version() != Version::V6 => {
+                self.warn(&format!(
+                    "ZSCII {} is only defined for output in version 6",
+                    zscii
+                ));
+            }
+            _ => {}
+        }
+        if let Some(ch) = output.to_char() {
+            let mut buf = [0; 4];
+            self.print(ch.encode_utf8(&mut buf));
+        }
+        Ok(())
+    }
+    /// Passes a warning to the frontend.
+    pub(crate) fn warn(&mut self, message: &str) {
+        if let Some(frontend) = &mut self.frontend {
+            frontend.warn(message);
+        }
+    }
+    /// Returns the current status line, or [`None`] if the story is version 4 or later.
+    pub fn status(&self) -> Option<Status> {
+        let progress = match self.status_line()? {
+            StatusLine::ScoreTurns => {
+                StatusProgress::ScoreTurns(self.peek_variable(17) as i16, self.peek_variable(18))
+            }
+            StatusLine::HoursMins => {
+                StatusProgress::HoursMins(self.peek_variable(17), self.peek_variable(18))
+            }
+        };
+        let location = match self.peek_variable(16) {
+            0 => String::new(),
+            obj => {
+                let mut name = String::new();
+                self.object_unchecked(obj as usize).copy_name(&mut name);
+                name
+            }
+        };
+        Some(Status { location, progress })
+    }
+    /// Updates the status line without waiting for keyboard input.
+    pub fn update_status_line(&mut self) {
+        if let Some(status) = self.status() {
+            if let Some(frontend) = &mut self.frontend {
+                frontend.show_status(&status);
+            }
+        }
+    }
+    /// Returns whether the frontend can print and read a character, as the `check_unicode` opcode
+    /// does: bit 0 is set if it can be printed and bit 1 if it can be read. Characters can only be
+    /// read if they have a ZSCII code.
+    pub fn check_unicode(&self, ch: char) -> u16 {
+        let frontend = match &self.frontend {
+            Some(frontend) => frontend,
+            None => return 0,
+        };
+        let print = frontend.can_print(ch) as u16;
+        let read = (frontend.can_read(ch) && self.char_zscii(ch).is_some()) as u16;
+        print | read << 1
+    }
+    /// Reads a line of input into a text buffer, as `sread` and `aread` do, then tokenises it into
+    /// the parse buffer if there is one. Characters are lowercased, and those with no ZSCII code
+    /// are dropped. Returns the ZSCII code of the key that ended input, or [`None`] if there was no
+    /// more input.
+    pub(crate) fn read_line(&mut self, text: ByteAddress, parse: ByteAddress) -> Option<u8> {
+        if self.version() <= Version::V3 {
+            self.update_status_line();
+        }
+        let late = self.version() >= Version::V5;
+        let max_len = if late {
+            self[text] as usize
+        } else {
+            (self[text] as usize).saturating_sub(1)
+        };
+        let line = if self.suspend.enabled {
+            self.take_input_line()?
+        } else {
+            self.frontend.as_mut()?.read_line(max_len)?
+        };
+        let zscii: Vec<u8> = line
+            .chars()
+            .flat_map(char::to_lowercase)
+            .filter_map(|ch| self.char_zscii(ch))
+            .filter(|&zscii| zscii != 13)
+            .take(max_len)
+            .collect();
+        let start = if late { text + 2 } else { text + 1 };
+        for (x, &byte) in zscii.iter().enumerate() {
+            self.write_byte(start + x, byte);
+        }
+        if late {
+            self.write_byte(text + 1, zscii.len() as u8);
+        } else {
+            self.write_byte(start + zscii.len(), 0);
+        }
+        if parse != ByteAddress::ZERO {
+            self.tokenise(text, parse);
+        }
+        Some(13)
+    }
+    /// Reads a single key press, as `read_char` does, returning its ZSCII code. Keys with no ZSCII
+    /// code are skipped.
+    pub(crate) fn read_key(&mut self) -> Option<u8> {
+        loop {
+            let ch = if self.suspend.enabled {
+                self.take_input_key()?
+            } else {
+                self.frontend.as_mut()?.read_char()?
+            };
+            let zscii = match ch {
+                '\u{8}' => Some(8),
+                '\u{1b}' => Some(27),
+                _ => self.char_zscii(ch),
+            };
+            if zscii.is_some() {
+                return zscii;
+            }
+        }
+    }
+    /// Splits the text in a text buffer into words and looks them up in the dictionary, writing
+    /// the results to a parse buffer.
+    pub(crate) fn tokenise(&mut self, text: ByteAddress, parse: ByteAddress) {
+        self.tokenise_with(text, parse, self.dictionary_base(), false);
+    }
+    /// Splits the text in a text buffer into words and looks them up in a dictionary, as the
+    /// `tokenise` opcode does. If `skip_unknown` is set, the parse buffer entries of words that
+    /// aren't in the dictionary are left alone.
+    pub(crate) fn tokenise_with(
+        &mut self,
+        text: ByteAddress,
+        parse: ByteAddress,
+        dictionary: ByteAddress,
+        skip_unknown: bool,
+    ) {
+        let (start, len) = if self.version() >= Version::V5 {
+            (text + 2, self[text + 1] as usize)
+        } else {
+            let start = text + 1;
+            let max = self[text] as usize;
+            let len = (0..max).position(|x| self[start + x] == 0).unwrap_or(max);
+            (start, len)
+        };
+        let chars = self[start..(start + len)].to_vec();
+        let separators = self.dictionary_separators(dictionary);
+        let mut words = Vec::new();
+        let mut word_start = None;
+        for (x, &ch) in chars.iter().enumerate() {
+            if ch == b' ' || separators.contains(&ch) {
+                if let Some(word_start) = word_start.take() {
+                    words.push((word_start, x));
+                }
+                if ch != b' ' {
+                    words.push((x, x + 1));
+                }
+            } else if word_start.is_none() {
+                word_start = Some(x);
+            }
+        }
+        if let Some(word_start) = word_start {
+            words.push((word_start, chars.len()));
+        }
+        words.truncate(self[parse] as usize);
+        let encoder = self.zstring_encoder();
+        let entries: Vec<_> = words
+            .iter()
+            .map(|&(word_start, word_end)| {
+                let word: String = chars[word_start..word_end]
+                    .iter()
+                    .map(|&zscii| self.zscii_char(zscii as u16).unwrap_or('?'))
+                    .collect();
+                let encoded = encoder.encode_dictionary_word(&word);
+                let entry = self.dictionary_lookup(dictionary, &encoded);
+                let position = (start - text) + word_start;
+                (entry, word_end - word_start, position)
+            })
+            .collect();
+        self.write_byte(parse + 1, entries.len() as u8);
+        for (x, &(entry, len, position)) in entries.iter().enumerate() {
+            if entry.is_none() && skip_unknown {
+                continue;
+            }
+            let block = parse + 2 + x * 4;
+            self.write_word(block, entry.map_or(0, |entry| entry.0 as Word));
+            self.write_byte(block + 2, len as u8);
+            self.write_byte(block + 3, position as u8);
+        }
+    }
+}
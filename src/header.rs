@@ -0,0 +1,740 @@
+use crate::*;
+
+impl ZMachine {
+    /// Returns a view of this story's header.
+    pub fn header(&self) -> Header<'_> {
+        Header { machine: self }
+    }
+}
+
+/// A view of a story's header.
+pub struct Header<'a> {
+    machine: &'a ZMachine,
+}
+
+impl<'a> Header<'a> {
+    /// Returns the version of the story.
+    pub fn version(&self) -> Version {
+        self.machine.version()
+    }
+    /// Returns the release number of the story.
+    pub fn release(&self) -> u16 {
+        self.machine.word(ByteAddress::RELEASE_NUMBER)
+    }
+    /// Returns the raw serial code of the story. This is conventionally six ASCII digits holding
+    /// the compilation date as `YYMMDD`.
+    pub fn serial(&self) -> [u8; 6] {
+        let mut serial = [0; 6];
+        serial.copy_from_slice(
+            &self.machine[ByteAddress::SERIAL_CODE..(ByteAddress::SERIAL_CODE + 6)],
+        );
+        serial
+    }
+    /// Returns the serial code of the story as a string, or [`None`] if it is not ASCII.
+    pub fn serial_str(&self) -> Option<&'a str> {
+        let bytes = &self.machine[ByteAddress::SERIAL_CODE..(ByteAddress::SERIAL_CODE + 6)];
+        if bytes.is_ascii() {
+            std::str::from_utf8(bytes).ok()
+        } else {
+            None
+        }
+    }
+    /// Returns the first set of header flags.
+    pub fn flags1(&self) -> Flags1 {
+        let z = self.machine;
+        if self.version() <= Version::V3 {
+            Flags1::Early {
+                status_line: if self.version() == Version::V3 && z[BitAddress::STATUS_LINE] {
+                    StatusLine::HoursMins
+                } else {
+                    StatusLine::ScoreTurns
+                },
+                two_disks: z[BitAddress::TWO_DISKS],
+                status_line_unavailable: z[BitAddress::STATUS_LINE_AVAILABLE],
+                screen_split_available: z[BitAddress::SCREEN_SPLIT_AVAILABLE],
+                variable_pitch_default: z[BitAddress::VARIABLE_PITCH_FONT_DEFAULT],
+            }
+        } else {
+            Flags1::Late {
+                colors_available: z[BitAddress::COLORS_AVAILABLE],
+                pictures_available: z[BitAddress::PICTURES_AVAILABLE],
+                bold_available: z[BitAddress::BOLD_AVAILABLE],
+                italic_available: z[BitAddress::ITALIC_AVAILABLE],
+                fixed_space_available: z[BitAddress::FIXED_SPACE_AVAILABLE],
+                sound_effects_available: z[BitAddress::SOUND_EFFECT_AVAILABLE],
+                timed_keyboard_available: z[BitAddress::TIMED_KEYBOARD_AVAILABLE],
+            }
+        }
+    }
+    /// Returns the second set of header flags.
+    pub fn flags2(&self) -> Flags2 {
+        let z = self.machine;
+        Flags2 {
+            transcripting: z[BitAddress::TRANSCRIPTING_ON],
+            force_fixed_pitch: z[BitAddress::FORCE_FIXED_PITCH],
+            redraw_requested: z[BitAddress::SCREEN_REDRAW_REQUESTED],
+            pictures_desired: z[BitAddress::PICTURES_DESIRED],
+            undo_desired: z[BitAddress::UNDO_DESIRED],
+            mouse_desired: z[BitAddress::MOUSE_DESIRED],
+            colors_desired: z[BitAddress::COLORS_DESIRED],
+            sounds_desired: z[BitAddress::SOUNDS_DESIRED],
+            menus_desired: z[BitAddress::MENUS_DESIRED],
+        }
+    }
+    /// Returns the base address of high memory.
+    pub fn high_memory_base(&self) -> ByteAddress {
+        self.machine.word(ByteAddress::HIGH_MEMORY_LOCATION).into()
+    }
+    /// Returns the initial value of the program counter. In version 6 this is instead the packed
+    /// address of the main routine.
+    pub fn initial_pc(&self) -> Word {
+        self.machine.word(ByteAddress::INITIAL_PC_LOCATION)
+    }
+    /// Returns the location of the dictionary.
+    pub fn dictionary_location(&self) -> ByteAddress {
+        self.machine.word(ByteAddress::DICTIONARY_LOCATION).into()
+    }
+    /// Returns the location of the object table.
+    pub fn object_table_location(&self) -> ByteAddress {
+        self.machine.word(ByteAddress::OBJECT_TABLE_LOCATION).into()
+    }
+    /// Returns the location of the global variable table.
+    pub fn global_variable_table_location(&self) -> ByteAddress {
+        self.machine
+            .word(ByteAddress::GLOBAL_VARIABLE_TABLE_LOCATION)
+            .into()
+    }
+    /// Returns the base address of static memory.
+    pub fn static_memory_base(&self) -> ByteAddress {
+        self.machine
+            .word(ByteAddress::STATIC_MEMORY_LOCATION)
+            .into()
+    }
+    /// Returns the location of the abbreviations table.
+    pub fn abbreviations_location(&self) -> ByteAddress {
+        self.machine
+            .word(ByteAddress::ABBREVIATIONS_LOCATION)
+            .into()
+    }
+    /// Returns the length of the story in bytes, as declared by the header. Some early stories
+    /// leave this as 0.
+    pub fn file_length(&self) -> usize {
+        let len = self.machine.word(ByteAddress::FILE_LENGTH) as usize;
+        len * match self.version() {
+            Version::V1 | Version::V2 | Version::V3 => 2,
+            Version::V4 | Version::V5 => 4,
+            Version::V6 | Version::V7 | Version::V8 => 8,
+        }
+    }
+    /// Returns the checksum declared by the header.
+    pub fn checksum(&self) -> u16 {
+        self.machine.word(ByteAddress::FILE_CHECKSUM)
+    }
+    /// Returns the interpreter number set by the interpreter.
+    pub fn interpreter_number(&self) -> InterpreterNumber {
+        InterpreterNumber::from_byte(self.machine[ByteAddress::INTERPRETER_NUMBER])
+    }
+    /// Returns the interpreter version set by the interpreter.
+    pub fn interpreter_version(&self) -> u8 {
+        self.machine[ByteAddress::INTERPRETER_VERSION]
+    }
+    /// Returns the screen metrics set by the interpreter.
+    pub fn screen_metrics(&self) -> ScreenMetrics {
+        let z = self.machine;
+        ScreenMetrics {
+            height_lines: z[ByteAddress::SCREEN_HEIGHT],
+            width_chars: z[ByteAddress::SCREEN_WIDTH_CHARS],
+            width_units: z.word(ByteAddress::SCREEN_WIDTH_UNITS),
+            height_units: z.word(ByteAddress::SCREEN_HEIGHT_UNITS),
+        }
+    }
+    /// Returns the font metrics set by the interpreter. The two fields are stored in the opposite
+    /// order in version 6.
+    pub fn font_metrics(&self) -> FontMetrics {
+        let z = self.machine;
+        if self.version() == Version::V6 {
+            FontMetrics {
+                width_units: z[ByteAddress::FONT_WIDTH_UNITS_V6],
+                height_units: z[ByteAddress::FONT_HEIGHT_UNITS_V6],
+            }
+        } else {
+            FontMetrics {
+                width_units: z[ByteAddress::FONT_WIDTH_UNITS_V5],
+                height_units: z[ByteAddress::FONT_HEIGHT_UNITS_V5],
+            }
+        }
+    }
+    /// Returns the offset of routines in versions 6 and 7, in units of 8 bytes.
+    pub fn routines_offset(&self) -> Word {
+        self.machine.word(ByteAddress::ROUTINES_OFFSET)
+    }
+    /// Returns the offset of static strings in versions 6 and 7, in units of 8 bytes.
+    pub fn strings_offset(&self) -> Word {
+        self.machine.word(ByteAddress::STATIC_STRINGS_OFFSET)
+    }
+    /// Returns the default background color.
+    pub fn default_background_color(&self) -> u8 {
+        self.machine[ByteAddress::DEFAULT_BACKGROUND_COLOR]
+    }
+    /// Returns the default foreground color.
+    pub fn default_foreground_color(&self) -> u8 {
+        self.machine[ByteAddress::DEFAULT_FOREGROUND_COLOR]
+    }
+    /// Returns the location of the terminating characters table, or [`None`] if there isn't one.
+    pub fn terminating_characters_location(&self) -> Option<ByteAddress> {
+        self.optional_location(ByteAddress::TERMINATING_CHARACTERS_TABLE_LOCATION)
+    }
+    /// Returns the total width in pixels of text sent to output stream 3.
+    pub fn output_stream_3_width(&self) -> Word {
+        self.machine
+            .word(ByteAddress::OUTPUT_STREAM_3_WIDTH_TOTAL_PIXELS)
+    }
+    /// Returns the revision of the Z-Machine Standard that the interpreter claims to follow, as
+    /// `(major, minor)`. `(0, 0)` means that the interpreter does not claim to follow it.
+    pub fn standard_revision(&self) -> (u8, u8) {
+        (
+            self.machine[ByteAddress::STANDARD_REVISION_MAJOR],
+            self.machine[ByteAddress::STANDARD_REVISION_MINOR],
+        )
+    }
+    /// Returns the location of the alphabet table, or [`None`] if the default alphabet is used.
+    pub fn alphabet_table_location(&self) -> Option<ByteAddress> {
+        if self.version() < Version::V5 {
+            None
+        } else {
+            self.optional_location(ByteAddress::ALPHABET_TABLE_ADDRESS)
+        }
+    }
+    /// Returns the location of the header extension table, or [`None`] if there isn't one.
+    pub fn extension_table_location(&self) -> Option<ByteAddress> {
+        if self.version() < Version::V5 {
+            None
+        } else {
+            self.optional_location(ByteAddress::HEADER_EXTENSION_TABLE_ADDRESS)
+        }
+    }
+    /// Returns the header extension table, or [`None`] if there isn't one.
+    pub fn extension(&self) -> Option<HeaderExtension<'a>> {
+        let base = self.extension_table_location()?;
+        if base + 1 >= ByteAddress(self.machine.len_bytes()) {
+            return None;
+        }
+        Some(HeaderExtension {
+            base,
+            machine: self.machine,
+        })
+    }
+    fn optional_location(&self, addr: ByteAddress) -> Option<ByteAddress> {
+        let word = self.machine.word(addr);
+        if word == 0 {
+            None
+        } else {
+            Some(word.into())
+        }
+    }
+}
+
+impl ZMachine {
+    /// Returns the header extension table, or [`None`] if the story doesn't have one.
+    pub fn header_extension(&self) -> Option<HeaderExtension<'_>> {
+        self.header().extension()
+    }
+    /// Writes a word into the header extension table at a byte offset from its start. Returns
+    /// false and writes nothing if the story's table is too short to contain that word.
+    pub fn write_header_extension_word(&mut self, offset: usize, word: Word) -> bool {
+        let addr = match self.header_extension() {
+            Some(ext) if ext.contains(offset) => ext.base + offset,
+            _ => return false,
+        };
+        self.write_word(addr, word);
+        true
+    }
+    /// Records the position of the mouse, usually after `read_mouse`. Ignored if the story's
+    /// header extension table is too short.
+    pub fn set_mouse_position(&mut self, x: Word, y: Word) {
+        self.write_header_extension_word(ByteAddress::HEADER_EXT_MOUSE_X, x);
+        self.write_header_extension_word(ByteAddress::HEADER_EXT_MOUSE_Y, y);
+    }
+    /// Records the true default colors, as 15-bit colors. Ignored if the story's header extension
+    /// table is too short.
+    pub fn set_true_default_colors(&mut self, foreground: Word, background: Word) {
+        self.write_header_extension_word(
+            ByteAddress::HEADER_EXT_DEFAULT_FOREGROUND_COLOR,
+            foreground,
+        );
+        self.write_header_extension_word(
+            ByteAddress::HEADER_EXT_DEFAULT_BACKGROUND_COLOR,
+            background,
+        );
+    }
+    /// Clears the story's request for transparency if the interpreter can't support it. Ignored if
+    /// the story's header extension table is too short.
+    pub fn set_transparency_available(&mut self, available: bool) {
+        if available {
+            return;
+        }
+        let bit = match self.header_extension() {
+            Some(ext) if ext.contains(ByteAddress::HEADER_EXT_FLAGS3) => {
+                BitAddress::from(ext.base) + BitAddress::HEADER_EXT_TRANSPARENCY_DESIRED.addr()
+            }
+            _ => return,
+        };
+        self.write_bit(bit, false);
+    }
+}
+
+/// A view of a story's header extension table. Every access is checked against the size of the
+/// table declared by the story; words past its end read as [`None`].
+pub struct HeaderExtension<'a> {
+    base: ByteAddress,
+    machine: &'a ZMachine,
+}
+
+impl<'a> HeaderExtension<'a> {
+    /// Returns the address of the table.
+    pub fn base(&self) -> ByteAddress {
+        self.base
+    }
+    /// Returns the number of words in the table, not counting the size word itself.
+    pub fn len(&self) -> usize {
+        self.machine
+            .word(self.base + ByteAddress::HEADER_EXT_SIZE)
+            .into()
+    }
+    /// Returns whether the table has no words besides the size word.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn contains(&self, offset: usize) -> bool {
+        let idx = offset / 2;
+        idx != 0
+            && idx <= self.len()
+            && self.base + offset + 1 < ByteAddress(self.machine.len_bytes())
+    }
+    /// Returns the word at a byte offset from the start of the table, or [`None`] if the table
+    /// is too short to contain it.
+    pub fn word(&self, offset: usize) -> Option<Word> {
+        if self.contains(offset) {
+            Some(self.machine.word(self.base + offset))
+        } else {
+            None
+        }
+    }
+    /// Returns the last recorded x coordinate of the mouse.
+    pub fn mouse_x(&self) -> Option<Word> {
+        self.word(ByteAddress::HEADER_EXT_MOUSE_X)
+    }
+    /// Returns the last recorded y coordinate of the mouse.
+    pub fn mouse_y(&self) -> Option<Word> {
+        self.word(ByteAddress::HEADER_EXT_MOUSE_Y)
+    }
+    /// Returns the location of the Unicode translation table, or [`None`] if the default table is
+    /// used.
+    pub fn unicode_table_location(&self) -> Option<ByteAddress> {
+        match self.word(ByteAddress::HEADER_EXT_UNICODE_TRANSLATION_TABLE_LOCATION) {
+            Some(0) | None => None,
+            Some(word) => Some(word.into()),
+        }
+    }
+    /// Returns the third set of header flags.
+    pub fn flags3(&self) -> Option<Flags3> {
+        self.word(ByteAddress::HEADER_EXT_FLAGS3)?;
+        let base = BitAddress::from(self.base);
+        Some(Flags3 {
+            transparency_desired: self.machine
+                [base + BitAddress::HEADER_EXT_TRANSPARENCY_DESIRED.addr()],
+        })
+    }
+    /// Returns the true default foreground color, as a 15-bit color.
+    pub fn true_default_foreground_color(&self) -> Option<Word> {
+        self.word(ByteAddress::HEADER_EXT_DEFAULT_FOREGROUND_COLOR)
+    }
+    /// Returns the true default background color, as a 15-bit color.
+    pub fn true_default_background_color(&self) -> Option<Word> {
+        self.word(ByteAddress::HEADER_EXT_DEFAULT_BACKGROUND_COLOR)
+    }
+}
+
+/// The third set of header flags, stored in the header extension table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Flags3 {
+    /// Whether the story wants to use transparency.
+    pub transparency_desired: bool,
+}
+
+/// The first set of header flags. Its meaning differs between early and late versions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Flags1 {
+    /// Flags used by versions 1 to 3.
+    Early {
+        /// What the status line displays.
+        status_line: StatusLine,
+        /// Whether the story is split across two disks.
+        two_disks: bool,
+        /// Whether the interpreter cannot display a status line.
+        status_line_unavailable: bool,
+        /// Whether the interpreter can split the screen.
+        screen_split_available: bool,
+        /// Whether a variable-pitch font is the default.
+        variable_pitch_default: bool,
+    },
+    /// Flags used by versions 4 and later.
+    Late {
+        /// Whether the interpreter supports colors. Version 5 and later.
+        colors_available: bool,
+        /// Whether the interpreter supports pictures. Version 6 only.
+        pictures_available: bool,
+        /// Whether the interpreter supports boldface.
+        bold_available: bool,
+        /// Whether the interpreter supports italics.
+        italic_available: bool,
+        /// Whether the interpreter supports a fixed-space font.
+        fixed_space_available: bool,
+        /// Whether the interpreter supports sound effects. Version 6 only.
+        sound_effects_available: bool,
+        /// Whether the interpreter supports timed keyboard input.
+        timed_keyboard_available: bool,
+    },
+}
+
+/// The second set of header flags, mostly set by the story to request features.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Flags2 {
+    /// Whether transcripting is on.
+    pub transcripting: bool,
+    /// Whether the story wants text printed in a fixed-pitch font.
+    pub force_fixed_pitch: bool,
+    /// Whether the interpreter has requested a screen redraw.
+    pub redraw_requested: bool,
+    /// Whether the story wants to use pictures.
+    pub pictures_desired: bool,
+    /// Whether the story wants to use the `undo` opcodes.
+    pub undo_desired: bool,
+    /// Whether the story wants to use a mouse.
+    pub mouse_desired: bool,
+    /// Whether the story wants to use colors.
+    pub colors_desired: bool,
+    /// Whether the story wants to use sound effects.
+    pub sounds_desired: bool,
+    /// Whether the story wants to use menus.
+    pub menus_desired: bool,
+}
+
+/// The size of the screen, as reported by the interpreter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ScreenMetrics {
+    /// The height of the screen in lines. 255 means infinite.
+    pub height_lines: u8,
+    /// The width of the screen in characters.
+    pub width_chars: u8,
+    /// The width of the screen in units. Version 5 and later.
+    pub width_units: Word,
+    /// The height of the screen in units. Version 5 and later.
+    pub height_units: Word,
+}
+
+/// The size of the font, as reported by the interpreter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct FontMetrics {
+    /// The width of a `0` in units.
+    pub width_units: u8,
+    /// The height of a line in units.
+    pub height_units: u8,
+}
+
+/// The machine that the interpreter claims to be running on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InterpreterNumber {
+    /// DECSystem-20.
+    DecSystem20,
+    /// Apple IIe.
+    AppleIIe,
+    /// Macintosh.
+    Macintosh,
+    /// Amiga.
+    Amiga,
+    /// Atari ST.
+    AtariSt,
+    /// IBM PC.
+    IbmPc,
+    /// Commodore 128.
+    Commodore128,
+    /// Commodore 64.
+    Commodore64,
+    /// Apple IIc.
+    AppleIIc,
+    /// Apple IIgs.
+    AppleIIgs,
+    /// Tandy Color.
+    TandyColor,
+    /// An unrecognized interpreter number.
+    Other(u8),
+}
+
+impl InterpreterNumber {
+    /// Converts a header byte into an interpreter number.
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            1 => InterpreterNumber::DecSystem20,
+            2 => InterpreterNumber::AppleIIe,
+            3 => InterpreterNumber::Macintosh,
+            4 => InterpreterNumber::Amiga,
+            5 => InterpreterNumber::AtariSt,
+            6 => InterpreterNumber::IbmPc,
+            7 => InterpreterNumber::Commodore128,
+            8 => InterpreterNumber::Commodore64,
+            9 => InterpreterNumber::AppleIIc,
+            10 => InterpreterNumber::AppleIIgs,
+            11 => InterpreterNumber::TandyColor,
+            _ => InterpreterNumber::Other(byte),
+        }
+    }
+    /// Converts this interpreter number into a header byte.
+    pub fn to_byte(self) -> u8 {
+        match self {
+            InterpreterNumber::DecSystem20 => 1,
+            InterpreterNumber::AppleIIe => 2,
+            InterpreterNumber::Macintosh => 3,
+            InterpreterNumber::Amiga => 4,
+            InterpreterNumber::AtariSt => 5,
+            InterpreterNumber::IbmPc => 6,
+            InterpreterNumber::Commodore128 => 7,
+            InterpreterNumber::Commodore64 => 8,
+            InterpreterNumber::AppleIIc => 9,
+            InterpreterNumber::AppleIIgs => 10,
+            InterpreterNumber::TandyColor => 11,
+            InterpreterNumber::Other(byte) => byte,
+        }
+    }
+}
+
+/// Builds the parts of the header that are set by the interpreter. Fields that are never set are
+/// left as the story has them.
+///
+/// Only fields meaningful to the story's version are written when
+/// [`apply`](HeaderBuilder::apply)ing the builder, so the same builder can be used for any story.
+#[derive(Debug, Clone, Default)]
+pub struct HeaderBuilder {
+    interpreter_number: Option<InterpreterNumber>,
+    interpreter_version: Option<u8>,
+    screen: Option<ScreenMetrics>,
+    font: Option<FontMetrics>,
+    default_colors: Option<(u8, u8)>,
+    standard_revision: Option<(u8, u8)>,
+    status_line: Option<bool>,
+    screen_split: Option<bool>,
+    variable_pitch_default: Option<bool>,
+    colors: Option<bool>,
+    pictures: Option<bool>,
+    bold: Option<bool>,
+    italic: Option<bool>,
+    fixed_space: Option<bool>,
+    sound_effects: Option<bool>,
+    timed_keyboard: Option<bool>,
+    undo: Option<bool>,
+    mouse: Option<bool>,
+    menus: Option<bool>,
+    transparency: Option<bool>,
+    true_default_colors: Option<(Word, Word)>,
+}
+
+impl HeaderBuilder {
+    /// Creates a new builder that leaves every field untouched.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the interpreter number. Version 4 and later.
+    pub fn interpreter_number(mut self, number: InterpreterNumber) -> Self {
+        self.interpreter_number = Some(number);
+        self
+    }
+    /// Sets the interpreter version. Version 4 and later.
+    pub fn interpreter_version(mut self, version: u8) -> Self {
+        self.interpreter_version = Some(version);
+        self
+    }
+    /// Sets the screen metrics. Version 4 and later; the fields in units are only written in
+    /// version 5 and later.
+    pub fn screen_metrics(mut self, screen: ScreenMetrics) -> Self {
+        self.screen = Some(screen);
+        self
+    }
+    /// Sets the font metrics. Version 5 and later.
+    pub fn font_metrics(mut self, font: FontMetrics) -> Self {
+        self.font = Some(font);
+        self
+    }
+    /// Sets the default background and foreground colors. Version 5 and later.
+    pub fn default_colors(mut self, background: u8, foreground: u8) -> Self {
+        self.default_colors = Some((background, foreground));
+        self
+    }
+    /// Sets the revision of the Z-Machine Standard that the interpreter follows.
+    pub fn standard_revision(mut self, major: u8, minor: u8) -> Self {
+        self.standard_revision = Some((major, minor));
+        self
+    }
+    /// Sets whether a status line can be displayed. Versions 1 to 3.
+    pub fn status_line_available(mut self, available: bool) -> Self {
+        self.status_line = Some(available);
+        self
+    }
+    /// Sets whether the screen can be split. Versions 1 to 3.
+    pub fn screen_split_available(mut self, available: bool) -> Self {
+        self.screen_split = Some(available);
+        self
+    }
+    /// Sets whether a variable-pitch font is the default. Versions 1 to 3.
+    pub fn variable_pitch_default(mut self, default: bool) -> Self {
+        self.variable_pitch_default = Some(default);
+        self
+    }
+    /// Sets whether colors are available. Version 5 and later.
+    pub fn colors_available(mut self, available: bool) -> Self {
+        self.colors = Some(available);
+        self
+    }
+    /// Sets whether pictures are available. Version 6.
+    pub fn pictures_available(mut self, available: bool) -> Self {
+        self.pictures = Some(available);
+        self
+    }
+    /// Sets whether boldface is available. Version 4 and later.
+    pub fn bold_available(mut self, available: bool) -> Self {
+        self.bold = Some(available);
+        self
+    }
+    /// Sets whether italics are available. Version 4 and later.
+    pub fn italic_available(mut self, available: bool) -> Self {
+        self.italic = Some(available);
+        self
+    }
+    /// Sets whether a fixed-space font is available. Version 4 and later.
+    pub fn fixed_space_available(mut self, available: bool) -> Self {
+        self.fixed_space = Some(available);
+        self
+    }
+    /// Sets whether sound effects are available. Version 6 only, though from version 5 the story's
+    /// request for sounds is cleared if they aren't.
+    pub fn sound_effects_available(mut self, available: bool) -> Self {
+        self.sound_effects = Some(available);
+        self
+    }
+    /// Sets whether timed keyboard input is available. Version 4 and later.
+    pub fn timed_keyboard_available(mut self, available: bool) -> Self {
+        self.timed_keyboard = Some(available);
+        self
+    }
+    /// Sets whether `save_undo` and `restore_undo` are supported. Version 5 and later.
+    pub fn undo_available(mut self, available: bool) -> Self {
+        self.undo = Some(available);
+        self
+    }
+    /// Sets whether a mouse is available. Version 5 and later.
+    pub fn mouse_available(mut self, available: bool) -> Self {
+        self.mouse = Some(available);
+        self
+    }
+    /// Sets whether menus are available. Version 6.
+    pub fn menus_available(mut self, available: bool) -> Self {
+        self.menus = Some(available);
+        self
+    }
+    /// Sets whether transparency is available. Version 6; written to the header extension table.
+    pub fn transparency_available(mut self, available: bool) -> Self {
+        self.transparency = Some(available);
+        self
+    }
+    /// Sets the true default foreground and background colors, as 15-bit colors. Version 5 and
+    /// later; written to the header extension table.
+    pub fn true_default_colors(mut self, foreground: Word, background: Word) -> Self {
+        self.true_default_colors = Some((foreground, background));
+        self
+    }
+    /// Writes the fields of this builder into a story's header.
+    ///
+    /// Features the interpreter does not support are cleared from the story's 'desired' flags, as
+    /// required by the standard.
+    pub fn apply(&self, z: &mut ZMachine) {
+        let version = z.version();
+        fn set(z: &mut ZMachine, bit: BitAddress, value: Option<bool>) {
+            if let Some(value) = value {
+                z.write_bit(bit, value);
+            }
+        }
+        fn clear_unless(z: &mut ZMachine, bit: BitAddress, value: Option<bool>) {
+            if value == Some(false) {
+                z.write_bit(bit, false);
+            }
+        }
+        if let Some((major, minor)) = self.standard_revision {
+            z.write_byte(ByteAddress::STANDARD_REVISION_MAJOR, major);
+            z.write_byte(ByteAddress::STANDARD_REVISION_MINOR, minor);
+        }
+        if version <= Version::V3 {
+            set(
+                z,
+                BitAddress::STATUS_LINE_AVAILABLE,
+                self.status_line.map(|x| !x),
+            );
+            set(z, BitAddress::SCREEN_SPLIT_AVAILABLE, self.screen_split);
+            set(
+                z,
+                BitAddress::VARIABLE_PITCH_FONT_DEFAULT,
+                self.variable_pitch_default,
+            );
+            return;
+        }
+        if let Some(number) = self.interpreter_number {
+            z.write_byte(ByteAddress::INTERPRETER_NUMBER, number.to_byte());
+        }
+        if let Some(version) = self.interpreter_version {
+            z.write_byte(ByteAddress::INTERPRETER_VERSION, version);
+        }
+        if let Some(screen) = self.screen {
+            z.write_byte(ByteAddress::SCREEN_HEIGHT, screen.height_lines);
+            z.write_byte(ByteAddress::SCREEN_WIDTH_CHARS, screen.width_chars);
+            if version >= Version::V5 {
+                z.write_word(ByteAddress::SCREEN_WIDTH_UNITS, screen.width_units);
+                z.write_word(ByteAddress::SCREEN_HEIGHT_UNITS, screen.height_units);
+            }
+        }
+        set(z, BitAddress::BOLD_AVAILABLE, self.bold);
+        set(z, BitAddress::ITALIC_AVAILABLE, self.italic);
+        set(z, BitAddress::FIXED_SPACE_AVAILABLE, self.fixed_space);
+        set(z, BitAddress::TIMED_KEYBOARD_AVAILABLE, self.timed_keyboard);
+        if version < Version::V5 {
+            return;
+        }
+        if let Some(font) = self.font {
+            if version == Version::V6 {
+                z.write_byte(ByteAddress::FONT_WIDTH_UNITS_V6, font.width_units);
+                z.write_byte(ByteAddress::FONT_HEIGHT_UNITS_V6, font.height_units);
+            } else {
+                z.write_byte(ByteAddress::FONT_WIDTH_UNITS_V5, font.width_units);
+                z.write_byte(ByteAddress::FONT_HEIGHT_UNITS_V5, font.height_units);
+            }
+        }
+        if let Some((background, foreground)) = self.default_colors {
+            z.write_byte(ByteAddress::DEFAULT_BACKGROUND_COLOR, background);
+            z.write_byte(ByteAddress::DEFAULT_FOREGROUND_COLOR, foreground);
+        }
+        set(z, BitAddress::COLORS_AVAILABLE, self.colors);
+        clear_unless(z, BitAddress::COLORS_DESIRED, self.colors);
+        clear_unless(z, BitAddress::SOUNDS_DESIRED, self.sound_effects);
+        clear_unless(z, BitAddress::UNDO_DESIRED, self.undo);
+        clear_unless(z, BitAddress::MOUSE_DESIRED, self.mouse);
+        if let Some((foreground, background)) = self.true_default_colors {
+            z.set_true_default_colors(foreground, background);
+        }
+        if version == Version::V6 {
+            set(z, BitAddress::SOUND_EFFECT_AVAILABLE, self.sound_effects);
+            set(z, BitAddress::PICTURES_AVAILABLE, self.pictures);
+            clear_unless(z, BitAddress::PICTURES_DESIRED, self.pictures);
+            clear_unless(z, BitAddress::MENUS_DESIRED, self.menus);
+            if let Some(transparency) = self.transparency {
+                z.set_transparency_available(transparency);
+            }
+        }
+    }
+}
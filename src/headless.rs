@@ -0,0 +1,44 @@
+use crate::*;
+use std::mem;
+
+/// What a story printed before it stopped, as returned by
+/// [`run_until_input`](ZMachine::run_until_input).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    /// Everything printed since the last output.
+    pub text: String,
+    /// The status line, if the story is version 3 or earlier.
+    pub status: Option<Status>,
+    /// The input that the story is waiting for, or [`None`] if it has quit.
+    pub waiting_for: Option<WaitingFor>,
+}
+
+impl ZMachine {
+    /// Runs the story until it needs input or quits, and returns what it printed. The first call
+    /// stops printing to the frontend, if there is one; from then on text is kept until it's
+    /// returned. Input is given with [`send_line`](ZMachine::send_line).
+    pub fn run_until_input(&mut self) -> Result<Output, RuntimeError> {
+        if self.captured_text.is_none() {
+            self.captured_text = Some(String::new());
+        }
+        let state = self.resume()?;
+        let text = self
+            .captured_text
+            .as_mut()
+            .map(mem::take)
+            .unwrap_or_default();
+        Ok(Output {
+            text,
+            status: self.status(),
+            waiting_for: match state {
+                State::Suspended(waiting_for) => Some(waiting_for),
+                State::Quit => None,
+            },
+        })
+    }
+    /// Gives the story a line of input, for it to read when it's next run. A story waiting for a
+    /// key press gets the first character of the line, or the return key if the line is empty.
+    pub fn send_line(&mut self, line: &str) {
+        self.give_input(Input::Line(line.to_string()));
+    }
+}
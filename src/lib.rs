@@ -1,5 +1,6 @@
-#![feature(crate_visibility_modifier)]
 #![allow(dead_code)]
+// bit masks are grouped by the fields of the byte they pick out
+#![allow(clippy::unusual_byte_groupings)]
 
 #[cfg(test)]
 mod tests;
@@ -7,43 +8,148 @@ mod tests;
 mod bits;
 pub use self::bits::*;
 mod memory;
-pub use self::memory::*;
 mod meta;
 pub use self::meta::*;
+mod header;
+pub use self::header::*;
+mod load;
+pub use self::load::*;
+mod blorb;
+pub use self::blorb::*;
+mod metadata;
+pub use self::metadata::*;
+mod frontend;
+mod xml;
+pub use self::frontend::*;
+mod random;
+pub use self::random::*;
+mod screen;
+pub use self::screen::*;
+mod streams;
+pub(crate) use self::streams::*;
+mod sound;
+pub use self::sound::*;
 mod text;
 pub use self::text::*;
+mod encode;
+pub use self::encode::*;
+mod abbreviate;
+pub use self::abbreviate::*;
+mod disassemble;
+pub use self::disassemble::*;
+mod patch;
+pub use self::patch::*;
 mod objects;
 pub use self::objects::*;
 mod vm;
 pub use self::vm::*;
+mod debugger;
+pub use self::debugger::*;
+mod debug_info;
+pub use self::debug_info::*;
+mod trace;
+pub use self::trace::*;
+mod suspend;
+pub use self::suspend::*;
+mod headless;
+pub use self::headless::*;
+mod snapshot;
+pub use self::snapshot::*;
 mod window;
 pub use self::window::*;
 
-use failure::Fail;
 use std::fs::File;
 use std::io::{Error as IoError, Read};
 use std::path::Path;
+use thiserror::Error;
 
 /// An implementation of a [Z-Machine](https://en.wikipedia.org/wiki/Z-machine) with a loaded story.
 pub struct ZMachine {
-    crate memory: Vec<u8>,
+    pub(crate) memory: Vec<u8>,
+    pub(crate) original: Vec<u8>,
+    pub(crate) blorb: Option<Blorb>,
+    pub(crate) pc: ByteAddress,
+    pub(crate) stack: Vec<Word>,
+    pub(crate) frames: Vec<Frame>,
+    pub(crate) running: bool,
+    pub(crate) has_quit: bool,
+    pub(crate) last_return: Word,
+    pub(crate) sound: SoundState,
+    pub(crate) rng: Rng,
+    pub(crate) frontend: Option<Box<dyn Frontend + Send>>,
+    pub(crate) print_buffer: String,
+    pub(crate) debug_info: Option<DebugInfo>,
+    pub(crate) trace: Option<Box<dyn TraceSink + Send>>,
+    pub(crate) faults: Faults,
+    pub(crate) protect_memory: bool,
+    pub(crate) write_error: Option<ExecuteError>,
+    pub(crate) decode_cache: DecodeCache,
+    pub(crate) suspend: Suspend,
+    pub(crate) captured_text: Option<String>,
+    pub(crate) screen: Screen,
+    pub(crate) streams: Streams,
 }
 
 impl ZMachine {
-    /// Creates a new Z-machine instance from a story file already loaded in memory.
+    /// Creates a new Z-machine instance from a story file already loaded in memory. The story's
+    /// header is validated strictly; see [`new_with_options`](ZMachine::new_with_options) to load
+    /// stories that don't quite follow the standard.
     pub fn new(file: impl Into<Vec<u8>>) -> LoadResult<Self> {
+        Self::new_with_options(file, LoadOptions::new())
+    }
+    /// Creates a new Z-machine instance from a story file already loaded in memory, validating it
+    /// according to the provided options.
+    pub fn new_with_options(file: impl Into<Vec<u8>>, options: LoadOptions) -> LoadResult<Self> {
         let vec = file.into();
-        if vec.len() < 64 {
-            return Err(LoadError::TooSmall(vec.len()));
-        }
-        Ok(Self { memory: vec })
+        options.validate(&vec)?;
+        let mut z = Self {
+            original: vec.clone(),
+            memory: vec,
+            blorb: None,
+            pc: ByteAddress::ZERO,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            running: false,
+            has_quit: false,
+            last_return: 0,
+            sound: SoundState::default(),
+            rng: Rng::new(RandomMode::Random),
+            frontend: None,
+            print_buffer: String::new(),
+            debug_info: None,
+            trace: None,
+            faults: Faults::default(),
+            protect_memory: false,
+            write_error: None,
+            decode_cache: DecodeCache::default(),
+            suspend: Suspend::default(),
+            captured_text: None,
+            screen: Screen::default(),
+            streams: Streams::default(),
+        };
+        z.reset_execution();
+        Ok(z)
     }
-    /// Utility function for reading from a filename and passing the contents to [`Self::new`].
+    /// Utility function for reading from a filename and passing the contents to [`Self::new`], or
+    /// to [`Self::from_blorb`] if the file is a Blorb file.
     pub fn from_file(path: impl AsRef<Path>) -> LoadResult<Self> {
+        Self::from_file_with_options(path, LoadOptions::new())
+    }
+    /// Utility function for reading from a filename and passing the contents to
+    /// [`Self::new_with_options`], or to [`Self::from_blorb_with_options`] if the file is a Blorb
+    /// file.
+    pub fn from_file_with_options(
+        path: impl AsRef<Path>,
+        options: LoadOptions,
+    ) -> LoadResult<Self> {
         let path = path.as_ref();
         let mut vec = Vec::new();
         File::open(path)?.read_to_end(&mut vec)?;
-        Self::new(vec)
+        if Blorb::is_blorb(&vec) {
+            Self::from_blorb_with_options(vec, options)
+        } else {
+            Self::new_with_options(vec, options)
+        }
     }
     //todo version-specific header sizing
     /// Returns the length of the story in bytes.
@@ -61,16 +167,37 @@ impl ZMachine {
 }
 
 /// Errors that can occur during loading a story.
-#[derive(Debug, Fail)]
+#[derive(Debug, Error)]
 pub enum LoadError {
     /// An error during IO. Only used with [`ZMachine::from_file`].
-    #[fail(display = "IO error: {}", _0)]
-    IoError(#[cause] IoError),
+    #[error("IO error: {0}")]
+    IoError(#[source] IoError),
     /// An error to do with the story's size. A story without a header (64 bytes) cannot be read.
-    #[fail(display = "Story is too small (was {} bytes, must be at least 64)", _0)]
+    #[error("Story is too small (was {0} bytes, must be at least 64)")]
     TooSmall(usize),
+    /// The story is larger than its version allows.
+    #[error("Story is too large (was {0} bytes, must be at most {1})")]
+    TooLarge(usize, usize),
+    /// The story's version byte isn't a known version.
+    #[error("Invalid story version {0}")]
+    InvalidVersion(u8),
+    /// The base of static memory is inside the header or past the end of the story.
+    #[error("Static memory base {0:#x} is out of range")]
+    StaticMemoryOutOfRange(usize),
+    /// The base of high memory is inside dynamic memory or past the end of the story.
+    #[error("High memory base {0:#x} is out of range")]
+    HighMemoryOutOfRange(usize),
+    /// One of the tables referenced by the header is out of range.
+    #[error("{0} table address {1:#x} is out of range")]
+    TableOutOfRange(StoryTable, usize),
+    /// The length declared by the header is longer than the story.
+    #[error("Story is truncated (header declares {0} bytes, but was {1})")]
+    Truncated(usize, usize),
+    /// An error in the Blorb file the story was loaded from.
+    #[error("Blorb error: {0}")]
+    Blorb(#[source] BlorbError),
     /// An unknown error of some other kind.
-    #[fail(display = "Unknown error")]
+    #[error("Unknown error")]
     Unknown,
 }
 
@@ -80,4 +207,10 @@ impl From<IoError> for LoadError {
     }
 }
 
+impl From<BlorbError> for LoadError {
+    fn from(err: BlorbError) -> Self {
+        LoadError::Blorb(err)
+    }
+}
+
 pub type LoadResult<T> = Result<T, LoadError>;
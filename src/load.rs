@@ -0,0 +1,123 @@
+use crate::*;
+use std::fmt::{self, Display, Formatter};
+
+/// Options controlling how a story is validated when it is loaded.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct LoadOptions {
+    lenient: bool,
+}
+
+impl LoadOptions {
+    /// Creates the default options, which validate the story strictly.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets whether loading is lenient. A lenient load accepts stories that are longer than their
+    /// version allows or shorter than their header says, like most real interpreters do; header
+    /// addresses are still checked, since the story can't be run without them. Some old stories,
+    /// and stories that have been patched by hand, need this.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+    /// Returns whether loading is lenient.
+    pub fn is_lenient(&self) -> bool {
+        self.lenient
+    }
+    pub(crate) fn validate(&self, story: &[u8]) -> LoadResult<()> {
+        let len = story.len();
+        if len < 64 {
+            return Err(LoadError::TooSmall(len));
+        }
+        let byte = story[ByteAddress::STORY_VERSION.addr()];
+        let version = Version::from_number(byte).ok_or(LoadError::InvalidVersion(byte))?;
+        if !self.lenient && len > version.max_story_len() {
+            return Err(LoadError::TooLarge(len, version.max_story_len()));
+        }
+        let word = |addr: ByteAddress| {
+            u16::from_be_bytes([story[addr.addr()], story[addr.addr() + 1]]) as usize
+        };
+        let static_base = word(ByteAddress::STATIC_MEMORY_LOCATION);
+        if static_base < 64 || static_base > len {
+            return Err(LoadError::StaticMemoryOutOfRange(static_base));
+        }
+        let high_base = word(ByteAddress::HIGH_MEMORY_LOCATION);
+        if high_base < static_base || high_base > len {
+            return Err(LoadError::HighMemoryOutOfRange(high_base));
+        }
+        let abbreviations_len = match version {
+            Version::V1 => 0,
+            Version::V2 => 32 * 2,
+            _ => 96 * 2,
+        };
+        let tables = [
+            (
+                StoryTable::Dictionary,
+                ByteAddress::DICTIONARY_LOCATION,
+                4,
+                len,
+            ),
+            (
+                StoryTable::Objects,
+                ByteAddress::OBJECT_TABLE_LOCATION,
+                2,
+                static_base,
+            ),
+            (
+                StoryTable::Globals,
+                ByteAddress::GLOBAL_VARIABLE_TABLE_LOCATION,
+                240 * 2,
+                static_base,
+            ),
+            (
+                StoryTable::Abbreviations,
+                ByteAddress::ABBREVIATIONS_LOCATION,
+                abbreviations_len,
+                len,
+            ),
+        ];
+        for &(table, location, table_len, end) in &tables {
+            if table_len == 0 {
+                continue;
+            }
+            let addr = word(location);
+            if addr < 64 || addr + table_len > end {
+                return Err(LoadError::TableOutOfRange(table, addr));
+            }
+        }
+        let declared_len = word(ByteAddress::FILE_LENGTH)
+            * match version {
+                Version::V1 | Version::V2 | Version::V3 => 2,
+                Version::V4 | Version::V5 => 4,
+                Version::V6 | Version::V7 | Version::V8 => 8,
+            };
+        if !self.lenient && declared_len > len {
+            return Err(LoadError::Truncated(declared_len, len));
+        }
+        Ok(())
+    }
+}
+
+/// The tables whose addresses are stored in the header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StoryTable {
+    /// The dictionary.
+    Dictionary,
+    /// The object table.
+    Objects,
+    /// The global variable table.
+    Globals,
+    /// The abbreviations table.
+    Abbreviations,
+}
+
+impl Display for StoryTable {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            StoryTable::Dictionary => "Dictionary",
+            StoryTable::Objects => "Object",
+            StoryTable::Globals => "Global variable",
+            StoryTable::Abbreviations => "Abbreviations",
+        })
+    }
+}
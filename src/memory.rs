@@ -5,7 +5,7 @@ use std::ops::Range;
 impl ZMachine {
     /// Returns the range of memory classified 'dynamic'.
     pub fn dynamic_memory_range(&self) -> Range<ByteAddress> {
-        ByteAddress::ZERO..ByteAddress::from(self.word(ByteAddress::STATIC_MEMORY_LOCATION) - 1)
+        ByteAddress::ZERO..ByteAddress::from(self.word(ByteAddress::STATIC_MEMORY_LOCATION))
     }
     /// Returns the range of memory classified 'static'.
     pub fn static_memory_range(&self) -> Range<ByteAddress> {
@@ -17,19 +17,84 @@ impl ZMachine {
         ByteAddress::from(self.word(ByteAddress::HIGH_MEMORY_LOCATION))
             ..ByteAddress(self.len_bytes())
     }
+    /// Checks whether the story may write bytes at an address: they must be in dynamic memory,
+    /// and the only header bits that may change are those the game is allowed to set, in the
+    /// second byte of Flags 2 and the last bit of the first.
+    pub fn check_write(&self, address: ByteAddress, bytes: &[u8]) -> Result<(), ExecuteError> {
+        let end = self.dynamic_memory_range().end.0.min(self.len_bytes());
+        if address.0 + bytes.len() > end {
+            return Err(ExecuteError::StaticMemoryWrite(address.0.max(end)));
+        }
+        for (x, &byte) in bytes.iter().enumerate() {
+            let addr = address + x;
+            if addr.0 >= 64 {
+                break;
+            }
+            let writable = if addr == ByteAddress::FLAGS2 {
+                0b0000_0001
+            } else if addr == ByteAddress::FLAGS2 + 1 {
+                0b1111_1111
+            } else {
+                0
+            };
+            if (self[addr] ^ byte) & !writable != 0 {
+                return Err(ExecuteError::HeaderWrite(addr.0));
+            }
+        }
+        Ok(())
+    }
+    /// Returns whether a write may go ahead. Writes made by instructions are checked with
+    /// [`check_write`](ZMachine::check_write); a fatal fault is kept to be returned once the
+    /// instruction finishes.
+    fn allow_write(&mut self, address: ByteAddress, bytes: &[u8]) -> bool {
+        if !self.protect_memory {
+            if address.0 + bytes.len() > self.dynamic_memory_range().end.0 {
+                self.decode_cache.clear();
+            }
+            return true;
+        }
+        let error = match self.check_write(address, bytes) {
+            Ok(()) => return true,
+            Err(error) => error,
+        };
+        if let Err(error) = self.fault(error) {
+            self.write_error.get_or_insert(error);
+        }
+        false
+    }
     /// Writes a byte at the specified address.
     pub fn write_byte(&mut self, address: ByteAddress, byte: u8) {
-        self.memory[address.0] = byte;
+        if self.allow_write(address, &[byte]) {
+            self.memory[address.0] = byte;
+        }
     }
     /// Writes a bit at the specified address.
     pub fn write_bit(&mut self, address: BitAddress, bit: bool) {
-        ZBitSlice::from_slice_mut(&mut self.memory).set(address.addr(), bit);
+        let (byte_address, mask) = address.byte_and_mask();
+        let byte = if bit {
+            self[byte_address] | mask
+        } else {
+            self[byte_address] & !mask
+        };
+        if self.allow_write(byte_address, &[byte]) {
+            self.memory[byte_address.0] = byte;
+        }
+    }
+    /// Writes a slice of bytes starting at the specified address.
+    pub fn write_bytes(&mut self, address: ByteAddress, bytes: &[u8]) {
+        if self.allow_write(address, bytes) {
+            self.memory[address.0..(address.0 + bytes.len())].copy_from_slice(bytes);
+        }
     }
     /// Writes a [`Word`] at the specified address.
     pub fn write_word(&mut self, address: ByteAddress, word: Word) {
         let mut bytes = word.to_be_bytes();
-        self.memory[address.0..=(address + 1).0].swap_with_slice(&mut bytes);
+        if self.allow_write(address, &bytes) {
+            self.memory[address.0..=(address + 1).0].swap_with_slice(&mut bytes);
+        }
     }
+    /// Returns a range of bits as a [`ZBitSlice`]. This is a convenience for tools; indexing with
+    /// a [`BitAddress`] reads a single bit much faster.
     pub fn bit_range(&self, range: Range<BitAddress>) -> &ZBitSlice {
         &ZBitSlice::from_slice(&self.memory)[range.start.addr()..range.end.addr()]
     }
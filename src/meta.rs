@@ -32,6 +32,36 @@ impl Version {
         Version::V7,
         Version::V8,
     ];
+    /// Returns the version with a particular number, or [`None`] if there isn't one.
+    pub fn from_number(number: u8) -> Option<Self> {
+        Self::VALUES.get((number as usize).wrapping_sub(1)).copied()
+    }
+    /// Returns the number of this version.
+    pub fn number(self) -> u8 {
+        self as u8 + 1
+    }
+    /// Returns the largest story size allowed in this version, in bytes.
+    pub fn max_story_len(self) -> usize {
+        match self {
+            Version::V1 | Version::V2 | Version::V3 => 0x20000,
+            Version::V4 | Version::V5 => 0x40000,
+            Version::V6 | Version::V7 | Version::V8 => 0x80000,
+        }
+    }
+}
+
+/// Returns the checksum of a story file: the sum of the bytes after the header, up to a length.
+pub(crate) fn checksum(story: &[u8], len: usize) -> u16 {
+    let end = if len == 0 {
+        story.len()
+    } else {
+        len.min(story.len())
+    };
+    story
+        .get(64..end)
+        .unwrap_or(&[])
+        .iter()
+        .fold(0u16, |sum, &byte| sum.wrapping_add(byte as u16))
 }
 
 /// The status line that the game displays.
@@ -70,8 +100,10 @@ impl ZMachine {
     pub fn is_two_disks(&self) -> bool {
         self[BitAddress::TWO_DISKS]
     }
-    /// Calculates a checksum of the story.
+    /// Calculates a checksum of the story, as `verify` does: the sum of the bytes after the header
+    /// as originally loaded, up to the length declared by the header (or the whole story if it
+    /// doesn't declare one).
     pub fn calculate_checksum(&self) -> u16 {
-        unimplemented!()
+        checksum(&self.original, self.header().file_length())
     }
 }
@@ -0,0 +1,103 @@
+use crate::*;
+
+impl ZMachine {
+    /// Returns the story's IFID, following the rules of the
+    /// [Treaty of Babel](https://babel.ifarchive.org/).
+    ///
+    /// Stories that embed an IFID as a `UUID://...//` string use that. Otherwise the IFID is built
+    /// from the header as `ZCODE-release-serial`, followed by the header checksum unless the story
+    /// looks like it was compiled by Infocom.
+    pub fn ifid(&self) -> String {
+        let header = self.header();
+        let serial = header.serial();
+        let vintage = serial[0] == b'8'
+            || serial[0] == b'9'
+            || (serial[0] == b'0' && (b'0'..=b'5').contains(&serial[1]));
+        if !vintage {
+            if let Some(ifid) = self.embedded_ifid() {
+                return ifid;
+            }
+        }
+        let serial: String = serial
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_alphanumeric() {
+                    b as char
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let mut ifid = format!("ZCODE-{}-{}", header.release(), serial);
+        if serial != "000000" && serial.as_bytes()[0].is_ascii_digit() && !serial.starts_with('8') {
+            ifid.push_str(&format!("-{:04X}", header.checksum()));
+        }
+        ifid
+    }
+    fn embedded_ifid(&self) -> Option<String> {
+        const PREFIX: &[u8] = b"UUID://";
+        let start = self
+            .memory
+            .windows(PREFIX.len())
+            .position(|window| window == PREFIX)?
+            + PREFIX.len();
+        let len = self.memory[start..].iter().position(|&b| b == b'/')?;
+        String::from_utf8(self.memory[start..(start + len)].to_vec()).ok()
+    }
+    /// Returns the story's bibliographic metadata, if it was loaded from a Blorb file with an
+    /// iFiction (`IFmd`) chunk.
+    pub fn metadata(&self) -> Option<StoryMetadata> {
+        StoryMetadata::from_ifiction(self.blorb()?.metadata()?)
+    }
+}
+
+/// Bibliographic information about a story, as described by an iFiction record.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StoryMetadata {
+    /// The IFIDs of the story. Most stories have exactly one.
+    pub ifids: Vec<String>,
+    /// The title of the story.
+    pub title: Option<String>,
+    /// The author or authors of the story.
+    pub author: Option<String>,
+    /// The headline of the story, e.g. "An Interactive Fantasy".
+    pub headline: Option<String>,
+    /// When the story was first published, as a year or a `YYYY-MM-DD` date.
+    pub first_published: Option<String>,
+    /// The language of the story, as an RFC 3066 tag.
+    pub language: Option<String>,
+    /// The genre of the story.
+    pub genre: Option<String>,
+    /// A description of the story.
+    pub description: Option<String>,
+}
+
+impl StoryMetadata {
+    /// Parses the first story in an iFiction XML document, returning [`None`] if the document
+    /// isn't well-formed or doesn't describe a story.
+    pub fn from_ifiction(xml: &str) -> Option<Self> {
+        let root = xml::parse(xml)?;
+        let story = if root.name == "story" {
+            &root
+        } else {
+            root.child("story")?
+        };
+        let mut metadata = StoryMetadata::default();
+        if let Some(identification) = story.child("identification") {
+            metadata.ifids = identification
+                .children_named("ifid")
+                .map(|ifid| ifid.text().trim().to_string())
+                .collect();
+        }
+        if let Some(bibliographic) = story.child("bibliographic") {
+            metadata.title = bibliographic.child_text("title");
+            metadata.author = bibliographic.child_text("author");
+            metadata.headline = bibliographic.child_text("headline");
+            metadata.first_published = bibliographic.child_text("firstpublished");
+            metadata.language = bibliographic.child_text("language");
+            metadata.genre = bibliographic.child_text("genre");
+            metadata.description = bibliographic.child_text("description");
+        }
+        Some(metadata)
+    }
+}
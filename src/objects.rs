@@ -1,4 +1,5 @@
 use crate::*;
+use std::ops::Range;
 
 impl ZMachine {
     /// Returns the base of the object table (i.e. at the start of the property defaults header).
@@ -44,19 +45,23 @@ impl ZMachine {
     /// lay out memory such that this fails, but no known stories do.
     pub fn objects_count(&self) -> usize {
         let object_1 = self.object_unchecked(1);
-        (object_1.property_table_location() - object_1.start) / self.object_entry_size()
+        let len = object_1
+            .property_table_location()
+            .0
+            .saturating_sub(object_1.start.0);
+        len / self.object_entry_size()
     }
     /// Returns the default value for a property. Panics if `property_id` is out of bounds
-    /// ([`object_property_count`](ZMachine::object_property_count))
+    /// (`1..=`[`object_property_count`](ZMachine::object_property_count))
     pub fn default_property(&self, property_id: usize) -> &[u8] {
         let ct = self.object_property_count();
         assert!(
-            property_id < ct,
+            property_id != 0 && property_id <= ct,
             "Property ID out of bounds (was {}, max {})",
             property_id,
             ct
         );
-        let idx = self.object_table_base() + property_id * 2;
+        let idx = self.object_table_base() + (property_id - 1) * 2;
         &self[idx..=(idx + 1)]
     }
     fn object_table_objects_start(&self) -> ByteAddress {
@@ -64,7 +69,7 @@ impl ZMachine {
     }
     /// Returns an object with a particular ID. Panics if `id` is out of bounds
     /// (1..[`objects_count`](ZMachine::objects_count))
-    pub fn object(&self, id: usize) -> Object {
+    pub fn object(&self, id: usize) -> Object<'_> {
         assert!(
             id != 0 && id < self.objects_count(),
             "Object ID out of bounds (was {}, requires 1..{})",
@@ -74,7 +79,7 @@ impl ZMachine {
         self.object_unchecked(id)
     }
     #[doc(hidden)]
-    pub fn object_unchecked(&self, id: usize) -> Object {
+    pub fn object_unchecked(&self, id: usize) -> Object<'_> {
         let start = self.object_table_objects_start();
         let sz = self.object_entry_size();
         let addr = start + sz * (id - 1);
@@ -83,6 +88,110 @@ impl ZMachine {
             machine: self,
         }
     }
+    fn relation_offset(&self, relation: Relation) -> usize {
+        let idx = match relation {
+            Relation::Parent => 0,
+            Relation::Sibling => 1,
+            Relation::Child => 2,
+        };
+        if self.version() > Version::V3 {
+            6 + idx * 2
+        } else {
+            4 + idx
+        }
+    }
+    /// Returns where one of an object's relations is stored.
+    pub(crate) fn relation_location(&self, id: usize, relation: Relation) -> Range<ByteAddress> {
+        let addr = self.object_unchecked(id).start + self.relation_offset(relation);
+        let len = if self.version() > Version::V3 { 2 } else { 1 };
+        addr..(addr + len)
+    }
+    fn set_relation(&mut self, id: usize, relation: Relation, to: usize) {
+        let addr = self.object_unchecked(id).start + self.relation_offset(relation);
+        if self.version() > Version::V3 {
+            self.write_word(addr, to as Word);
+        } else {
+            self.write_byte(addr, to as u8);
+        }
+    }
+    /// Sets or clears an attribute of an object. Panics if `attribute_id` is out of bounds
+    /// ([`object_attribute_count`](ZMachine::object_attribute_count))
+    pub fn set_attribute(&mut self, id: usize, attribute_id: usize, value: bool) {
+        let ct = self.object_attribute_count();
+        assert!(
+            attribute_id < ct,
+            "Attribute ID out of range (was {}, max {})",
+            attribute_id,
+            ct - 1
+        );
+        let start = self.object_unchecked(id).start;
+        self.write_bit(BitAddress::from(start) + attribute_id, value);
+    }
+    /// Detaches an object from its parent, or does nothing if it has no parent. Its children go
+    /// with it.
+    pub fn remove_object(&mut self, id: usize) {
+        let obj = self.object_unchecked(id);
+        let parent = match obj.parent_id() {
+            Some(parent) => parent,
+            None => return,
+        };
+        let sibling = obj.sibling_id().unwrap_or(0);
+        let mut prev = self.object_unchecked(parent).child_id();
+        if prev == Some(id) {
+            self.set_relation(parent, Relation::Child, sibling);
+        } else {
+            while let Some(prev_id) = prev {
+                let next = self.object_unchecked(prev_id).sibling_id();
+                if next == Some(id) {
+                    self.set_relation(prev_id, Relation::Sibling, sibling);
+                    break;
+                }
+                prev = next;
+            }
+        }
+        self.set_relation(id, Relation::Parent, 0);
+        self.set_relation(id, Relation::Sibling, 0);
+    }
+    /// Moves an object to be the first child of another object.
+    pub fn insert_object(&mut self, id: usize, destination: usize) {
+        self.remove_object(id);
+        let first = self.object_unchecked(destination).child_id().unwrap_or(0);
+        self.set_relation(id, Relation::Sibling, first);
+        self.set_relation(id, Relation::Parent, destination);
+        self.set_relation(destination, Relation::Child, id);
+    }
+    /// Sets the value of an object's property. Panics if the object doesn't have the property, or
+    /// if the property is longer than 2 bytes.
+    pub fn put_property(&mut self, id: usize, property_id: usize, value: Word) {
+        let entry = self
+            .object_unchecked(id)
+            .property_entry(property_id)
+            .unwrap_or_else(|| panic!("Object {} has no property {}", id, property_id));
+        match entry.len {
+            1 => self.write_byte(entry.data, value as u8),
+            2 => self.write_word(entry.data, value),
+            len => panic!("Property {} is {} bytes long", property_id, len),
+        }
+    }
+}
+
+/// One of the links between an object and the objects around it in the object tree.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Relation {
+    /// The object that contains this one.
+    Parent,
+    /// The next object with the same parent.
+    Sibling,
+    /// The first object this one contains.
+    Child,
+}
+
+/// The location of a property's data.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct PropertyEntry {
+    pub(crate) id: usize,
+    pub(crate) data: ByteAddress,
+    pub(crate) len: usize,
 }
 
 /// Represents a game object.
@@ -116,10 +225,6 @@ impl<'a> Object<'a> {
             Some(id)
         }
     }
-    /// Detaches this object from its parent, or does nothing if it has no parent.
-    pub fn detach(&mut self) {
-        unimplemented!()
-    }
     /// Returns this object's parent, or `None` if this object has no parent.
     pub fn parent(&self) -> Option<Object<'a>> {
         self.parent_id().map(|x| self.machine.object_unchecked(x))
@@ -166,30 +271,98 @@ impl<'a> Object<'a> {
             self.machine.word(self.start + 7).into()
         }
     }
-    /// Returns the short name of this object.
+    /// Returns this object's ID.
+    pub fn id(&self) -> usize {
+        (self.start - self.machine.object_table_objects_start()) / self.machine.object_entry_size()
+            + 1
+    }
+    /// Returns the short name of this object. Objects with no short name are named by their
+    /// identifier instead, if [debugging information](ZMachine::set_debug_info) is loaded.
     pub fn read_name(&self) -> String {
         let mut string = String::new();
         self.copy_name(&mut string);
+        if string.is_empty() {
+            if let Some(info) = self.machine.debug_info() {
+                if let Some(identifier) = info.objects.get(&self.id()) {
+                    string.push_str(identifier);
+                }
+            }
+        }
         string
     }
     fn properties_start(&self) -> ByteAddress {
+        // the short name's length is in words
+        let tbl = self.property_table_location();
+        tbl + self.machine[tbl] as usize * 2 + 1
+    }
+    /// Returns the address of this object's short name, or [`None`] if it doesn't have one.
+    pub fn name_location(&self) -> Option<ByteAddress> {
         let tbl = self.property_table_location();
-        tbl + self.machine[tbl] as usize + 1
+        if self.machine[tbl] == 0 {
+            None
+        } else {
+            Some(tbl + 1)
+        }
     }
     /// Copies the short name of this object into the provided buffer.
     pub fn copy_name(&self, string: &mut String) {
-        let name_addr = self.property_table_location() + 1;
-        self.machine.copy_zstring(name_addr, string);
+        if let Some(name_addr) = self.name_location() {
+            self.machine.copy_zstring(name_addr, string);
+        }
     }
     /// Returns the number of properties on this object.
     pub fn property_count(&self) -> usize {
-        let start = self.properties_start();
-        let sz_byte1 = self.machine[start] as usize;
-        sz_byte1 & 0b00111111
+        self.property_entries().count()
+    }
+    fn property_entry_at(&self, mut addr: ByteAddress) -> Option<PropertyEntry> {
+        let sz_byte1 = self.machine[addr];
+        if sz_byte1 == 0 {
+            return None;
+        }
+        addr += 1;
+        let (id, len) = if self.machine.version() > Version::V3 {
+            let sz = if sz_byte1 & 0b1_0000000 == 0b1_0000000 {
+                let sz_byte2 = self.machine[addr];
+                addr += 1;
+                match sz_byte2 & 0b00_111111 {
+                    0 => 64,
+                    sz => sz,
+                }
+            } else if sz_byte1 & 0b0_1_000000 == 0 {
+                1
+            } else {
+                2
+            };
+            (sz_byte1 & 0b00_111111, sz)
+        } else {
+            (sz_byte1 & 0b000_11111, (sz_byte1 >> 5) + 1)
+        };
+        Some(PropertyEntry {
+            id: id as usize,
+            data: addr,
+            len: len as usize,
+        })
+    }
+    /// Returns the locations of this object's properties, in descending order of ID.
+    pub(crate) fn property_entries(&self) -> impl Iterator<Item = PropertyEntry> + 'a {
+        let obj = Object {
+            start: self.start,
+            machine: self.machine,
+        };
+        let mut addr = self.properties_start();
+        std::iter::from_fn(move || {
+            let entry = obj.property_entry_at(addr)?;
+            addr = entry.data + entry.len;
+            Some(entry)
+        })
+    }
+    /// Returns the location of a property, or `None` if the property is unset.
+    pub(crate) fn property_entry(&self, property_id: usize) -> Option<PropertyEntry> {
+        self.property_entries()
+            .find(|entry| entry.id == property_id)
     }
-    // untested
     /// Returns the value of a property at a particular ID, or `None` if the property is unset.
-    /// Panics if `property_id` is out of bounds (`1..=`[`ZMachine::objects_property_count`])
+    /// Panics if `property_id` is out of bounds (`1..=`[`ZMachine::object_property_count`])
     pub fn property_value(&self, property_id: usize) -> Option<&'a [u8]> {
         assert!(
             property_id <= self.machine.object_property_count(),
@@ -197,53 +370,8 @@ impl<'a> Object<'a> {
             property_id,
             self.machine.object_property_count()
         );
-        if property_id >= self.property_count() {
-            return None;
-        }
-        let mut addr = self.properties_start();
-        if self.machine.version() > Version::V3 {
-            loop {
-                let sz_byte1 = self.machine[addr];
-                if sz_byte1 == 0 {
-                    break None;
-                }
-                addr += 1;
-                let idx = sz_byte1 & 0b00111111; // sz_byte1 % 32
-                let sz = if sz_byte1 & 0b10000000 == 0b10000000 {
-                    let sz_byte2 = self.machine[addr];
-                    addr += 1;
-                    if sz_byte2 == 0 {
-                        64
-                    } else {
-                        sz_byte2 & 0b00111111 // sz_byte2 % 32
-                    }
-                } else if sz_byte1 & 0b01000000 == 0 {
-                    2
-                } else {
-                    1
-                } as usize;
-                if idx as usize == property_id {
-                    break Some(&self.machine[addr..(addr + sz)]);
-                } else {
-                    addr += sz;
-                }
-            }
-        } else {
-            loop {
-                let sz_byte = self.machine[addr];
-                if sz_byte == 0 {
-                    break None;
-                }
-                addr += 1;
-                let idx = sz_byte & 0b00111111; // sz_byte % 32
-                let sz = ((sz_byte >> 5) + 1) as usize; // sz_byte / 32 + 1
-                if idx as usize == property_id {
-                    break Some(&self.machine[addr..(addr + sz)]);
-                } else {
-                    addr += sz;
-                }
-            }
-        }
+        let entry = self.property_entry(property_id)?;
+        Some(&self.machine[entry.data..(entry.data + entry.len)])
     }
     /// Returns the value of a property at a particular ID, or the equivalent
     /// [`ZMachine::default_property`] if the property is unset. Panics if `property_id` is out of
@@ -0,0 +1,234 @@
+use crate::*;
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+const PRINT_PADDR: u8 = 0x8D;
+const NEW_LINE: u8 = 0xBB;
+const RTRUE: u8 = 0xB0;
+const NOP: u8 = 0xB4;
+
+/// Instructions whose large constant operands might be packed string addresses kept for later,
+/// along with the arguments given to calls.
+const STRING_OPERANDS: &[&str] = &["store", "storew", "put_prop", "push", "ret"];
+
+/// An error from [`ZMachine::patch_strings`].
+#[derive(Debug, Error)]
+pub enum PatchError {
+    /// There is no string at an address, as far as [`ZMachine::strings`] can tell.
+    #[error("No string was found at {0:#x}")]
+    UnknownString(usize),
+    /// A string got longer, and is stored somewhere it can't be moved from.
+    #[error("String at {0:#x} doesn't fit and can't be moved")]
+    NoRoom(usize),
+    /// The patched story is larger than its version allows.
+    #[error("Patched story is too large (was {0} bytes, must be at most {1})")]
+    TooLarge(usize, usize),
+}
+
+/// Where a replacement string goes.
+enum Placement {
+    /// Over the old string, padded to the same length.
+    InPlace(Vec<u8>),
+    /// At the end of the story, with references to the old string moved to it.
+    Moved(Vec<u8>),
+}
+
+impl ZMachine {
+    /// Replaces strings in the story with new text, as found by [`strings`](ZMachine::strings).
+    /// Afterwards the patched story replaces the original, as if it had been loaded, so this should
+    /// be done before the story is run.
+    ///
+    /// Strings that still fit are rewritten in place. Longer static strings are moved to the end of
+    /// the story, and references to them are updated: `print_paddr` operands, and as a guess, the
+    /// operands of calls and of instructions that store a value (`store`, `storew`, `put_prop`,
+    /// `push` and `ret`), and two-byte property values. References anywhere else (e.g. in arrays)
+    /// aren't found. Longer inline strings are moved in the same way, replacing the `print` or
+    /// `print_ret` instruction with `print_paddr`. Object names can't move, and neither can
+    /// abbreviations beyond the first 128K of the story. Changing an abbreviation changes every
+    /// string that uses it.
+    ///
+    /// The file length and checksum in the header are updated to match. Nothing is changed if
+    /// anything doesn't fit. Returns the locations of the references that were updated on a guess,
+    /// since a number that happens to equal a string's packed address is changed too.
+    pub fn patch_strings(
+        &mut self,
+        replacements: &BTreeMap<ByteAddress, String>,
+    ) -> Result<Vec<ByteAddress>, PatchError> {
+        let strings: BTreeMap<_, _> = self
+            .strings()
+            .into_iter()
+            .map(|string| (string.addr, string.source))
+            .collect();
+        let mut abbreviations = self.abbreviations();
+        for (&addr, text) in replacements {
+            match strings.get(&addr) {
+                Some(StringSource::Abbreviation(abbrv)) => {
+                    abbreviations[abbrv.idx() as usize].1 = text.clone();
+                }
+                Some(_) => {}
+                None => return Err(PatchError::UnknownString(addr.0)),
+            }
+        }
+        let encoder = self.zstring_encoder();
+        let abbreviating = encoder.clone().abbreviations(abbreviations);
+        let mut placements = Vec::with_capacity(replacements.len());
+        for (&addr, text) in replacements {
+            let source = strings[&addr];
+            let mut zchars = match source {
+                StringSource::Abbreviation(_) => encoder.encode_zchars(text),
+                _ => abbreviating.encode_zchars(text),
+            };
+            let len = self.zstring_end(addr) - addr;
+            let placement = if zchars.len() <= len / 2 * 3 {
+                zchars.resize(len / 2 * 3, 5);
+                Placement::InPlace(pack_zchars(&zchars))
+            } else {
+                let room = self.zstring_end(addr) - addr;
+                let movable = match source {
+                    StringSource::Static | StringSource::Abbreviation(_) => true,
+                    StringSource::Inline(instruction) => {
+                        let needed = if self[instruction] == 0xB3 { 5 } else { 3 };
+                        room + (addr - instruction) >= needed
+                    }
+                    StringSource::ObjectName(_) => false,
+                };
+                if !movable {
+                    return Err(PatchError::NoRoom(addr.0));
+                }
+                Placement::Moved(pack_zchars(&zchars))
+            };
+            placements.push((addr, source, placement));
+        }
+
+        // references are found before anything moves
+        let routines = self.disassemble();
+        let mut story = self.memory.clone();
+        let mut moved = HashMap::new();
+        for (addr, source, placement) in placements {
+            let bytes = match placement {
+                Placement::InPlace(bytes) => {
+                    story[addr.0..(addr.0 + bytes.len())].copy_from_slice(&bytes);
+                    continue;
+                }
+                Placement::Moved(bytes) => bytes,
+            };
+            let align = self.packing_alignment();
+            story.resize(story.len().div_ceil(align) * align, 0);
+            let new = ByteAddress(story.len());
+            story.extend_from_slice(&bytes);
+            match source {
+                StringSource::Static => {
+                    let old = self.pack_address(addr, false);
+                    let new = self.pack_address(new, false);
+                    match (old, new) {
+                        (Some(old), Some(new)) => {
+                            moved.insert(old, new);
+                        }
+                        _ => return Err(PatchError::NoRoom(addr.0)),
+                    }
+                }
+                StringSource::Inline(instruction) => {
+                    let packed = self
+                        .pack_address(new, false)
+                        .ok_or(PatchError::NoRoom(addr.0))?;
+                    let end = self.zstring_end(addr);
+                    let mut code = vec![PRINT_PADDR];
+                    code.extend_from_slice(&packed.to_be_bytes());
+                    if self[instruction] == 0xB3 {
+                        code.extend_from_slice(&[NEW_LINE, RTRUE]);
+                    }
+                    code.resize(end - instruction, NOP);
+                    story[instruction.0..end.0].copy_from_slice(&code);
+                }
+                StringSource::Abbreviation(abbrv) => {
+                    if new.0 / 2 > Word::MAX as usize {
+                        return Err(PatchError::NoRoom(addr.0));
+                    }
+                    let entry = self.abbreviations_table_base() + abbrv.idx() as usize * 2;
+                    let word = (new.0 as Word / 2).to_be_bytes();
+                    story[entry.0..(entry.0 + 2)].copy_from_slice(&word);
+                }
+                StringSource::ObjectName(_) => unreachable!(),
+            }
+        }
+        let mut guessed = Vec::new();
+        for (location, old, certain) in self.packed_references(&routines) {
+            if let Some(new) = moved.get(&old) {
+                story[location.0..(location.0 + 2)].copy_from_slice(&new.to_be_bytes());
+                if !certain {
+                    guessed.push(location);
+                }
+            }
+        }
+
+        let align = self.packing_alignment();
+        story.resize(story.len().div_ceil(align) * align, 0);
+        let max = self.version().max_story_len();
+        if story.len() > max {
+            return Err(PatchError::TooLarge(story.len(), max));
+        }
+        let len = ((story.len() / align) as Word).to_be_bytes();
+        story[ByteAddress::FILE_LENGTH.0..(ByteAddress::FILE_LENGTH.0 + 2)].copy_from_slice(&len);
+        let sum = checksum(&story, story.len()).to_be_bytes();
+        story[ByteAddress::FILE_CHECKSUM.0..(ByteAddress::FILE_CHECKSUM.0 + 2)]
+            .copy_from_slice(&sum);
+        self.memory = story.clone();
+        self.original = story;
+        self.decode_cache.clear();
+        self.reset_execution();
+        Ok(guessed)
+    }
+    /// Returns the number of bytes that packed addresses are a multiple of, which is also what the
+    /// header's file length is measured in.
+    fn packing_alignment(&self) -> usize {
+        match self.version() {
+            Version::V1 | Version::V2 | Version::V3 => 2,
+            Version::V4 | Version::V5 => 4,
+            Version::V6 | Version::V7 | Version::V8 => 8,
+        }
+    }
+    /// Returns the locations of words that might be packed string addresses, with their values and
+    /// whether they're certainly strings: the operands of `print_paddr`, other large constant
+    /// operands of calls (but not the routines called) and of the instructions in
+    /// [`STRING_OPERANDS`], and two-byte property values.
+    fn packed_references(&self, routines: &[Routine]) -> Vec<(ByteAddress, Word, bool)> {
+        let mut references = Vec::new();
+        for instruction in routines.iter().flat_map(|routine| &routine.instructions) {
+            let mut end = instruction.addr;
+            if self.decode_opcode(&mut end).is_err() {
+                continue;
+            }
+            // operands are stored in order, finishing just before `end`
+            let sizes: Vec<_> = instruction
+                .operands
+                .iter()
+                .map(|operand| match operand {
+                    Operand::LargeConstant(_) => 2,
+                    Operand::Omitted => 0,
+                    _ => 1,
+                })
+                .collect();
+            let certain = instruction.name == "print_paddr";
+            let call = instruction.name.starts_with("call");
+            if !certain && !call && !STRING_OPERANDS.contains(&instruction.name) {
+                continue;
+            }
+            let mut location = end - sizes.iter().sum::<usize>();
+            for (x, (operand, size)) in instruction.operands.iter().zip(sizes).enumerate() {
+                if let Operand::LargeConstant(value) = *operand {
+                    if !(call && x == 0) {
+                        references.push((location, value, certain));
+                    }
+                }
+                location += size;
+            }
+        }
+        for id in 1..=self.objects_count() {
+            let object = self.object_unchecked(id);
+            for entry in object.property_entries().filter(|entry| entry.len == 2) {
+                references.push((entry.data, self.word(entry.data), false));
+            }
+        }
+        references
+    }
+}
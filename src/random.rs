@@ -0,0 +1,151 @@
+use crate::*;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How the `random` opcode generates numbers.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RandomMode {
+    /// Unpredictable numbers, seeded from the system. This is the default.
+    #[default]
+    Random,
+    /// A repeatable sequence of numbers generated from a seed. Reseeding randomly (`random 0`)
+    /// continues the sequence instead of using the system, so a whole playthrough is repeatable.
+    Seeded(u64),
+    /// The predictable sequence 0, 1, 2, 3..., each taken modulo the range requested, plus 1. This
+    /// is what Inform's test suites expect, and the story can't change it.
+    Sequential,
+}
+
+impl FromStr for RandomMode {
+    type Err = std::num::ParseIntError;
+    /// Parses a `--seed` argument: `random`, `sequential`, or a number to seed with.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(RandomMode::Random),
+            "sequential" => Ok(RandomMode::Sequential),
+            _ => s.parse().map(RandomMode::Seeded),
+        }
+    }
+}
+
+/// The random number generator, along with any predictable mode the story has asked for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Rng {
+    mode: RandomMode,
+    state: u64,
+    counter: u16,
+    /// The upper bound of the counting mode that stories request by seeding with a number below
+    /// 1000, if they have.
+    cycle: Option<u16>,
+}
+
+impl Rng {
+    pub(crate) fn new(mode: RandomMode) -> Self {
+        let state = match mode {
+            RandomMode::Seeded(seed) => seed,
+            _ => entropy(),
+        };
+        Self {
+            mode,
+            state: scramble(state),
+            counter: 0,
+            cycle: None,
+        }
+    }
+    /// Performs the `random` opcode: a positive range returns a number from 1 to the range, a
+    /// negative range seeds the generator predictably and 0 reseeds it randomly. Seeding returns
+    /// 0.
+    pub(crate) fn random(&mut self, range: i16) -> u16 {
+        if range > 0 {
+            self.next(range as u16)
+        } else {
+            if self.mode != RandomMode::Sequential {
+                if range < 0 {
+                    self.seed((range as i32).unsigned_abs() as u16);
+                } else {
+                    self.cycle = None;
+                    self.state = match self.mode {
+                        RandomMode::Random => scramble(entropy()),
+                        _ => self.next_raw(),
+                    };
+                }
+            }
+            0
+        }
+    }
+    fn seed(&mut self, seed: u16) {
+        // the standard recommends counting up to small seeds, which is easier to test with
+        if seed < 1000 {
+            self.cycle = Some(seed);
+            self.counter = 0;
+        } else {
+            self.cycle = None;
+            self.state = scramble(seed as u64);
+        }
+    }
+    fn next(&mut self, range: u16) -> u16 {
+        let value = match (self.mode, self.cycle) {
+            (RandomMode::Sequential, _) => {
+                let value = self.counter;
+                self.counter = self.counter.wrapping_add(1);
+                value
+            }
+            (_, Some(cycle)) => {
+                let value = self.counter;
+                self.counter = (self.counter + 1) % cycle;
+                value
+            }
+            _ => (self.next_raw() >> 32) as u16,
+        };
+        value % range + 1
+    }
+    /// Advances the xorshift64* generator.
+    fn next_raw(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+/// Spreads a seed's bits out, since xorshift can't start from 0 and does poorly from small seeds.
+fn scramble(seed: u64) -> u64 {
+    let mut x = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    match x ^ (x >> 31) {
+        0 => 1,
+        x => x,
+    }
+}
+
+fn entropy() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(time) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(time.as_nanos());
+    }
+    hasher.finish()
+}
+
+impl ZMachine {
+    /// Sets how the `random` opcode generates numbers, starting a new sequence.
+    pub fn set_random_mode(&mut self, mode: RandomMode) {
+        self.rng = Rng::new(mode);
+    }
+    /// Returns how the `random` opcode generates numbers.
+    pub fn random_mode(&self) -> RandomMode {
+        self.rng.mode
+    }
+    /// Performs the `random` opcode: a positive range returns a number from 1 to the range, a
+    /// negative range seeds the generator predictably and 0 reseeds it randomly. Seeding returns
+    /// 0.
+    pub fn random(&mut self, range: i16) -> u16 {
+        self.rng.random(range)
+    }
+}
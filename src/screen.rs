@@ -0,0 +1,243 @@
+use crate::*;
+
+/// One of the two windows that stories before version 6 print to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Window {
+    /// The main window, which text scrolls up through.
+    Lower,
+    /// The window at the top of the screen, split off with `split_window`, which has a cursor
+    /// the story moves itself. Status lines are usually drawn in it.
+    Upper,
+}
+
+/// The style that text is printed in, as set by `set_text_style`. Every style is off for roman.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextStyle {
+    /// Reverse video.
+    pub reverse: bool,
+    /// Bold.
+    pub bold: bool,
+    /// Italic.
+    pub italic: bool,
+    /// A fixed-pitch font.
+    pub fixed: bool,
+}
+
+impl TextStyle {
+    /// Returns the style given by the bits of a `set_text_style` operand.
+    pub fn from_bits(bits: Word) -> Self {
+        Self {
+            reverse: bits & 1 != 0,
+            bold: bits & 2 != 0,
+            italic: bits & 4 != 0,
+            fixed: bits & 8 != 0,
+        }
+    }
+}
+
+/// What the story has done to the screen, kept so that it can be asked for back, and so that it
+/// survives being snapshotted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Screen {
+    pub(crate) window: Window,
+    pub(crate) upper_height: Word,
+    /// The upper window's cursor, as a line and column counted from 1.
+    pub(crate) cursor: (Word, Word),
+    pub(crate) style: TextStyle,
+    pub(crate) buffered: bool,
+    pub(crate) font: Word,
+    /// The foreground and background colors, as Z-machine color numbers.
+    pub(crate) colors: (Word, Word),
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self {
+            window: Window::Lower,
+            upper_height: 0,
+            cursor: (1, 1),
+            style: TextStyle::default(),
+            buffered: true,
+            font: 1,
+            colors: (1, 1),
+        }
+    }
+}
+
+impl ZMachine {
+    /// Returns the window that text is being printed to.
+    pub fn current_window(&self) -> Window {
+        self.screen.window
+    }
+    /// Returns how many lines the upper window has.
+    pub fn upper_window_height(&self) -> Word {
+        self.screen.upper_height
+    }
+    /// Returns the upper window's cursor, as a line and column counted from 1.
+    pub fn cursor(&self) -> (Word, Word) {
+        self.screen.cursor
+    }
+    /// Returns the style that text is being printed in.
+    pub fn text_style(&self) -> TextStyle {
+        self.screen.style
+    }
+    /// Performs the `split_window` opcode. In version 3 the new upper window is cleared.
+    pub(crate) fn split_window(&mut self, lines: Word) {
+        self.screen.upper_height = lines;
+        if self.screen.cursor.0 > lines {
+            self.screen.cursor = (1, 1);
+        }
+        let clear = self.version() == Version::V3 && lines > 0;
+        if let Some(frontend) = &mut self.frontend {
+            frontend.split_window(lines);
+            if clear {
+                frontend.erase_window(Window::Upper);
+            }
+        }
+    }
+    /// Performs the `set_window` opcode. Selecting the upper window moves its cursor to the top
+    /// left.
+    pub(crate) fn set_window(&mut self, window: Word) {
+        let window = if window == 0 {
+            Window::Lower
+        } else {
+            Window::Upper
+        };
+        self.screen.window = window;
+        if window == Window::Upper {
+            self.screen.cursor = (1, 1);
+        }
+        if let Some(frontend) = &mut self.frontend {
+            frontend.set_window(window);
+        }
+    }
+    /// Performs the `erase_window` opcode: -1 unsplits the screen and clears it, and -2 clears it
+    /// without unsplitting.
+    pub(crate) fn erase_window(&mut self, window: i16) {
+        let windows: &[Window] = match window {
+            0 => &[Window::Lower],
+            1 => &[Window::Upper],
+            -1 | -2 => &[Window::Lower, Window::Upper],
+            _ => &[],
+        };
+        if window == -1 {
+            self.split_window(0);
+            self.set_window(0);
+        }
+        if windows.contains(&Window::Upper) {
+            self.screen.cursor = (1, 1);
+        }
+        if let Some(frontend) = &mut self.frontend {
+            for &window in windows {
+                frontend.erase_window(window);
+            }
+        }
+    }
+    /// Performs the `erase_line` opcode, which only does anything when its operand is 1.
+    pub(crate) fn erase_line(&mut self, value: Word) {
+        if value == 1 {
+            if let Some(frontend) = &mut self.frontend {
+                frontend.erase_line();
+            }
+        }
+    }
+    /// Performs the `set_cursor` opcode. The cursor can only be moved in the upper window.
+    pub(crate) fn set_cursor(&mut self, line: Word, column: Word) {
+        if self.screen.window != Window::Upper {
+            return;
+        }
+        self.screen.cursor = (line, column);
+        if let Some(frontend) = &mut self.frontend {
+            frontend.set_cursor(line, column);
+        }
+    }
+    /// Performs the `set_text_style` opcode: 0 is roman, and anything else adds to the style.
+    pub(crate) fn set_text_style(&mut self, bits: Word) {
+        let style = if bits == 0 {
+            TextStyle::default()
+        } else {
+            let add = TextStyle::from_bits(bits);
+            let style = self.screen.style;
+            TextStyle {
+                reverse: style.reverse || add.reverse,
+                bold: style.bold || add.bold,
+                italic: style.italic || add.italic,
+                fixed: style.fixed || add.fixed,
+            }
+        };
+        self.screen.style = style;
+        if let Some(frontend) = &mut self.frontend {
+            frontend.set_text_style(style);
+        }
+    }
+    /// Performs the `buffer_mode` opcode.
+    pub(crate) fn buffer_mode(&mut self, buffered: bool) {
+        self.screen.buffered = buffered;
+        if let Some(frontend) = &mut self.frontend {
+            frontend.set_buffered(buffered);
+        }
+    }
+    /// Performs the `set_colour` opcode. Color 0 keeps the current color and 1 is the default.
+    pub(crate) fn set_colors(&mut self, foreground: Word, background: Word) {
+        let (current_foreground, current_background) = self.screen.colors;
+        let foreground = if foreground == 0 {
+            current_foreground
+        } else {
+            foreground
+        };
+        let background = if background == 0 {
+            current_background
+        } else {
+            background
+        };
+        self.screen.colors = (foreground, background);
+        if let Some(frontend) = &mut self.frontend {
+            frontend.set_colors(foreground, background);
+        }
+    }
+    /// Performs the `set_true_colour` opcode, passing the colors straight to the frontend.
+    pub(crate) fn set_true_colors(&mut self, foreground: Word, background: Word) {
+        if let Some(frontend) = &mut self.frontend {
+            frontend.set_true_colors(foreground, background);
+        }
+    }
+    /// Performs the `set_font` opcode. Returns the previous font, or 0 if the font isn't
+    /// available; font 0 asks for the current font without changing it.
+    pub(crate) fn set_font(&mut self, font: Word) -> Word {
+        let previous = self.screen.font;
+        if font == 0 {
+            return previous;
+        }
+        let available = match &self.frontend {
+            Some(frontend) => frontend.font_available(font),
+            None => font == 1 || font == 4,
+        };
+        if !available {
+            return 0;
+        }
+        self.screen.font = font;
+        if let Some(frontend) = &mut self.frontend {
+            frontend.set_font(font);
+        }
+        previous
+    }
+    /// Moves the upper window's cursor past printed text.
+    pub(crate) fn advance_cursor(&mut self, text: &str) {
+        if self.screen.window != Window::Upper {
+            return;
+        }
+        let (mut line, mut column) = self.screen.cursor;
+        for ch in text.chars() {
+            if ch == '\n' {
+                line = line.saturating_add(1);
+                column = 1;
+            } else {
+                column = column.saturating_add(1);
+            }
+        }
+        self.screen.cursor = (line, column);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize, Serializer};
+use thiserror::Error;
+
+/// The state of a running story, which can be restored into a machine that has loaded the same
+/// story. Unlike a saved game, it's taken between any two instructions and includes input that
+/// the story is suspended waiting for, so a story played with [`resume`](ZMachine::resume) can
+/// be put away between turns and carried on elsewhere. With the `serde` feature it can be
+/// serialized; memory is stored as the bytes that differ from the story, to keep it small.
+///
+/// Window and stream state is included, but the frontend isn't told about it when it's restored.
+/// The frontend, sound player, trace and fault policies belong to the host and aren't included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Snapshot {
+    version: u16,
+    story: StoryId,
+    flags2: Word,
+    memory: Vec<MemoryChange>,
+    pc: usize,
+    stack: Vec<Word>,
+    frames: Vec<FrameSnapshot>,
+    running: bool,
+    has_quit: bool,
+    last_return: Word,
+    rng: Rng,
+    suspend: Suspend,
+    captured_text: Option<String>,
+    screen: Screen,
+    streams: StreamsSnapshot,
+}
+
+/// The snapshot format's version, which is changed whenever what's stored changes.
+const SNAPSHOT_VERSION: u16 = 1;
+
+/// What a snapshot's story is known by: its release, serial code, checksum and length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct StoryId {
+    release: Word,
+    serial: [u8; 6],
+    checksum: Word,
+    len: usize,
+}
+
+/// A run of dynamic memory that differs from the story.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct MemoryChange {
+    address: usize,
+    bytes: Vec<u8>,
+}
+
+/// A call frame, with plain addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct FrameSnapshot {
+    routine: usize,
+    return_pc: usize,
+    store: Option<u8>,
+    locals: Vec<Word>,
+    arg_count: usize,
+    stack_base: usize,
+}
+
+/// The selected streams, with plain addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct StreamsSnapshot {
+    screen: bool,
+    memory: Vec<(usize, Word)>,
+    commands: bool,
+    input: Word,
+}
+
+/// An error restoring a [`Snapshot`].
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    /// The snapshot was taken of a different story, or a different release of it.
+    #[error("Snapshot was taken of a different story")]
+    WrongStory,
+    /// The snapshot was made by a version of this crate that stored something different.
+    #[error("Snapshot version {0} isn't supported")]
+    UnsupportedVersion(u16),
+    /// The snapshot doesn't describe a state the story could be in, so it's been corrupted.
+    #[error("Invalid snapshot: {0}")]
+    Invalid(&'static str),
+}
+
+/// Unchanged bytes shorter than this between two changes are stored as part of one change, which
+/// is smaller than starting another.
+const MIN_GAP: usize = 4;
+
+impl StoryId {
+    fn of(story: &[u8]) -> Self {
+        let mut serial = [0; 6];
+        let start = ByteAddress::SERIAL_CODE.0;
+        serial.copy_from_slice(&story[start..(start + 6)]);
+        let word = |addr: ByteAddress| Word::from_be_bytes([story[addr.0], story[addr.0 + 1]]);
+        Self {
+            release: word(ByteAddress::RELEASE_NUMBER),
+            serial,
+            checksum: word(ByteAddress::FILE_CHECKSUM),
+            len: story.len(),
+        }
+    }
+}
+
+impl From<&Frame> for FrameSnapshot {
+    fn from(frame: &Frame) -> Self {
+        Self {
+            routine: frame.routine.0,
+            return_pc: frame.return_pc.0,
+            store: frame.store,
+            locals: frame.locals.to_vec(),
+            arg_count: frame.arg_count,
+            stack_base: frame.stack_base,
+        }
+    }
+}
+
+impl From<&Streams> for StreamsSnapshot {
+    fn from(streams: &Streams) -> Self {
+        Self {
+            screen: streams.screen,
+            memory: streams
+                .memory
+                .iter()
+                .map(|stream| (stream.table.0, stream.len))
+                .collect(),
+            commands: streams.commands,
+            input: streams.input,
+        }
+    }
+}
+
+impl ZMachine {
+    /// Returns a snapshot of the story's state, to be restored with
+    /// [`restore_snapshot`](ZMachine::restore_snapshot).
+    pub fn snapshot(&self) -> Snapshot {
+        let mut memory: Vec<MemoryChange> = Vec::new();
+        for addr in 64..self.dynamic_end() {
+            if self.memory[addr] == self.original[addr] {
+                continue;
+            }
+            match memory.last_mut() {
+                Some(change) if addr - (change.address + change.bytes.len()) < MIN_GAP => {
+                    let start = change.address + change.bytes.len();
+                    change.bytes.extend_from_slice(&self.memory[start..=addr]);
+                }
+                _ => memory.push(MemoryChange {
+                    address: addr,
+                    bytes: vec![self.memory[addr]],
+                }),
+            }
+        }
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            story: StoryId::of(&self.original),
+            flags2: self.word(ByteAddress::FLAGS2),
+            memory,
+            pc: self.pc.0,
+            stack: self.stack.clone(),
+            frames: self.frames.iter().map(FrameSnapshot::from).collect(),
+            running: self.running,
+            has_quit: self.has_quit,
+            last_return: self.last_return,
+            rng: self.rng.clone(),
+            suspend: self.suspend.clone(),
+            captured_text: self.captured_text.clone(),
+            screen: self.screen.clone(),
+            streams: StreamsSnapshot::from(&self.streams),
+        }
+    }
+    /// Puts the story back in the state a snapshot was taken in. The snapshot must be of the same
+    /// story; it's checked, and nothing is changed if it's wrong. As with `restore`, the header is
+    /// left as it is apart from the flags the story may change, so the interpreter's fields stay
+    /// filled in.
+    pub fn restore_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(snapshot.version));
+        }
+        if snapshot.story != StoryId::of(&self.original) {
+            return Err(SnapshotError::WrongStory);
+        }
+        let end = self.dynamic_end();
+        for change in &snapshot.memory {
+            if change.address < 64 || change.address + change.bytes.len() > end {
+                return Err(SnapshotError::Invalid(
+                    "memory change outside dynamic memory",
+                ));
+            }
+        }
+        if snapshot.pc >= self.len_bytes() {
+            return Err(SnapshotError::Invalid("program counter outside the story"));
+        }
+        if snapshot.frames.is_empty() {
+            return Err(SnapshotError::Invalid("no call frames"));
+        }
+        let mut stack_base = 0;
+        let mut frames = Vec::with_capacity(snapshot.frames.len());
+        for frame in &snapshot.frames {
+            if frame.stack_base < stack_base || frame.stack_base > snapshot.stack.len() {
+                return Err(SnapshotError::Invalid("call frame outside the stack"));
+            }
+            if frame.locals.len() > 15 || frame.arg_count > 7 {
+                return Err(SnapshotError::Invalid("too many locals or arguments"));
+            }
+            stack_base = frame.stack_base;
+            frames.push(Frame {
+                routine: ByteAddress(frame.routine),
+                return_pc: ByteAddress(frame.return_pc),
+                store: frame.store,
+                locals: frame.locals.iter().copied().collect(),
+                arg_count: frame.arg_count,
+                stack_base: frame.stack_base,
+            });
+        }
+        if snapshot.streams.memory.len() > MAX_MEMORY_STREAMS {
+            return Err(SnapshotError::Invalid("output stream 3 nested too deeply"));
+        }
+        for &(table, len) in &snapshot.streams.memory {
+            if table + 2 + len as usize > end {
+                return Err(SnapshotError::Invalid(
+                    "output stream table outside dynamic memory",
+                ));
+            }
+        }
+        self.memory[64..end].copy_from_slice(&self.original[64..end]);
+        for change in &snapshot.memory {
+            let start = change.address;
+            self.memory[start..(start + change.bytes.len())].copy_from_slice(&change.bytes);
+        }
+        self.write_word(ByteAddress::FLAGS2, snapshot.flags2);
+        self.pc = ByteAddress(snapshot.pc);
+        self.stack = snapshot.stack.clone();
+        self.frames = frames;
+        self.running = snapshot.running;
+        self.has_quit = snapshot.has_quit;
+        self.last_return = snapshot.last_return;
+        self.rng = snapshot.rng.clone();
+        self.suspend = snapshot.suspend.clone();
+        self.captured_text = snapshot.captured_text.clone();
+        self.screen = snapshot.screen.clone();
+        self.streams = Streams {
+            screen: snapshot.streams.screen,
+            memory: snapshot
+                .streams
+                .memory
+                .iter()
+                .map(|&(table, len)| MemoryStream {
+                    table: ByteAddress(table),
+                    len,
+                })
+                .collect(),
+            commands: snapshot.streams.commands,
+            input: snapshot.streams.input,
+        };
+        self.sound.playing = None;
+        self.write_error = None;
+        Ok(())
+    }
+    /// Returns where dynamic memory ends in the story as loaded, which the story can't change.
+    fn dynamic_end(&self) -> usize {
+        let addr = ByteAddress::STATIC_MEMORY_LOCATION.0;
+        let end = Word::from_be_bytes([self.original[addr], self.original[addr + 1]]) as usize;
+        end.min(self.original.len()).min(self.len_bytes())
+    }
+}
+
+/// Serializes the story's [`Snapshot`], which can be restored with
+/// [`restore_snapshot`](ZMachine::restore_snapshot) once deserialized.
+#[cfg(feature = "serde")]
+impl Serialize for ZMachine {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.snapshot().serialize(serializer)
+    }
+}
@@ -0,0 +1,261 @@
+use crate::*;
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::sync::{Arc, Mutex};
+
+/// One of the two bleeps every interpreter can make, which are sound effects 1 and 2.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bleep {
+    /// A high-pitched bleep.
+    High,
+    /// A low-pitched bleep.
+    Low,
+}
+
+/// The format of a sound's sample data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SoundFormat {
+    /// An AIFF file.
+    Aiff,
+    /// An Ogg Vorbis file.
+    Ogg,
+    /// A MOD music file.
+    Mod,
+    /// Some other format, identified by its Blorb chunk type.
+    Other([u8; 4]),
+}
+
+/// The sample data of a sound, taken from a Blorb `Snd ` resource.
+#[derive(Debug, Copy, Clone)]
+pub struct Sound<'a> {
+    /// The format of the data.
+    pub format: SoundFormat,
+    /// The complete sound file.
+    pub data: &'a [u8],
+}
+
+impl<'a> Sound<'a> {
+    fn from_chunk(chunk: BlorbChunk<'a>) -> Self {
+        match &chunk.kind {
+            b"FORM" => Sound {
+                format: SoundFormat::Aiff,
+                data: chunk.raw,
+            },
+            b"OGGV" => Sound {
+                format: SoundFormat::Ogg,
+                data: chunk.data,
+            },
+            b"MOD " => Sound {
+                format: SoundFormat::Mod,
+                data: chunk.data,
+            },
+            _ => Sound {
+                format: SoundFormat::Other(chunk.kind),
+                data: chunk.data,
+            },
+        }
+    }
+}
+
+/// A frontend that plays the sounds a story asks for with `sound_effect`.
+///
+/// Sounds are identified by their resource number. The sound is [`None`] if the story wasn't
+/// loaded from a Blorb file containing it, in which case the player may find it elsewhere (e.g. in
+/// Infocom's separate sound files) or stay quiet.
+pub trait SoundPlayer {
+    /// Plays one of the bleeps.
+    fn bleep(&mut self, bleep: Bleep);
+    /// Loads a sound in advance, ready to be started. Players that don't need to do this can
+    /// ignore it.
+    fn prepare(&mut self, _number: u16, _sound: Option<Sound>) {}
+    /// Starts playing a sound, stopping any sound that is already playing. `volume` ranges from 1
+    /// to 8, or is 255 for the loudest volume available. `repeats` is the number of times to play
+    /// the sound, or 255 to repeat it forever.
+    fn start(&mut self, number: u16, sound: Option<Sound>, volume: u8, repeats: u8);
+    /// Stops a sound if it is playing.
+    fn stop(&mut self, number: u16);
+    /// Stops a sound if it is playing, and unloads it since the story won't need it again.
+    fn finish_with(&mut self, number: u16) {
+        self.stop(number);
+    }
+    /// Returns the number of a sound that has finished playing by itself since the last call, if
+    /// there is one. This is checked before each instruction, so that the story's routine for the
+    /// sound can be called.
+    fn finished(&mut self) -> Option<u16> {
+        None
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct SoundState {
+    pub(crate) player: Option<Box<dyn SoundPlayer + Send>>,
+    pub(crate) playing: Option<(u16, Option<ByteAddress>)>,
+}
+
+impl ZMachine {
+    /// Sets the frontend used to play sound effects. Stories only use sound effects if the header
+    /// says they are available; see [`HeaderBuilder::sound_effects_available`].
+    pub fn set_sound_player(&mut self, player: impl SoundPlayer + Send + 'static) {
+        self.sound.player = Some(Box::new(player));
+    }
+    /// Performs the `sound_effect` instruction, calling the sound player if there is one.
+    pub(crate) fn sound_effect(
+        &mut self,
+        number: u16,
+        effect: u16,
+        volume: u16,
+        routine: Option<ByteAddress>,
+    ) {
+        let repeats = if self.version() >= Version::V5 {
+            match (volume >> 8) as u8 {
+                0 => 1,
+                repeats => repeats,
+            }
+        } else {
+            self.sound_loops(number)
+        };
+        let volume = volume as u8;
+        let player = match &mut self.sound.player {
+            Some(player) => player,
+            None => return,
+        };
+        let blorb = self.blorb.as_ref();
+        let sound = || {
+            blorb
+                .and_then(|blorb| blorb.sound(number as u32))
+                .map(Sound::from_chunk)
+        };
+        match (number, effect) {
+            (0, _) => {}
+            (1, _) => player.bleep(Bleep::High),
+            (2, _) => player.bleep(Bleep::Low),
+            (_, 1) => player.prepare(number, sound()),
+            (_, 2) => {
+                player.start(number, sound(), volume, repeats);
+                self.sound.playing = Some((number, routine));
+            }
+            (_, 3) | (_, 4) => {
+                if effect == 3 {
+                    player.stop(number);
+                } else {
+                    player.finish_with(number);
+                }
+                if self.sound.playing.map(|(playing, _)| playing) == Some(number) {
+                    self.sound.playing = None;
+                }
+            }
+            _ => {}
+        }
+    }
+    /// Returns how many times a sound should play in versions 3 and 4, which take this from the
+    /// Blorb `Loop` chunk rather than the story.
+    fn sound_loops(&self, number: u16) -> u8 {
+        let data = match self.blorb.as_ref().and_then(|blorb| blorb.chunk(b"Loop")) {
+            Some(chunk) => chunk.data,
+            None => return 1,
+        };
+        data.chunks_exact(8)
+            .map(|entry| {
+                let number = u32::from_be_bytes(entry[0..4].try_into().unwrap());
+                let repeats = u32::from_be_bytes(entry[4..8].try_into().unwrap());
+                (number, repeats)
+            })
+            .find(|&(entry, _)| entry == number as u32)
+            .map_or(1, |(_, repeats)| match repeats {
+                0 => 255,
+                repeats => repeats.min(254) as u8,
+            })
+    }
+    /// Returns the routine to call for the playing sound if the player says it has finished.
+    pub(crate) fn finished_sound_routine(&mut self) -> Option<ByteAddress> {
+        let number = self.sound.player.as_mut()?.finished()?;
+        match self.sound.playing {
+            Some((playing, routine)) if playing == number => {
+                self.sound.playing = None;
+                routine
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Something that a [`SoundLog`] was asked to do.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SoundEvent {
+    /// A bleep was played.
+    Bleep(Bleep),
+    /// A sound was prepared.
+    Prepare(u16),
+    /// A sound was started.
+    Start {
+        /// The sound number.
+        number: u16,
+        /// The volume, from 1 to 8 or 255.
+        volume: u8,
+        /// The number of times to play, or 255 for forever.
+        repeats: u8,
+        /// Whether sample data was found for the sound.
+        found: bool,
+    },
+    /// A sound was stopped.
+    Stop(u16),
+    /// A sound was finished with.
+    FinishWith(u16),
+}
+
+/// A [`SoundPlayer`] that records what it is asked to do instead of playing anything. Clones share
+/// the same log, so one can be given to the machine and another kept to inspect it.
+#[derive(Debug, Clone, Default)]
+pub struct SoundLog {
+    inner: Arc<Mutex<SoundLogInner>>,
+}
+
+#[derive(Debug, Default)]
+struct SoundLogInner {
+    events: Vec<SoundEvent>,
+    finished: VecDeque<u16>,
+}
+
+impl SoundLog {
+    /// Creates an empty log.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the events recorded so far.
+    pub fn events(&self) -> Vec<SoundEvent> {
+        self.inner.lock().unwrap().events.clone()
+    }
+    /// Pretends that a sound has finished playing, so that the story's routine for it is called.
+    pub fn end(&self, number: u16) {
+        self.inner.lock().unwrap().finished.push_back(number);
+    }
+    fn record(&mut self, event: SoundEvent) {
+        self.inner.lock().unwrap().events.push(event);
+    }
+}
+
+impl SoundPlayer for SoundLog {
+    fn bleep(&mut self, bleep: Bleep) {
+        self.record(SoundEvent::Bleep(bleep));
+    }
+    fn prepare(&mut self, number: u16, _sound: Option<Sound>) {
+        self.record(SoundEvent::Prepare(number));
+    }
+    fn start(&mut self, number: u16, sound: Option<Sound>, volume: u8, repeats: u8) {
+        self.record(SoundEvent::Start {
+            number,
+            volume,
+            repeats,
+            found: sound.is_some(),
+        });
+    }
+    fn stop(&mut self, number: u16) {
+        self.record(SoundEvent::Stop(number));
+    }
+    fn finish_with(&mut self, number: u16) {
+        self.record(SoundEvent::FinishWith(number));
+    }
+    fn finished(&mut self) -> Option<u16> {
+        self.inner.lock().unwrap().finished.pop_front()
+    }
+}
@@ -0,0 +1,100 @@
+use crate::*;
+
+/// The most tables that output stream 3 can be nested into.
+pub(crate) const MAX_MEMORY_STREAMS: usize = 16;
+
+/// Which output streams are selected, and the input stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Streams {
+    /// Stream 1, the screen.
+    pub(crate) screen: bool,
+    /// Stream 3: the tables being printed to, innermost last.
+    pub(crate) memory: Vec<MemoryStream>,
+    /// Stream 4, the player's commands.
+    pub(crate) commands: bool,
+    /// The input stream: 0 for the keyboard, 1 for a file of commands.
+    pub(crate) input: Word,
+}
+
+/// A table that output stream 3 is printing to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct MemoryStream {
+    pub(crate) table: ByteAddress,
+    pub(crate) len: Word,
+}
+
+impl Default for Streams {
+    fn default() -> Self {
+        Self {
+            screen: true,
+            memory: Vec::new(),
+            commands: false,
+            input: 0,
+        }
+    }
+}
+
+impl ZMachine {
+    /// Performs the `output_stream` opcode: a positive stream number selects the stream and a
+    /// negative one deselects it. Stream 3 prints to `table` instead of any other stream until
+    /// it's deselected, then stores how many characters were printed at the start of the table.
+    pub(crate) fn output_stream(
+        &mut self,
+        number: i16,
+        table: Option<Word>,
+    ) -> Result<(), ExecuteError> {
+        match number {
+            1 | -1 => self.streams.screen = number > 0,
+            2 | -2 => self.write_bit(BitAddress::TRANSCRIPTING_ON, number > 0),
+            3 => {
+                if self.streams.memory.len() == MAX_MEMORY_STREAMS {
+                    return Err(ExecuteError::StreamsTooDeep);
+                }
+                let table = table.ok_or(ExecuteError::InvalidInstructionFormat(self.pc.0))?;
+                self.streams.memory.push(MemoryStream {
+                    table: table.into(),
+                    len: 0,
+                });
+            }
+            -3 => {
+                if let Some(stream) = self.streams.memory.pop() {
+                    self.write_word(stream.table, stream.len);
+                }
+            }
+            4 | -4 => self.streams.commands = number > 0,
+            _ => {}
+        }
+        Ok(())
+    }
+    /// Performs the `input_stream` opcode. Only the keyboard is read from, so this is remembered
+    /// but has no effect.
+    pub(crate) fn input_stream(&mut self, number: Word) {
+        self.streams.input = number;
+    }
+    /// Returns whether output stream 3 is selected.
+    pub fn printing_to_memory(&self) -> bool {
+        !self.streams.memory.is_empty()
+    }
+    /// Prints text to the innermost output stream 3 table, as ZSCII. Returns whether there was a
+    /// table to print to.
+    pub(crate) fn print_to_memory(&mut self, text: &str) -> bool {
+        let mut stream = match self.streams.memory.last() {
+            Some(&stream) => stream,
+            None => return false,
+        };
+        for ch in text.chars() {
+            let zscii = match ch {
+                '\n' => Some(13),
+                _ => self.char_zscii(ch),
+            };
+            if let Some(zscii) = zscii {
+                self.write_byte(stream.table + 2 + stream.len as usize, zscii);
+                stream.len = stream.len.wrapping_add(1);
+            }
+        }
+        if let Some(last) = self.streams.memory.last_mut() {
+            *last = stream;
+        }
+        true
+    }
+}
@@ -0,0 +1,138 @@
+use crate::vm::opcodes::var;
+use crate::*;
+
+/// The kind of input that a story is waiting for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WaitingFor {
+    /// A line of text, for `sread` or `aread`.
+    Line,
+    /// A single key press, for `read_char`.
+    Char,
+}
+
+/// Input for a suspended story.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Input {
+    /// A line of text. A story waiting for a key press gets its first character, or the return key
+    /// if it's empty.
+    Line(String),
+    /// A key press, as [`Frontend::read_char`] returns it. A story waiting for a line gets a line
+    /// of just that character.
+    Key(char),
+}
+
+/// Where a story run with [`resume`](ZMachine::resume) stopped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    /// The story is suspended until it's given input with
+    /// [`resume_with`](ZMachine::resume_with).
+    Suspended(WaitingFor),
+    /// The story has quit.
+    Quit,
+}
+
+/// Whether the story suspends for input rather than asking the frontend, and the input given to
+/// it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Suspend {
+    pub(crate) enabled: bool,
+    pub(crate) input: Option<Input>,
+    pub(crate) waiting_for: Option<WaitingFor>,
+}
+
+impl ZMachine {
+    /// Runs the story until it needs input or quits. From the first call on, `sread`, `aread`
+    /// and `read_char` suspend the story instead of calling [`Frontend::read_line`] or
+    /// [`Frontend::read_char`], which are never called again; everything else still goes to the
+    /// frontend. Once the story has quit, nothing more is run.
+    pub fn resume(&mut self) -> Result<State, RuntimeError> {
+        self.suspend.enabled = true;
+        if self.has_quit {
+            return Ok(State::Quit);
+        }
+        self.suspend.waiting_for = None;
+        self.running = true;
+        while self.running && self.suspend.waiting_for.is_none() {
+            self.step()?;
+        }
+        Ok(match self.suspend.waiting_for {
+            Some(waiting_for) if self.running => State::Suspended(waiting_for),
+            _ => State::Quit,
+        })
+    }
+    /// Gives a suspended story input, then carries on running it as
+    /// [`resume`](ZMachine::resume) does.
+    pub fn resume_with(&mut self, input: Input) -> Result<State, RuntimeError> {
+        self.give_input(input);
+        self.resume()
+    }
+    /// Gives the story input, for it to read when it's next run. Input given before is replaced
+    /// if the story hasn't read it yet.
+    pub(crate) fn give_input(&mut self, input: Input) {
+        self.suspend.input = Some(input);
+        self.suspend.waiting_for = None;
+    }
+    /// Returns the input that the story is suspended waiting for, if it is.
+    pub fn waiting_for(&self) -> Option<WaitingFor> {
+        self.suspend.waiting_for
+    }
+    /// Takes the input given to a suspended story as a line.
+    pub(crate) fn take_input_line(&mut self) -> Option<String> {
+        match self.suspend.input.take()? {
+            Input::Line(line) => Some(line),
+            Input::Key(ch) => Some(ch.to_string()),
+        }
+    }
+    /// Takes the input given to a suspended story as a key press.
+    pub(crate) fn take_input_key(&mut self) -> Option<char> {
+        match self.suspend.input.take()? {
+            Input::Line(line) => Some(line.chars().next().unwrap_or('\n')),
+            Input::Key(ch) => Some(ch),
+        }
+    }
+    /// Returns the input that an instruction would suspend the story for, if suspending is on and
+    /// none has been given. This is checked before the operands are read, so that the instruction
+    /// can be run again once there's input.
+    pub(crate) fn input_needed(
+        &self,
+        form: OpcodeForm,
+        desc: OperandsDesc,
+        opcode: u8,
+    ) -> Option<WaitingFor> {
+        if !self.suspend.enabled || self.suspend.input.is_some() {
+            return None;
+        }
+        if desc != OperandsDesc::Var || form == OpcodeForm::Extended {
+            return None;
+        }
+        match opcode {
+            var::sread => Some(WaitingFor::Line),
+            var::read_char if self.version() >= Version::V4 => Some(WaitingFor::Char),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl ZMachine {
+    /// Runs the story until it quits, awaiting input from `read` whenever the story is suspended,
+    /// so that a player waiting to type doesn't tie up a thread. Stops if `read` returns
+    /// [`None`].
+    pub async fn run_async<F, Fut>(&mut self, mut read: F) -> Result<(), RuntimeError>
+    where
+        F: FnMut(WaitingFor) -> Fut,
+        Fut: std::future::Future<Output = Option<Input>>,
+    {
+        let mut state = self.resume()?;
+        while let State::Suspended(waiting_for) = state {
+            match read(waiting_for).await {
+                Some(input) => state = self.resume_with(input)?,
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
 use crate::*;
+use proptest::prelude::*;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Mutex};
 
 #[test]
 fn read_zstring() {
@@ -36,3 +39,1471 @@ fn read_object_name() {
     let object_6 = z_machine.object(6);
     assert_eq!(object_6.read_name(), "control panel")
 }
+
+#[test]
+fn read_header() {
+    let z_machine = ZMachine::from_file("minizork.z3").unwrap();
+    let header = z_machine.header();
+    assert_eq!(header.version(), Version::V3);
+    assert_eq!(header.release(), 34);
+    assert_eq!(header.serial_str(), Some("871124"));
+}
+
+#[test]
+fn apply_header_builder() {
+    let mut story = story_bytes(5);
+    story[0x11] = 0b1111_1000; // sounds, colors, mouse, undo, pictures desired
+    let mut z_machine =
+        ZMachine::new_with_options(story, LoadOptions::new().lenient(true)).unwrap();
+    HeaderBuilder::new()
+        .interpreter_number(InterpreterNumber::IbmPc)
+        .interpreter_version(b'M')
+        .standard_revision(1, 1)
+        .bold_available(true)
+        .colors_available(true)
+        .sound_effects_available(false)
+        .undo_available(true)
+        .mouse_available(false)
+        .apply(&mut z_machine);
+    let header = z_machine.header();
+    assert_eq!(header.interpreter_number(), InterpreterNumber::IbmPc);
+    assert_eq!(header.interpreter_version(), b'M');
+    assert_eq!(header.standard_revision(), (1, 1));
+    match header.flags1() {
+        Flags1::Late {
+            bold_available,
+            colors_available,
+            italic_available,
+            ..
+        } => assert!(bold_available && colors_available && !italic_available),
+        flags => panic!("Expected late flags, got {:?}", flags),
+    }
+    let flags2 = header.flags2();
+    assert!(flags2.undo_desired && flags2.colors_desired);
+    assert!(!flags2.mouse_desired && !flags2.sounds_desired);
+    // pictures are only negotiated in version 6
+    assert!(flags2.pictures_desired);
+}
+
+#[test]
+fn header_extension_bounds() {
+    let mut story = story_bytes(5);
+    story[0x36] = 0x03; // extension table at 0x380
+    story[0x37] = 0x80;
+    story[0x381] = 2; // mouse coordinates only
+    let mut z_machine =
+        ZMachine::new_with_options(story, LoadOptions::new().lenient(true)).unwrap();
+    z_machine.set_mouse_position(12, 34);
+    z_machine.set_true_default_colors(0x7FFF, 0);
+    let ext = z_machine.header_extension().unwrap();
+    assert_eq!(ext.len(), 2);
+    assert_eq!((ext.mouse_x(), ext.mouse_y()), (Some(12), Some(34)));
+    assert_eq!(ext.unicode_table_location(), None);
+    assert_eq!(ext.true_default_foreground_color(), None);
+    assert_eq!(z_machine.word(ByteAddress(0x38A)), 0);
+}
+
+#[test]
+fn validate_story() {
+    let mut story = vec![0; 128];
+    story[0] = 9;
+    match ZMachine::new(story.clone()) {
+        Err(LoadError::InvalidVersion(9)) => {}
+        other => panic!("Expected invalid version, got {:?}", other.err()),
+    }
+    story[0] = 3;
+    let lenient = LoadOptions::new().lenient(true);
+    for options in &[LoadOptions::new(), lenient] {
+        match ZMachine::new_with_options(story.clone(), *options) {
+            Err(LoadError::StaticMemoryOutOfRange(0)) => {}
+            other => panic!("Expected bad static memory, got {:?}", other.err()),
+        }
+    }
+    let mut story = story_bytes(3);
+    story[0x0C] = 0x03; // globals at 0x340, running past static memory
+    match ZMachine::new_with_options(story, lenient) {
+        Err(LoadError::TableOutOfRange(StoryTable::Globals, 0x340)) => {}
+        other => panic!("Expected bad globals, got {:?}", other.err()),
+    }
+    // only a lenient load puts up with a story shorter than its header says
+    let mut story = story_bytes(3);
+    story[0x1A] = 0x10; // 0x2000 bytes long
+    match ZMachine::new(story.clone()) {
+        Err(LoadError::Truncated(0x2000, 0x400)) => {}
+        other => panic!("Expected a truncated story, got {:?}", other.err()),
+    }
+    assert!(ZMachine::new_with_options(story, lenient).is_ok());
+}
+
+fn iff_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+#[test]
+fn load_blorb() {
+    let mut story = story_bytes(5);
+    story.push(0);
+    let exec = iff_chunk(b"ZCOD", &story);
+    let snd = iff_chunk(b"OGGV", b"OggS");
+    let fspc = iff_chunk(b"Fspc", &3u32.to_be_bytes());
+    let index_len = 8 + 4 + 2 * 12;
+    let exec_offset = 12 + index_len;
+    let snd_offset = exec_offset + exec.len();
+    let mut index = 2u32.to_be_bytes().to_vec();
+    for &(usage, number, offset) in &[(b"Exec", 0u32, exec_offset), (b"Snd ", 3, snd_offset)] {
+        index.extend_from_slice(usage);
+        index.extend_from_slice(&number.to_be_bytes());
+        index.extend_from_slice(&(offset as u32).to_be_bytes());
+    }
+    let mut body = b"IFRS".to_vec();
+    body.extend(iff_chunk(b"RIdx", &index));
+    body.extend(exec);
+    body.extend(snd);
+    body.extend(fspc);
+    let blorb = iff_chunk(b"FORM", &body);
+    let z_machine =
+        ZMachine::from_blorb_with_options(blorb, LoadOptions::new().lenient(true)).unwrap();
+    assert_eq!(z_machine.len_bytes(), 0x401);
+    let blorb = z_machine.blorb().unwrap();
+    assert_eq!(blorb.sound(3).unwrap().data, b"OggS");
+    assert!(blorb.sound(4).is_none());
+    assert_eq!(blorb.frontispiece(), Some(3));
+}
+
+#[test]
+fn story_ifid() {
+    let mut story = story_bytes(5);
+    story[0x3] = 2;
+    story[0x12..0x18].copy_from_slice(b"120101");
+    story[0x1C] = 0xAB;
+    story[0x1D] = 0xCD;
+    let options = LoadOptions::new().lenient(true);
+    let z_machine = ZMachine::new_with_options(story.clone(), options).unwrap();
+    assert_eq!(z_machine.ifid(), "ZCODE-2-120101-ABCD");
+    let uuid = b"UUID://5C8C3E2A-6C1F-4B0F-9B0E-2B1F5A8C1D2E//";
+    story[64..(64 + uuid.len())].copy_from_slice(uuid);
+    let z_machine = ZMachine::new_with_options(story.clone(), options).unwrap();
+    assert_eq!(z_machine.ifid(), "5C8C3E2A-6C1F-4B0F-9B0E-2B1F5A8C1D2E");
+    story[0x12..0x18].copy_from_slice(b"871124");
+    let z_machine = ZMachine::new_with_options(story, options).unwrap();
+    assert_eq!(z_machine.ifid(), "ZCODE-2-871124");
+}
+
+#[test]
+fn parse_ifiction() {
+    let metadata = StoryMetadata::from_ifiction(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ifindex version="1.0" xmlns="http://babel.ifarchive.org/protocol/iFiction/">
+  <!-- a comment -->
+  <story>
+    <identification>
+      <ifid>ZCODE-2-120101-ABCD</ifid>
+      <format>zcode</format>
+    </identification>
+    <bibliographic>
+      <title>Tea &amp; Biscuits</title>
+      <author>A. N. Author</author>
+      <headline>An Interactive Snack</headline>
+      <firstpublished>2012</firstpublished>
+      <description>Crumbs.<br/>Everywhere.</description>
+    </bibliographic>
+  </story>
+</ifindex>"#,
+    )
+    .unwrap();
+    assert_eq!(metadata.ifids, vec!["ZCODE-2-120101-ABCD"]);
+    assert_eq!(metadata.title.as_deref(), Some("Tea & Biscuits"));
+    assert_eq!(metadata.author.as_deref(), Some("A. N. Author"));
+    assert_eq!(metadata.headline.as_deref(), Some("An Interactive Snack"));
+    assert_eq!(metadata.first_published.as_deref(), Some("2012"));
+    assert_eq!(
+        metadata.description.as_deref(),
+        Some("Crumbs.\nEverywhere.")
+    );
+}
+
+/// Builds a story with globals at 0x40 and `code` at 0x400, where execution starts.
+/// Returns a 0x400 byte story with an empty dictionary at 0x3F0, objects at 0x100, abbreviations
+/// at 0x240 and globals at 0x40, and high and static memory starting at its end.
+fn story_bytes(version: u8) -> Vec<u8> {
+    let mut story = vec![0; 0x400];
+    story[0] = version;
+    story[0x04] = 0x04; // high memory
+    story[0x06] = 0x04; // initial PC
+    story[0x08] = 0x03; // dictionary
+    story[0x09] = 0xF0;
+    story[0x0A] = 0x01; // objects
+    story[0x0D] = 0x40; // globals
+    story[0x0E] = 0x04; // static memory
+    story[0x18] = 0x02; // abbreviations
+    story[0x19] = 0x40;
+    story
+}
+
+fn code_story(version: u8, code: &[u8]) -> ZMachine {
+    let mut story = story_bytes(version);
+    story.extend_from_slice(code);
+    ZMachine::new_with_options(story, LoadOptions::new().lenient(true)).unwrap()
+}
+
+#[test]
+fn sound_effect() {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0xF5, 0x7F, 0x01, // sound_effect 1
+        0xF5, 0x50, 0x03, 0x02, 0x01, 0x08, 0x01, 0x04, // sound_effect 3 2 $0108 routine
+        0x8C, 0xFF, 0xFF, // jump to itself
+        0x00, 0x00, // padding
+        0x00, 0x0D, 0x10, 0x01, 0xB0, // routine: store g00 1; rtrue
+    ]);
+    let log = SoundLog::new();
+    z_machine.set_sound_player(log.clone());
+    for _ in 0..3 {
+        z_machine.step().unwrap();
+    }
+    assert_eq!(
+        log.events(),
+        vec![
+            SoundEvent::Bleep(Bleep::High),
+            SoundEvent::Start {
+                number: 3,
+                volume: 8,
+                repeats: 1,
+                found: false,
+            },
+        ]
+    );
+    assert_eq!(z_machine.peek_variable(16), 0);
+    log.end(3);
+    z_machine.step().unwrap();
+    assert_eq!(z_machine.peek_variable(16), 1);
+    assert_eq!(z_machine.pc(), ByteAddress(0x40B));
+}
+
+#[test]
+fn invoke_routine_input() {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0xF6, 0x7F, 0x01, 0x00, // read_char 1 -> sp
+        0xBA, // quit
+        0x00, 0xE8, 0x7F, 0x05, 0xF6, 0x7F, 0x01, 0x00, 0xB0, // routine: push 5; read_char 1 -> sp; rtrue
+        0x00, 0x9B, 0x07, // routine: ret 7
+    ]);
+    assert_eq!(
+        z_machine.resume().unwrap(),
+        State::Suspended(WaitingFor::Char)
+    );
+    match z_machine.invoke_routine(ByteAddress(0x405), &[]) {
+        Err(RuntimeError {
+            error: ExecuteError::InputInRoutine(0x405),
+            ..
+        }) => {}
+        other => panic!("Expected input in a routine, got {:?}", other),
+    }
+    // the routine is abandoned, and the story is still waiting
+    assert_eq!(z_machine.pc(), ByteAddress(0x400));
+    assert!(z_machine.call_stack()[0].stack.is_empty());
+    assert_eq!(z_machine.waiting_for(), Some(WaitingFor::Char));
+    assert_eq!(
+        z_machine.invoke_routine(ByteAddress(0x40E), &[]).unwrap(),
+        7
+    );
+    assert_eq!(z_machine.waiting_for(), Some(WaitingFor::Char));
+    assert_eq!(z_machine.resume_with(Input::Key('a')).unwrap(), State::Quit);
+    assert_eq!(z_machine.peek_variable(0), 'a' as Word);
+}
+
+#[test]
+fn random_modes() {
+    let mut z_machine = code_story(5, &[]);
+    z_machine.set_random_mode(RandomMode::Sequential);
+    let rolls: Vec<_> = (0..5).map(|_| z_machine.random(3)).collect();
+    assert_eq!(rolls, vec![1, 2, 3, 1, 2]);
+    assert_eq!(z_machine.random(-10), 0);
+    assert_eq!(z_machine.random(100), 6);
+
+    z_machine.set_random_mode("1234".parse().unwrap());
+    let first: Vec<_> = (0..20).map(|_| z_machine.random(100)).collect();
+    assert!(first.iter().all(|roll| (1..=100).contains(roll)));
+    z_machine.set_random_mode(RandomMode::Seeded(1234));
+    let second: Vec<_> = (0..20).map(|_| z_machine.random(100)).collect();
+    assert_eq!(first, second);
+
+    assert_eq!(z_machine.random(-4), 0);
+    let rolls: Vec<_> = (0..6).map(|_| z_machine.random(10)).collect();
+    assert_eq!(rolls, vec![1, 2, 3, 4, 1, 2]);
+    assert_eq!(z_machine.random(0), 0);
+    assert!(z_machine.random(1000) <= 1000);
+}
+
+/// A frontend that types preset lines and records what is printed.
+#[derive(Clone, Default)]
+struct TestFrontend {
+    output: Arc<Mutex<String>>,
+    input: Arc<Mutex<VecDeque<String>>>,
+    warnings: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestFrontend {
+    fn new(input: &[&str]) -> Self {
+        let frontend = Self::default();
+        frontend
+            .input
+            .lock()
+            .unwrap()
+            .extend(input.iter().map(|line| line.to_string()));
+        frontend
+    }
+    fn output(&self) -> String {
+        self.output.lock().unwrap().clone()
+    }
+    fn warnings(&self) -> Vec<String> {
+        self.warnings.lock().unwrap().clone()
+    }
+}
+
+impl Frontend for TestFrontend {
+    fn print(&mut self, text: &str) {
+        self.output.lock().unwrap().push_str(text);
+    }
+    fn read_line(&mut self, _max_len: usize) -> Option<String> {
+        self.input.lock().unwrap().pop_front()
+    }
+    fn warn(&mut self, message: &str) {
+        self.warnings.lock().unwrap().push(message.to_string());
+    }
+    fn can_print(&self, ch: char) -> bool {
+        ch != '\u{263A}'
+    }
+}
+
+#[test]
+fn screen_and_streams() {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0xEA, 0x7F, 0x01, // split_window 1
+        0xEB, 0x7F, 0x01, // set_window 1
+        0xEF, 0x5F, 0x01, 0x05, // set_cursor 1 5
+        0xB2, 0xB5, 0xC5, // print "hi"
+        0xF0, 0x3F, 0x03, 0x00, // get_cursor $300
+        0xEB, 0x7F, 0x00, // set_window 0
+        0xF3, 0x4F, 0x03, 0x03, 0x10, // output_stream 3 $310
+        0xB2, 0xB5, 0xC5, // print "hi"
+        0xF3, 0x3F, 0xFF, 0xFD, // output_stream -3
+        0xBE, 0x09, 0xFF, 0x10, // save_undo -> g00
+        0xBE, 0x0A, 0xFF, 0x12, // restore_undo -> g02
+        0xF1, 0x7F, 0x02, // set_text_style 2
+        0xBE, 0x04, 0x7F, 0x03, 0x11, // set_font 3 -> g01
+        0xED, 0x3F, 0xFF, 0xFF, // erase_window -1
+        0xB2, 0xB5, 0xC5, // print "hi"
+        0xBA, // quit
+    ]);
+    let frontend = TestFrontend::new(&[]);
+    z_machine.set_frontend(frontend.clone());
+    z_machine.run().unwrap();
+    // what went to output stream 3 wasn't printed
+    assert_eq!(frontend.output(), "hihi");
+    assert_eq!(z_machine.word(ByteAddress(0x300)), 1);
+    assert_eq!(z_machine.word(ByteAddress(0x302)), 7);
+    assert_eq!(z_machine.word(ByteAddress(0x310)), 2);
+    assert_eq!(&z_machine[ByteAddress(0x312)..ByteAddress(0x314)], b"hi");
+    // undo isn't supported, and neither is the character graphics font
+    assert_eq!(z_machine.peek_variable(16), 0xFFFF);
+    assert_eq!(z_machine.peek_variable(18), 0);
+    assert_eq!(z_machine.peek_variable(17), 0);
+    assert!(z_machine.text_style().bold);
+    assert_eq!(z_machine.current_window(), Window::Lower);
+    assert_eq!(z_machine.upper_window_height(), 0);
+}
+
+#[test]
+fn return_from_v6_main() {
+    let mut story = story_bytes(6);
+    // the main routine is at packed address $100, and only returns
+    story[0x06] = 0x01;
+    story[0x07] = 0x00;
+    story.extend_from_slice(&[0x00, 0xB0]);
+    let mut z_machine =
+        ZMachine::new_with_options(story, LoadOptions::new().lenient(true)).unwrap();
+    let error = z_machine.run().unwrap_err();
+    assert!(matches!(error.error, ExecuteError::ReturnFromMain));
+
+    z_machine.reset_execution();
+    z_machine.set_fault_policy(Fault::ReturnFromMain, FaultPolicy::Ignore);
+    z_machine.run().unwrap();
+    assert!(z_machine.has_quit());
+}
+
+#[test]
+fn unicode_text() {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0xBE, 0x0B, 0x3F, 0x00, 0xE9, // print_unicode 'é'
+        0xBE, 0x0C, 0x3F, 0x00, 0xE9, 0x10, // check_unicode 'é' -> g00
+        0xBE, 0x0C, 0x3F, 0x26, 0x3A, 0x11, // check_unicode '☺' -> g01
+        0xE4, 0x1F, 0x03, 0x00, 0x00, 0x12, // aread $300 0 -> g02
+        0xBA, // quit
+    ]);
+    z_machine.write_byte(ByteAddress(0x300), 10);
+    let frontend = TestFrontend::new(&["Café"]);
+    z_machine.set_frontend(frontend.clone());
+    z_machine.run().unwrap();
+    assert_eq!(frontend.output(), "é");
+    assert_eq!(z_machine.peek_variable(16), 3);
+    assert_eq!(z_machine.peek_variable(17), 0);
+    assert_eq!(z_machine.peek_variable(18), 13);
+    assert_eq!(z_machine.char_zscii('é'), Some(170));
+    assert_eq!(
+        &z_machine[ByteAddress(0x301)..ByteAddress(0x306)],
+        &[4, b'c', b'a', b'f', 170]
+    );
+}
+
+#[test]
+fn zscii_output() {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0xE5, 0x7F, 0x41, // print_char 'A'
+        0xE5, 0x7F, 0x09, // print_char tab
+        0xE5, 0x7F, 0x00, // print_char null
+        0xE5, 0x7F, 0xF0, // print_char 240
+    ]);
+    assert_eq!(z_machine.zscii_output(13), ZsciiOutput::Newline);
+    assert_eq!(z_machine.zscii_output(130), ZsciiOutput::Invalid);
+    assert_eq!(z_machine.zscii_output(170), ZsciiOutput::Char('é'));
+    let frontend = TestFrontend::new(&[]);
+    z_machine.set_frontend(frontend.clone());
+    match z_machine.run() {
+        Err(RuntimeError {
+            error: ExecuteError::InvalidZscii(240),
+            ..
+        }) => {}
+        other => panic!("Expected invalid ZSCII, got {:?}", other),
+    }
+    assert_eq!(frontend.output(), "A\t");
+    assert_eq!(frontend.warnings().len(), 1);
+    // a 10-bit escape for cursor up, which is input-only
+    z_machine.write_word(ByteAddress(0x300), 0x14C4);
+    z_machine.write_word(ByteAddress(0x302), 0x88A5);
+    assert_eq!(z_machine.read_zstring(ByteAddress(0x300)).0, "?");
+}
+
+/// Returns a story with abbreviations for "the " and "ing", and optionally a custom alphabet with
+/// the lowercase letters reversed and the symbols rearranged.
+fn encoding_story(version: u8, custom_alphabet: bool) -> ZMachine {
+    let mut z_machine = code_story(version, &[]);
+    write_abbreviations(&mut z_machine);
+    if custom_alphabet {
+        let alphabet = b"zyxwvutsrqponmlkjihgfedcba\
+ABCDEFGHIJKLMNOPQRSTUVWXYZ\
+ \n()-:/\\\"'#_?!,.9876543210";
+        for (x, &byte) in alphabet.iter().enumerate() {
+            z_machine.write_byte(ByteAddress(0x300 + x), byte);
+        }
+        z_machine.write_word(ByteAddress::ALPHABET_TABLE_ADDRESS, 0x300);
+    }
+    z_machine
+}
+
+/// Writes an abbreviation table at $200 with "the " and "ing" as the first two abbreviations,
+/// and the empty string as the rest.
+fn write_abbreviations(z_machine: &mut ZMachine) {
+    z_machine.write_word(ByteAddress::ABBREVIATIONS_LOCATION, 0x200);
+    let strings = ["the ", "ing"];
+    for idx in 0..96 {
+        let string = ByteAddress(0x2C0 + strings.len().min(idx) * 4);
+        z_machine.write_word(ByteAddress(0x200 + idx * 2), (string.0 / 2) as Word);
+    }
+    for (idx, string) in strings.iter().chain(&[""]).enumerate() {
+        let encoded = z_machine.encode_zstring(string, false);
+        for (x, &byte) in encoded.iter().enumerate() {
+            z_machine.write_byte(ByteAddress(0x2C0 + idx * 4 + x), byte);
+        }
+    }
+}
+
+fn round_trip(z_machine: &mut ZMachine, text: &str, abbreviate: bool) -> String {
+    let encoded = z_machine.encode_zstring(text, abbreviate);
+    for (x, &byte) in encoded.iter().enumerate() {
+        z_machine.write_byte(ByteAddress(0x380 + x), byte);
+    }
+    let (decoded, len) = z_machine.read_zstring(ByteAddress(0x380));
+    assert_eq!(len, encoded.len());
+    decoded
+}
+
+#[test]
+fn encode_zstring() {
+    let z_machine = encoding_story(3, false);
+    assert_eq!(
+        z_machine.encode_zstring("Hi!", false),
+        pack_zchars(&[4, 13, 14, 5, 20, 5])
+    );
+    assert_eq!(
+        z_machine.encode_zstring("sing", true),
+        pack_zchars(&[24, 1, 1])
+    );
+    assert_eq!(
+        z_machine
+            .zstring_encoder()
+            .encode_dictionary_word("lantern"),
+        pack_zchars(&[17, 6, 19, 25, 10, 23])
+    );
+    let z_machine = encoding_story(1, false);
+    assert_eq!(
+        z_machine.encode_zstring("ABc", false),
+        pack_zchars(&[4, 6, 7, 3, 8, 5])
+    );
+    // the last word is padded
+    assert_eq!(
+        pack_zchars(&[4, 13, 14, 5]),
+        pack_zchars(&[4, 13, 14, 5, 5, 5])
+    );
+    assert_eq!(pack_zchars(&[]), pack_zchars(&[5, 5, 5]));
+}
+
+proptest! {
+    #[test]
+    fn zstring_round_trip(
+        text in "[ -~\né€ßÄ]{0,40}",
+        version in prop::sample::select(vec![1, 2, 3, 5, 8]),
+        abbreviate: bool,
+    ) {
+        let mut z_machine = encoding_story(version, false);
+        prop_assert_eq!(round_trip(&mut z_machine, &text, abbreviate), text.replace('€', "?"));
+    }
+
+    #[test]
+    fn zstring_round_trip_custom_alphabet(text in "[ -~\né]{0,40}", abbreviate: bool) {
+        let mut z_machine = encoding_story(5, true);
+        prop_assert_eq!(round_trip(&mut z_machine, &text, abbreviate), text);
+    }
+}
+
+#[test]
+fn tokenise() {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0xE4, 0x0F, 0x03, 0x00, 0x03, 0x40, 0x10, // aread $300 $340 -> g00
+        0xFC, 0x14, 0x03, 0x02, 0x04, 0x01, 0x03, 0x60, // encode_text $302 4 1 $360
+        0xBA, // quit
+    ]);
+    z_machine.write_word(ByteAddress::DICTIONARY_LOCATION, 0x200);
+    let dictionary = [&[1, b',', 7, 0, 2][..], &[0; 14]].concat();
+    for (x, &byte) in dictionary.iter().enumerate() {
+        z_machine.write_byte(ByteAddress(0x200 + x), byte);
+    }
+    for (x, word) in ["lamp", "look"].iter().enumerate() {
+        let encoded = z_machine.zstring_encoder().encode_dictionary_word(word);
+        for (y, &byte) in encoded.iter().enumerate() {
+            z_machine.write_byte(ByteAddress(0x205 + x * 7 + y), byte);
+        }
+    }
+    z_machine.write_byte(ByteAddress(0x300), 20);
+    z_machine.write_byte(ByteAddress(0x340), 3);
+    z_machine.set_frontend(TestFrontend::new(&["Look,xyzzy lamp"]));
+    z_machine.run().unwrap();
+    #[rustfmt::skip]
+    assert_eq!(&z_machine[ByteAddress(0x341)..ByteAddress(0x34E)], &[
+        3,
+        0x02, 0x0C, 4, 2,
+        0x00, 0x00, 1, 6,
+        0x00, 0x00, 5, 7,
+    ][..]);
+    assert_eq!(
+        &z_machine[ByteAddress(0x360)..ByteAddress(0x366)],
+        &z_machine.zstring_encoder().encode_dictionary_word("ook,")[..]
+    );
+}
+
+#[test]
+fn abbreviations() {
+    let mut z_machine = encoding_story(3, false);
+    let corpus = ["the lamp is here", "the lamp is there", "the lamp"];
+    let abbreviations = optimise_abbreviations(&z_machine.zstring_encoder(), &corpus, 1);
+    assert_eq!(abbreviations, vec!["the lamp"]);
+    let encoded = z_machine.encode_zstring(corpus[0], true);
+    z_machine.write_bytes(ByteAddress(0x340), &encoded);
+    match z_machine.rewrite_abbreviations(&["the lamp is here"], &[]) {
+        Err(AbbreviationError::NoRoom(14, 10)) => {}
+        other => panic!("Expected no room, got {:?}", other),
+    }
+    z_machine
+        .rewrite_abbreviations(&abbreviations, &[ByteAddress(0x340)])
+        .unwrap();
+    assert_eq!(
+        z_machine.read_abbrvd_zstring(ZStringAbbrv::new(0).unwrap()),
+        "the lamp"
+    );
+    assert_eq!(
+        z_machine.read_abbrvd_zstring(ZStringAbbrv::new(1).unwrap()),
+        ""
+    );
+    assert_eq!(
+        z_machine.read_zstring(ByteAddress(0x340)),
+        (corpus[0].to_string(), encoded.len())
+    );
+    assert_eq!(
+        z_machine.header().checksum(),
+        z_machine.calculate_checksum()
+    );
+}
+
+#[test]
+fn zstring_chars() {
+    let mut z_machine = encoding_story(3, false);
+    let encoded = z_machine.encode_zstring("sing the song", true);
+    z_machine.write_bytes(ByteAddress(0x340), &encoded);
+    let start: String = z_machine
+        .zstring_chars(ByteAddress(0x340))
+        .take(6)
+        .collect();
+    assert_eq!(start, "sing t");
+    assert_eq!(
+        z_machine.zstring_end(ByteAddress(0x340)),
+        ByteAddress(0x340 + encoded.len())
+    );
+    // abbreviations inside abbreviations are skipped
+    let nested = z_machine.encode_zstring("ring", true);
+    z_machine.write_bytes(ByteAddress(0x2C0), &nested);
+    assert_eq!(z_machine.read_zstring(ByteAddress(0x340)).0, "sing rsong");
+    assert_eq!(
+        z_machine.read_zstring_bitslice(ByteAddress(0x340)),
+        z_machine.read_zstring(ByteAddress(0x340))
+    );
+}
+
+/// A V3 story with a routine, some strings and an object called "lamp".
+fn strings_story() -> ZMachine {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(3, &[
+        0xE0, 0x3F, 0x02, 0x04, 0x00, // call $408 -> sp
+        0xBA, // quit
+        0x00, 0x00, // padding
+        0x00, // routine with no locals
+        0x90, 0x00, 0xC1, // jz 0 ?rtrue
+        0xB2, 0xB5, 0xC5, // print "hi"
+        0x8D, 0x02, 0x0A, // print_paddr $414
+        0xB0, // rtrue
+        0x00, // padding
+        0xD2, 0x05, // "ok"
+        0x10, 0xFE, 0xA8, 0xA5, // "Bye"
+    ]);
+    write_abbreviations(&mut z_machine);
+    // one object, called "lamp"
+    z_machine.write_word(ByteAddress::OBJECT_TABLE_LOCATION, 0x100);
+    z_machine.write_word(ByteAddress(0x145), 0x147);
+    z_machine.write_byte(ByteAddress(0x147), 2);
+    let name = z_machine.encode_zstring("lamp", false);
+    z_machine.write_bytes(ByteAddress(0x148), &name);
+    z_machine
+}
+
+#[test]
+fn disassemble() {
+    let mut z_machine = strings_story();
+    let routines = z_machine.disassemble();
+    let names: Vec<Vec<_>> = routines
+        .iter()
+        .map(|routine| {
+            routine
+                .instructions
+                .iter()
+                .map(|instruction| instruction.name)
+                .collect()
+        })
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            vec!["call", "quit"],
+            vec!["jz", "print", "print_paddr", "rtrue"]
+        ]
+    );
+    assert_eq!(routines[1].addr, ByteAddress(0x408));
+    assert_eq!(routines[1].end(), ByteAddress(0x413));
+    assert_eq!(
+        routines[0].instructions[0],
+        Instruction {
+            addr: ByteAddress(0x400),
+            name: "call",
+            operands: vec![Operand::LargeConstant(0x204)],
+            store: Some(0),
+            branch: None,
+            text: None,
+            next: ByteAddress(0x405),
+        }
+    );
+    assert_eq!(
+        routines[1].instructions[0].branch,
+        Some(Branch {
+            on: true,
+            target: BranchTarget::ReturnTrue,
+        })
+    );
+    // unused abbreviations are left as 0, which isn't a string
+    for idx in 2..96 {
+        z_machine.write_word(ByteAddress(0x200 + idx * 2), 0);
+    }
+    assert!(z_machine
+        .strings()
+        .iter()
+        .all(|string| string.addr != ByteAddress::ZERO));
+    let strings: Vec<_> = z_machine
+        .strings()
+        .into_iter()
+        .filter(|string| !matches!(string.source, StringSource::Abbreviation(_)))
+        .map(|string| (string.addr.0, string.source, string.text))
+        .collect();
+    assert_eq!(
+        strings,
+        vec![
+            (0x148, StringSource::ObjectName(1), "lamp".to_string()),
+            (
+                0x40D,
+                StringSource::Inline(ByteAddress(0x40C)),
+                "hi".to_string()
+            ),
+            (0x414, StringSource::Static, "ok".to_string()),
+            (0x416, StringSource::Static, "Bye".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn patch_strings() {
+    let mut z_machine = strings_story();
+    let mut replacements = BTreeMap::new();
+    replacements.insert(ByteAddress(0x148), "a much longer lamp".to_string());
+    match z_machine.patch_strings(&replacements) {
+        Err(PatchError::NoRoom(0x148)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+    replacements.clear();
+    replacements.insert(ByteAddress(0x415), "nothing".to_string());
+    match z_machine.patch_strings(&replacements) {
+        Err(PatchError::UnknownString(0x415)) => {}
+        result => panic!("unexpected result {:?}", result),
+    }
+
+    replacements.clear();
+    replacements.insert(ByteAddress(0x40D), "greetings".to_string());
+    replacements.insert(ByteAddress(0x414), "okay then".to_string());
+    replacements.insert(ByteAddress(0x416), "Bye".to_string());
+    // the lamp's property 5 might be "ok"
+    z_machine.write_bytes(ByteAddress(0x14C), &[0x25, 0x02, 0x0A, 0x00]);
+    let len = z_machine.len_bytes();
+    let guessed = z_machine.patch_strings(&replacements).unwrap();
+    assert_eq!(guessed, vec![ByteAddress(0x14D)]);
+    // "Bye" fits, so stays where it was
+    assert_eq!(z_machine.read_zstring(ByteAddress(0x416)).0, "Bye");
+    // "okay then" is moved to the end, and print_paddr follows it
+    let packed = z_machine.word(ByteAddress(0x410)) as usize;
+    let moved = z_machine.resolve_packed_address(packed, false);
+    assert!(moved.0 >= len);
+    assert_eq!(z_machine.read_zstring(moved).0, "okay then");
+    assert_eq!(z_machine.word(ByteAddress(0x14D)) as usize, packed);
+    // print "hi" becomes print_paddr, padded with nops
+    assert_eq!(z_machine[ByteAddress(0x40C)], 0x8D);
+    let packed = z_machine.word(ByteAddress(0x40D)) as usize;
+    let moved = z_machine.resolve_packed_address(packed, false);
+    assert_eq!(z_machine.read_zstring(moved).0, "greetings");
+    assert_eq!(
+        z_machine.header().file_length(),
+        z_machine.len_bytes(),
+        "file length"
+    );
+    assert_eq!(
+        z_machine.header().checksum(),
+        z_machine.calculate_checksum()
+    );
+}
+
+#[test]
+fn debugger() {
+    #[rustfmt::skip]
+    let code = [
+        0xE0, 0x3F, 0x02, 0x06, 0x00, // call $40c -> sp
+        0x0D, 0x10, 0x05, // store g00 5
+        0xBA, // quit
+        0x00, 0x00, 0x00, // padding
+        0x01, 0x00, 0x07, // routine with one local, initially 7
+        0x0D, 0x01, 0x02, // store l00 2
+        0xB0, // rtrue
+    ];
+    let mut z_machine = code_story(3, &code);
+    let mut debugger = Debugger::new();
+    assert_eq!(debugger.step_over(&mut z_machine).unwrap(), Stop::Step);
+    assert_eq!(z_machine.pc(), ByteAddress(0x405));
+
+    let mut z_machine = code_story(3, &code);
+    let breakpoint = debugger.add_breakpoint(Breakpoint::Routine(ByteAddress(0x40C)));
+    assert_eq!(
+        debugger.resume(&mut z_machine).unwrap(),
+        Stop::Breakpoint(breakpoint)
+    );
+    assert_eq!(z_machine.pc(), ByteAddress(0x40F));
+    let frames = z_machine.call_stack();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[1].routine, ByteAddress(0x40C));
+    assert_eq!(frames[1].return_pc, ByteAddress(0x405));
+    assert_eq!(frames[1].locals, &[7]);
+    assert_eq!(debugger.step(&mut z_machine).unwrap(), Stop::Step);
+    assert_eq!(z_machine.call_stack()[1].locals, &[2]);
+    assert_eq!(debugger.step_out(&mut z_machine).unwrap(), Stop::Step);
+    assert_eq!(z_machine.pc(), ByteAddress(0x405));
+    assert_eq!(z_machine.call_stack()[0].stack, &[1]);
+    let watchpoint = debugger
+        .add_watchpoint(&z_machine, Watchpoint::Global(16))
+        .unwrap();
+    // there are no objects, and the story is shorter than this
+    for watchpoint in [
+        Watchpoint::Global(15),
+        Watchpoint::Relation(0, Relation::Parent),
+        Watchpoint::Relation(250, Relation::Child),
+        Watchpoint::Memory(ByteAddress(0x400)..ByteAddress(0x500)),
+    ] {
+        assert!(debugger.add_watchpoint(&z_machine, watchpoint).is_err());
+    }
+    assert_eq!(
+        debugger.resume(&mut z_machine).unwrap(),
+        Stop::Watchpoint {
+            id: watchpoint,
+            old: vec![0, 0],
+            new: vec![0, 5],
+        }
+    );
+    assert!(debugger.remove(breakpoint));
+    assert!(!debugger.remove(breakpoint));
+    assert_eq!(debugger.resume(&mut z_machine).unwrap(), Stop::Quit);
+}
+
+#[test]
+fn debug_info() {
+    let info = DebugInfo::from_xml(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<inform-story-file version="1.0" content-creator="Inform" content-creator-version="6.34">
+  <source index="0"><given-path>game.inf</given-path></source>
+  <global-variable><identifier>score</identifier><address>64</address></global-variable>
+  <object><identifier>brass_lamp</identifier><value>1</value></object>
+  <property><identifier>description</identifier><value>3</value></property>
+  <routine>
+    <identifier>Main</identifier>
+    <address>1036</address>
+    <byte-count>7</byte-count>
+    <source-code-location><file-index>0</file-index><line>10</line></source-code-location>
+    <local-variable><identifier>x</identifier><index>1</index></local-variable>
+    <sequence-point>
+      <address>1039</address>
+      <source-code-location><file-index>0</file-index><line>11</line></source-code-location>
+    </sequence-point>
+    <sequence-point>
+      <address>1042</address>
+      <source-code-location><file-index>0</file-index><line>12</line></source-code-location>
+    </sequence-point>
+  </routine>
+</inform-story-file>"#,
+    )
+    .unwrap();
+    assert_eq!(
+        info.properties.get(&3).map(|name| &name[..]),
+        Some("description")
+    );
+    assert_eq!(info.routine_at(ByteAddress(0x412)).unwrap().name, "Main");
+    assert!(info.routine_at(ByteAddress(0x413)).is_none());
+    assert_eq!(
+        info.source_location(ByteAddress(0x410)),
+        Some(SourceLocation { file: 0, line: 11 })
+    );
+
+    #[rustfmt::skip]
+    let mut z_machine = code_story(3, &[
+        0xE0, 0x3F, 0x02, 0x06, 0x00, // call $40c -> sp
+        0x0D, 0x10, 0x05, // store g00 5
+        0xBA, // quit
+        0x00, 0x00, 0x00, // padding
+        0x01, 0x00, 0x07, // routine with one local, initially 7
+        0x0D, 0x01, 0x02, // store l00 2
+        0xB0, // rtrue
+    ]);
+    z_machine.set_debug_info(info.clone());
+    assert_eq!(z_machine.variable_name(16, ByteAddress(0x400)), "score");
+    assert_eq!(z_machine.variable_name(17, ByteAddress(0x400)), "G01");
+    assert_eq!(z_machine.variable_name(1, ByteAddress(0x40F)), "x");
+    assert_eq!(
+        z_machine.describe_routine(ByteAddress(0x40C), ByteAddress(0x412)),
+        "routine Main (game.inf:12)"
+    );
+    assert_eq!(
+        z_machine.describe_routine(ByteAddress(0x400), ByteAddress(0x400)),
+        "routine 0x400"
+    );
+    let call = z_machine.decode_instruction(ByteAddress(0x400)).unwrap();
+    assert_eq!(
+        z_machine.format_instruction(&call),
+        "0x00400  call Main -> sp"
+    );
+    // past the end of the story, or with operands, a branch or text running past it
+    let len = z_machine.len_bytes();
+    match z_machine.decode_instruction(ByteAddress(len)) {
+        Err(ExecuteError::InvalidInstructionFormat(addr)) if addr == len => {}
+        other => panic!("Expected an invalid instruction, got {:?}", other),
+    }
+    for code in [
+        &[0xE0, 0x3F, 0x02][..],
+        &[0x01, 0x01, 0x01],
+        &[0xB2, 0x11, 0xAA],
+    ] {
+        match code_story(3, code).decode_instruction(ByteAddress(0x400)) {
+            Err(ExecuteError::InvalidInstructionFormat(0x400)) => {}
+            other => panic!("Expected an invalid instruction, got {:?}", other),
+        }
+    }
+
+    let mut z_machine = strings_story();
+    z_machine.set_debug_info(info);
+    assert_eq!(z_machine.object_unchecked(1).read_name(), "lamp");
+    z_machine.write_byte(ByteAddress(0x147), 0);
+    assert_eq!(z_machine.object_unchecked(1).read_name(), "brass_lamp");
+}
+
+/// A writer whose clones all write to the same buffer.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn trace() {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(3, &[
+        0xE0, 0x1F, 0x02, 0x06, 0x09, 0x00, // call $40c #09 -> sp
+        0xBA, // quit
+        0x00, 0x00, 0x00, 0x00, 0x00, // padding
+        0x01, 0x00, 0x07, // routine with one local, initially 7
+        0x54, 0x01, 0x01, 0x00, // add l00 #01 -> sp
+        0x41, 0x00, 0x0A, 0xC1, // je sp #0a ?rtrue
+        0xB1, // rfalse
+    ]);
+    let ring = RingTrace::new(4);
+    z_machine.set_trace_sink(ring.clone());
+    for _ in 0..3 {
+        z_machine.step().unwrap();
+    }
+    let events = ring.events();
+    assert_eq!(events.len(), 4);
+    assert_eq!(
+        events[0],
+        TraceEvent::Call {
+            routine: ByteAddress(0x40C),
+            name: "routine 0x40c".to_string(),
+            args: vec![9],
+            depth: 2,
+        }
+    );
+    match &events[1] {
+        TraceEvent::Instruction {
+            addr,
+            operands,
+            result,
+            branched,
+            ..
+        } => {
+            assert_eq!(*addr, ByteAddress(0x40F));
+            assert_eq!(operands, &[9, 1]);
+            assert_eq!(*result, Some(10));
+            assert_eq!(*branched, None);
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+    match &events[2] {
+        TraceEvent::Instruction { branched, .. } => assert_eq!(*branched, Some(true)),
+        event => panic!("unexpected event {:?}", event),
+    }
+    assert_eq!(
+        events[3],
+        TraceEvent::Return {
+            routine: ByteAddress(0x40C),
+            value: 1,
+            depth: 2,
+        }
+    );
+
+    let mut z_machine = code_story(3, &z_machine[ByteAddress(0x400)..ByteAddress(0x418)]);
+    let buffer = SharedBuffer::default();
+    z_machine.set_trace_sink(FileTrace::new(buffer.clone()).calls_only(true));
+    for _ in 0..4 {
+        z_machine.step().unwrap();
+    }
+    assert_eq!(
+        String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(),
+        "  -> routine 0x40c [0009]\n  <- routine 0x40c returned 0x0001\n"
+    );
+
+    // an instruction that fails is traced before the error is returned
+    let mut z_machine = code_story(3, &[0x17, 0x05, 0x00, 0x00]); // div #05 #00 -> sp
+    let ring = RingTrace::new(4);
+    z_machine.set_trace_sink(ring.clone());
+    z_machine.step().unwrap_err();
+    assert_eq!(
+        ring.events(),
+        vec![TraceEvent::Instruction {
+            addr: ByteAddress(0x400),
+            text: "0x00400  div #05 #00 -> sp".to_string(),
+            operands: vec![5, 0],
+            result: None,
+            branched: None,
+            depth: 1,
+        }]
+    );
+}
+
+#[test]
+fn runtime_faults() {
+    #[rustfmt::skip]
+    let story = || code_story(3, &[
+        0xE0, 0x1F, 0x02, 0x06, 0x09, 0x00, // call $40c #09 -> sp
+        0xBA, // quit
+        0x00, 0x00, 0x00, 0x00, 0x00, // padding
+        0x01, 0x00, 0x00, // routine with one local
+        0x57, 0x01, 0x00, 0x00, // div l00 #00 -> sp
+        0xB9, // pop
+        0xB9, // pop, with the stack empty
+        0xB0, // rtrue
+    ]);
+    let mut z_machine = story();
+    let error = z_machine.run().unwrap_err();
+    match error.error {
+        ExecuteError::DivisionByZero => {}
+        ref other => panic!("Expected division by zero, got {:?}", other),
+    }
+    assert_eq!(error.pc, ByteAddress(0x40F));
+    assert_eq!(
+        error.backtrace,
+        vec![
+            BacktraceFrame {
+                routine: ByteAddress(0x40C),
+                pc: ByteAddress(0x40F),
+            },
+            BacktraceFrame {
+                routine: ByteAddress(0x400),
+                pc: ByteAddress(0x406),
+            },
+        ]
+    );
+    assert_eq!(
+        z_machine.describe_error(&error),
+        "Division by zero at 0x40f\n  in routine 0x40c at 0x40f\n  in routine 0x400 at 0x406"
+    );
+
+    let mut z_machine = story();
+    let frontend = TestFrontend::new(&[]);
+    z_machine.set_frontend(frontend.clone());
+    z_machine.set_fault_policy(Fault::DivisionByZero, FaultPolicy::Ignore);
+    z_machine.set_fault_policy(Fault::StackUnderflow, FaultPolicy::WarnOnce);
+    z_machine.run().unwrap();
+    assert_eq!(frontend.warnings(), vec!["Stack underflow".to_string()]);
+}
+
+#[test]
+fn invalid_operands() {
+    #[rustfmt::skip]
+    let story = || {
+        let mut z_machine = code_story(3, &[
+            0xE3, 0x57, 0x01, 0x03, 0x07, // put_prop #01 #03 #07
+            0x13, 0x01, 0x04, 0x10, // get_next_prop #01 #04 -> g00
+            0x0B, 0x01, 0x20, // set_attr #01 #32
+            0x0A, 0x01, 0x21, 0xC1, // test_attr #01 #33 ?rtrue
+            0xCF, 0x1F, 0xFF, 0xF0, 0x00, 0x11, // loadw $fff0 #00 -> g01
+            0x9B, 0x05, // ret #05
+        ]);
+        // one object, with a 1-byte property 5 and a 4-byte property 3
+        z_machine.write_word(ByteAddress::OBJECT_TABLE_LOCATION, 0x100);
+        z_machine.write_word(ByteAddress(0x145), 0x160);
+        z_machine.write_bytes(ByteAddress(0x160), &[0x00, 0x05, 0x2A, 0x63, 1, 2, 3, 4, 0x00]);
+        z_machine.write_word(ByteAddress(0x40), 0xFFFF);
+        z_machine.write_word(ByteAddress(0x42), 0xFFFF);
+        z_machine
+    };
+    let mut z_machine = story();
+    let mut errors = Vec::new();
+    while let Err(error) = z_machine.run() {
+        errors.push(error.to_string());
+        z_machine.set_fault_policy(error.error.fault().unwrap(), FaultPolicy::Ignore);
+    }
+    assert_eq!(
+        errors,
+        vec![
+            "Invalid property 3 of object 1 at 0x400",
+            "Attribute 32 doesn't exist at 0x409",
+            "Read outside memory at address 65520 at 0x410",
+            "Returned from the main routine at 0x416",
+        ]
+    );
+    // tolerated, nothing is changed and 0 is stored
+    assert!(!z_machine.is_running());
+    assert_eq!(
+        z_machine.object(1).property_value(3),
+        Some(&[1, 2, 3, 4][..])
+    );
+    assert_eq!(z_machine.word(ByteAddress(0x40)), 0);
+    assert_eq!(z_machine.word(ByteAddress(0x42)), 0);
+
+    #[rustfmt::skip]
+    let mut z_machine = code_story(3, &[
+        0xE0, 0x3F, 0x02, 0x03, 0x00, // call $406 -> sp
+        0x00, // padding
+        0x10, // routine with 16 locals
+    ]);
+    match z_machine.run().unwrap_err().error {
+        ExecuteError::TooManyLocals(0x406) => {}
+        other => panic!("Expected too many locals, got {:?}", other),
+    }
+}
+
+#[test]
+fn out_of_range_code() {
+    let error = |code: &[u8]| code_story(3, code).run().unwrap_err().to_string();
+    // jumping past the end, or before the start, of memory
+    assert_eq!(
+        error(&[0x8C, 0x7F, 0x00]),
+        "Read outside memory at address 33537 at 0x8301"
+    );
+    assert_eq!(
+        error(&[0x8C, 0x80, 0x00]),
+        "Read outside memory at address 1027 at 0x400"
+    );
+    // an instruction, its branch, or its text running past the end
+    assert_eq!(
+        error(&[0xE0]),
+        "Read outside memory at address 1025 at 0x400"
+    );
+    assert_eq!(
+        error(&[0x01, 0x01, 0x01]),
+        "Read outside memory at address 1027 at 0x400"
+    );
+    assert_eq!(
+        error(&[0xB2, 0x11, 0xAA]),
+        "Read outside memory at address 1025 at 0x400"
+    );
+
+    #[rustfmt::skip]
+    let mut z_machine = code_story(3, &[
+        0x8D, 0x7F, 0xFF, // print_paddr #7fff
+        0x84, 0xFF, 0xFF, 0x10, // get_prop_len #ffff -> g00
+        0x93, 0xFA, 0x11, // get_parent #fa -> g01
+        0xBA, // quit
+    ]);
+    z_machine.write_word(ByteAddress(0x40), 0xFFFF);
+    z_machine.write_word(ByteAddress(0x42), 0xFFFF);
+    let mut errors = Vec::new();
+    while let Err(error) = z_machine.run() {
+        errors.push(error.to_string());
+        z_machine.set_fault_policy(error.error.fault().unwrap(), FaultPolicy::Ignore);
+    }
+    assert_eq!(
+        errors,
+        vec![
+            "Read outside memory at address 65534 at 0x400",
+            "Object 250 doesn't exist at 0x407",
+        ]
+    );
+    assert_eq!(z_machine.word(ByteAddress(0x40)), 0);
+    assert_eq!(z_machine.word(ByteAddress(0x42)), 0);
+}
+
+#[test]
+fn write_protection() {
+    #[rustfmt::skip]
+    let story = || code_story(5, &[
+        0xE2, 0x57, 0x00, 0x11, 0x01, // storeb #00 #11 #01, turning transcripting on
+        0xE2, 0x57, 0x00, 0x00, 0x03, // storeb #00 #00 #03, changing the version
+        0xE1, 0x17, 0x04, 0x00, 0x00, 0x05, // storew $400 #00 #05
+        0xBA, // quit
+    ]);
+    let mut z_machine = story();
+    assert_eq!(
+        z_machine.dynamic_memory_range(),
+        ByteAddress::ZERO..ByteAddress(0x400)
+    );
+    z_machine.step().unwrap();
+    assert_eq!(z_machine[ByteAddress(0x11)], 1);
+    let error = z_machine.step().unwrap_err();
+    match error.error {
+        ExecuteError::HeaderWrite(0) => {}
+        ref other => panic!("Expected a header write, got {:?}", other),
+    }
+    assert_eq!(error.pc, ByteAddress(0x405));
+    assert_eq!(z_machine.pc(), ByteAddress(0x405));
+    assert_eq!(z_machine[ByteAddress(0)], 5);
+
+    let mut z_machine = story();
+    let frontend = TestFrontend::new(&[]);
+    z_machine.set_frontend(frontend.clone());
+    z_machine.set_fault_policy(Fault::HeaderWrite, FaultPolicy::Ignore);
+    z_machine.set_fault_policy(Fault::StaticMemoryWrite, FaultPolicy::WarnOnce);
+    z_machine.run().unwrap();
+    assert_eq!(z_machine[ByteAddress(0)], 5);
+    assert_eq!(z_machine.word(ByteAddress(0x400)), 0xE257);
+    assert_eq!(
+        frontend.warnings(),
+        vec!["Write outside dynamic memory at address 1024".to_string()]
+    );
+    // the host isn't limited to dynamic memory
+    z_machine.write_word(ByteAddress(0x400), 0x1234);
+    assert_eq!(z_machine.word(ByteAddress(0x400)), 0x1234);
+}
+
+#[test]
+fn decode_cache() {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0x8C, 0xFF, 0xFF, // jump to itself
+    ]);
+    z_machine.step().unwrap();
+    z_machine.step().unwrap();
+    assert_eq!(z_machine.pc(), ByteAddress(0x400));
+    // changing code outside dynamic memory throws away what was decoded
+    z_machine.write_byte(ByteAddress(0x400), 0xBA); // quit
+    z_machine.step().unwrap();
+    assert!(!z_machine.is_running());
+}
+
+#[test]
+fn run_until_input() {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0xB2, 0xB5, 0xC5, // print "hi"
+        0xE4, 0x1F, 0x03, 0x00, 0x00, 0x00, // aread $300 #00 -> sp
+        0xF6, 0x7F, 0x01, 0x10, // read_char #01 -> g00
+        0xBA, // quit
+    ]);
+    z_machine.write_byte(ByteAddress(0x300), 20);
+    let output = z_machine.run_until_input().unwrap();
+    assert_eq!(output.text, "hi");
+    assert_eq!(output.status, None);
+    assert_eq!(output.waiting_for, Some(WaitingFor::Line));
+    // running again without sending anything waits again
+    assert_eq!(
+        z_machine.run_until_input().unwrap().waiting_for,
+        Some(WaitingFor::Line)
+    );
+    z_machine.send_line("Open Mailbox");
+    let output = z_machine.run_until_input().unwrap();
+    assert_eq!(output.text, "");
+    assert_eq!(output.waiting_for, Some(WaitingFor::Char));
+    assert_eq!(z_machine[ByteAddress(0x301)], 12);
+    assert_eq!(
+        &z_machine[ByteAddress(0x302)..ByteAddress(0x30E)],
+        b"open mailbox"
+    );
+    assert_eq!(z_machine.peek_stack(), 13);
+    z_machine.send_line("y");
+    let output = z_machine.run_until_input().unwrap();
+    assert_eq!(output.waiting_for, None);
+    assert_eq!(z_machine.peek_variable(16), 'y' as Word);
+}
+
+#[test]
+fn resume() {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0xB2, 0xB5, 0xC5, // print "hi"
+        0xE4, 0x1F, 0x03, 0x00, 0x00, 0x00, // aread $300 #00 -> sp
+        0xF6, 0x7F, 0x01, 0x10, // read_char #01 -> g00
+        0xBA, // quit
+    ]);
+    z_machine.write_byte(ByteAddress(0x300), 20);
+    // the frontend still prints, but is never asked for input
+    let frontend = TestFrontend::new(&["never read"]);
+    z_machine.set_frontend(frontend.clone());
+    assert_eq!(
+        z_machine.resume().unwrap(),
+        State::Suspended(WaitingFor::Line)
+    );
+    assert_eq!(frontend.output(), "hi");
+    assert_eq!(z_machine.waiting_for(), Some(WaitingFor::Line));
+    assert_eq!(
+        z_machine.resume_with(Input::Key('x')).unwrap(),
+        State::Suspended(WaitingFor::Char)
+    );
+    assert_eq!(&z_machine[ByteAddress(0x301)..ByteAddress(0x303)], b"\x01x");
+    assert_eq!(
+        z_machine.resume_with(Input::Key('\u{8}')).unwrap(),
+        State::Quit
+    );
+    assert_eq!(z_machine.peek_variable(16), 8);
+    assert_eq!(z_machine.waiting_for(), None);
+    // nothing past quit is run
+    let pc = z_machine.pc();
+    assert_eq!(z_machine.resume_with(Input::Key('y')).unwrap(), State::Quit);
+    z_machine.step().unwrap();
+    assert_eq!(Debugger::new().resume(&mut z_machine).unwrap(), Stop::Quit);
+    assert_eq!(z_machine.pc(), pc);
+    assert!(z_machine.has_quit());
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn run_async() {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    /// Polls a future that never has to wait until it's done.
+    fn block_on<F: Future>(mut future: F) -> F::Output {
+        fn raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                raw_waker()
+            }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        let waker = unsafe { Waker::from_raw(raw_waker()) };
+        let mut context = Context::from_waker(&waker);
+        let mut future = unsafe { Pin::new_unchecked(&mut future) };
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0xE4, 0x1F, 0x03, 0x00, 0x00, 0x00, // aread $300 #00 -> sp
+        0xF6, 0x7F, 0x01, 0x10, // read_char #01 -> g00
+        0xBA, // quit
+    ]);
+    z_machine.write_byte(ByteAddress(0x300), 20);
+    let mut asked = Vec::new();
+    block_on(z_machine.run_async(|waiting_for| {
+        asked.push(waiting_for);
+        async move {
+            Some(match waiting_for {
+                WaitingFor::Line => Input::Line("look".to_string()),
+                WaitingFor::Char => Input::Key('y'),
+            })
+        }
+    }))
+    .unwrap();
+    assert_eq!(asked, vec![WaitingFor::Line, WaitingFor::Char]);
+    assert_eq!(z_machine.peek_variable(16), 'y' as Word);
+    assert!(!z_machine.is_running());
+}
+
+/// A story that stores 5 in g00, waits for a line, then increments g00 and quits.
+fn snapshot_story() -> ZMachine {
+    #[rustfmt::skip]
+    let mut z_machine = code_story(5, &[
+        0x0D, 0x10, 0x05, // store g00 5
+        0xEA, 0x7F, 0x02, // split_window 2
+        0xEB, 0x7F, 0x01, // set_window 1
+        0xEF, 0x5F, 0x02, 0x03, // set_cursor 2 3
+        0xF1, 0x7F, 0x04, // set_text_style 4
+        0xF3, 0x4F, 0x03, 0x03, 0x10, // output_stream 3 $310
+        0xB2, 0xB5, 0xC5, // print "hi"
+        0xE4, 0x1F, 0x03, 0x00, 0x00, 0x00, // aread $300 #00 -> sp
+        0x95, 0x10, // inc g00
+        0xF3, 0x3F, 0xFF, 0xFD, // output_stream -3
+        0xBA, // quit
+    ]);
+    z_machine.write_byte(ByteAddress(0x300), 20);
+    z_machine
+}
+
+#[test]
+fn snapshot() {
+    let mut z_machine = snapshot_story();
+    z_machine.set_random_mode(RandomMode::Seeded(7));
+    assert_eq!(
+        z_machine.resume().unwrap(),
+        State::Suspended(WaitingFor::Line)
+    );
+    let snapshot = z_machine.snapshot();
+
+    let mut restored = snapshot_story();
+    restored.write_byte(ByteAddress(0x300), 0);
+    restored.restore_snapshot(&snapshot).unwrap();
+    assert_eq!(restored.snapshot(), snapshot);
+    assert_eq!(restored.waiting_for(), Some(WaitingFor::Line));
+    assert_eq!(restored.peek_variable(16), 5);
+    assert_eq!(restored[ByteAddress(0x300)], 20);
+    assert_eq!(restored.random(1000), z_machine.random(1000));
+    assert_eq!(
+        restored.resume_with(Input::Line("hi".to_string())).unwrap(),
+        State::Quit
+    );
+    assert_eq!(restored.peek_variable(16), 6);
+    assert_eq!(&restored[ByteAddress(0x301)..ByteAddress(0x304)], b"\x02hi");
+    // the table that output stream 3 was printing to is finished
+    assert_eq!(restored.word(ByteAddress(0x310)), 2);
+
+    let mut other = code_story(5, &[0xBA]);
+    match other.restore_snapshot(&snapshot) {
+        Err(SnapshotError::WrongStory) => {}
+        result => panic!("Expected a wrong story error, got {:?}", result),
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialize_snapshot() {
+    let mut z_machine = snapshot_story();
+    z_machine.resume().unwrap();
+    let json = serde_json::to_string(&z_machine).unwrap();
+    assert!(json.starts_with(r#"{"version":1,"#));
+    // only the bytes that changed are stored
+    assert!(json.contains(
+        r#""memory":[{"address":65,"bytes":[5]},{"address":768,"bytes":[20]},{"address":786,"bytes":[104,105]}]"#
+    ));
+    let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+    let mut restored = snapshot_story();
+    restored.restore_snapshot(&snapshot).unwrap();
+    assert_eq!(restored.snapshot(), z_machine.snapshot());
+    assert_eq!(restored.current_window(), Window::Upper);
+    assert_eq!(restored.upper_window_height(), 2);
+    assert_eq!(restored.cursor(), (2, 3));
+    assert!(restored.text_style().italic);
+    assert!(restored.printing_to_memory());
+    assert_eq!(
+        restored.resume_with(Input::Line("hi".to_string())).unwrap(),
+        State::Quit
+    );
+    assert_eq!(restored.word(ByteAddress(0x310)), 2);
+
+    let json = json.replacen(r#""version":1"#, r#""version":2"#, 1);
+    let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+    match snapshot_story().restore_snapshot(&snapshot) {
+        Err(SnapshotError::UnsupportedVersion(2)) => {}
+        result => panic!("Expected an unsupported version error, got {:?}", result),
+    }
+}
+
+#[test]
+fn play_minizork() {
+    let mut z_machine = ZMachine::from_file("minizork.z3").unwrap();
+    let output = z_machine.run_until_input().unwrap();
+    assert!(output.text.contains("West of House"));
+    assert_eq!(output.waiting_for, Some(WaitingFor::Line));
+    assert_eq!(output.status.unwrap().location, "West of House");
+    z_machine.send_line("open mailbox");
+    let output = z_machine.run_until_input().unwrap();
+    assert!(output.text.contains("leaflet"));
+    assert_eq!(output.waiting_for, Some(WaitingFor::Line));
+}
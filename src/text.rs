@@ -1,4 +1,5 @@
 use crate::*;
+use arrayvec::ArrayVec;
 use std::char;
 
 /// An abbreviation identifier for a Z-string. References a Z-string addressed in the abbreviation
@@ -50,10 +51,13 @@ impl ZMachine {
             "Invalid Z-string abbreviation {}",
             abbrv.0
         );
+        str.extend(self.zstring_chars(self.abbreviation_location(abbrv)));
+    }
+    /// Returns the address of the Z-string referenced by an abbreviation.
+    pub(crate) fn abbreviation_location(&self, abbrv: ZStringAbbrv) -> ByteAddress {
         let abbrv_table = self.abbreviations_table_base();
         let abbrv_table_idx = abbrv_table + (abbrv.0 as usize) * 2;
-        let abbrv_addr = ByteAddress::from(self.word(abbrv_table_idx) * 2);
-        self.copy_zstring(abbrv_addr.into(), str);
+        ByteAddress::from(self.word(abbrv_table_idx) * 2)
     }
     /// Returns a Z-string at a particular address in memory and the number of bytes that it was
     /// stored in.
@@ -62,185 +66,79 @@ impl ZMachine {
         let len = self.copy_zstring(addr, &mut str);
         (str, len)
     }
-    /// Copies a Z-string at a particular address in memory into a string buffer, if there is one.
-    /// Returns true if the copy was successful (i.e. a valid encoding) and false if it wasn't.
+    /// Copies a Z-string at a particular address in memory into a string buffer. Returns the number
+    /// of bytes that it was stored in.
     pub fn copy_zstring(&self, addr: ByteAddress, string: &mut String) -> usize {
-        let mut addr = addr;
-        fn zchar_to_byte(bits: &BEBitSlice<Word>) -> u8 {
-            ((bits[0] as u8) << 4)
-                | ((bits[1] as u8) << 3)
-                | ((bits[2] as u8) << 2)
-                | ((bits[3] as u8) << 1)
-                | (bits[4] as u8)
+        string.extend(self.zstring_chars(addr));
+        self.zstring_end(addr) - addr
+    }
+    /// Returns an iterator that decodes a Z-string at a particular address in memory as it goes,
+    /// without allocating.
+    pub fn zstring_chars(&self, addr: ByteAddress) -> ZStringChars<'_> {
+        let mut stack = ArrayVec::new();
+        stack.push(ZStringCursor::new(addr));
+        ZStringChars {
+            machine: self,
+            version: self.version(),
+            alphabet: self.alphabet(),
+            unicode_table: self.unicode_table(),
+            stack,
+            bitslice: false,
         }
-        let alphabet = self.alphabet();
-        let mut state = ZStringState::Unset;
-        let mut mode = AlphabetMode::Lowercase;
-        let mut bytes_read = 0;
+    }
+    /// Does what [`read_zstring`](ZMachine::read_zstring) does, extracting Z-characters through a
+    /// [`BEBitSlice`] as it used to. Only for benchmarks to compare with.
+    #[doc(hidden)]
+    pub fn read_zstring_bitslice(&self, addr: ByteAddress) -> (String, usize) {
+        let mut chars = self.zstring_chars(addr);
+        chars.bitslice = true;
+        (chars.collect(), self.zstring_end(addr) - addr)
+    }
+    /// Returns the address just past the end of a Z-string, without decoding it. A string that
+    /// runs past the end of the story ends there.
+    pub fn zstring_end(&self, addr: ByteAddress) -> ByteAddress {
+        self.checked_zstring_end(addr)
+            .unwrap_or_else(|| ByteAddress(self.len_bytes()).max(addr))
+    }
+    /// Returns the address just past the end of a Z-string, or [`None`] if it runs past the end
+    /// of the story.
+    pub(crate) fn checked_zstring_end(&self, addr: ByteAddress) -> Option<ByteAddress> {
+        let mut addr = addr;
         loop {
-            let word = self.word(addr);
-            bytes_read += 2;
-            let bits = BEBitSlice::from_element(&word);
-            let is_end = bits[0];
-            let zchar1 = zchar_to_byte(&bits[1..6]);
-            let zchar2 = zchar_to_byte(&bits[6..11]);
-            let zchar3 = zchar_to_byte(&bits[11..16]);
-            let zchars = [zchar1, zchar2, zchar3];
-            for &zchar in &zchars {
-                self.decode_zchar(zchar, &mut mode, &mut state, &alphabet, string);
-            }
-            if is_end {
-                break;
-            }
+            let word = self.checked_word(addr)?;
             addr += 2;
-        }
-        bytes_read
-    }
-    fn decode_zchar(
-        &self,
-        current_zchar: u8,
-        alphabet_mode: &mut AlphabetMode,
-        state: &mut ZStringState,
-        alphabet: &Alphabet,
-        string: &mut String,
-    ) -> bool {
-        fn rotate_up(mode: AlphabetMode) -> AlphabetMode {
-            match mode {
-                AlphabetMode::Lowercase => AlphabetMode::Uppercase,
-                AlphabetMode::Uppercase => AlphabetMode::Symbol,
-                AlphabetMode::Symbol => AlphabetMode::Lowercase,
+            if word & 0x8000 != 0 {
+                return Some(addr);
             }
         }
-        fn rotate_down(mode: AlphabetMode) -> AlphabetMode {
-            match mode {
-                AlphabetMode::Lowercase => AlphabetMode::Symbol,
-                AlphabetMode::Uppercase => AlphabetMode::Lowercase,
-                AlphabetMode::Symbol => AlphabetMode::Uppercase,
-            }
-        }
-        let mut current_mode = *alphabet_mode;
-        let (printable, mut new_state) = match *state {
-            ZStringState::TenBitHigh => (false, ZStringState::TenBitLow(current_zchar)),
-            ZStringState::TenBitLow(prev) => {
-                let zscii = ((prev as u16) << 5) | current_zchar as u16;
-                if let Some(ch) = self.zscii_char(zscii) {
-                    string.push(ch);
-                }
-                (false, ZStringState::Unset)
-            }
-            ZStringState::Abbreviation(section) => {
-                let abbrv = ZStringAbbrv((section - 1) * 32 + current_zchar);
-                self.copy_abbrvd_zstring(abbrv, string);
-                (false, ZStringState::Unset)
-            }
-            ZStringState::ModeShift(mode) => {
-                current_mode = mode;
-                (true, ZStringState::Unset)
-            }
-            ZStringState::Unset => (true, ZStringState::Unset),
-        };
-        if printable {
-            new_state = match current_zchar {
-                0 => {
-                    string.push(' ');
-                    new_state
-                }
-                1 => {
-                    if self.version() == Version::V1 {
-                        string.push('\n');
-                        new_state
-                    } else {
-                        ZStringState::Abbreviation(1)
-                    }
-                }
-                2 => {
-                    if self.version() > Version::V2 {
-                        ZStringState::Abbreviation(2)
-                    } else {
-                        ZStringState::ModeShift(rotate_up(*alphabet_mode))
-                    }
-                }
-                3 => {
-                    if self.version() > Version::V2 {
-                        ZStringState::Abbreviation(3)
-                    } else {
-                        ZStringState::ModeShift(rotate_down(*alphabet_mode))
-                    }
-                }
-                4 => {
-                    if self.version() > Version::V2 {
-                        ZStringState::ModeShift(rotate_up(*alphabet_mode))
-                    } else {
-                        *alphabet_mode = rotate_up(*alphabet_mode);
-                        new_state
-                    }
-                }
-                5 => {
-                    if self.version() > Version::V2 {
-                        ZStringState::ModeShift(rotate_down(*alphabet_mode))
-                    } else {
-                        *alphabet_mode = rotate_down(*alphabet_mode);
-                        new_state
-                    }
-                }
-                6 if current_mode == AlphabetMode::Symbol => ZStringState::TenBitHigh,
-                _ => {
-                    string.push(alphabet.letter_for_zchar(current_zchar, current_mode));
-                    new_state
-                }
-            };
-        }
-        *state = new_state;
-        true
     }
     /// Returns the alphabet in use by this story.
-    pub fn alphabet(&self) -> Alphabet {
+    pub fn alphabet(&self) -> Alphabet<'_> {
         match self.version() {
-            Version::V1 => Alphabet {
-                lower: &DEFAULT_LOWERCASE_ALPHABET,
-                upper: &DEFAULT_UPPERCASE_ALPHABET,
-                symbol: &DEFAULT_SYMBOL_ALPHABET_V1,
-            },
-            Version::V2 | Version::V3 | Version::V4 => Alphabet::default(),
+            version if version <= Version::V4 => Alphabet::for_version(version),
             _ => {
-                let word = self.word(ByteAddress::DICTIONARY_LOCATION);
+                let word = self.word(ByteAddress::ALPHABET_TABLE_ADDRESS);
                 if word == 0 {
                     Alphabet::default()
                 } else {
                     let addr = ByteAddress::from(word);
-                    let bytes = &self[addr..(addr + 78)];
-                    Alphabet {
-                        lower: &bytes[0..26],
-                        upper: &bytes[26..52],
-                        symbol: &bytes[52..78],
-                    }
+                    Alphabet::from_table(&self[addr..(addr + 78)]).unwrap()
                 }
             }
         }
     }
     /// Returns the unicode table in use by this story.
-    pub fn unicode_table(&self) -> UnicodeTable {
-        if self.version() < Version::V5 {
-            UnicodeTable::default()
-        } else {
-            let word = self.word(ByteAddress::HEADER_EXTENSION_TABLE_ADDRESS);
-            if word == 0 {
-                UnicodeTable::default()
-            } else {
-                let ext_addr = ByteAddress::from(word);
-                let unicode_addr_addr =
-                    ext_addr + ByteAddress::HEADER_EXT_UNICODE_TRANSLATION_TABLE_LOCATION;
-                let unicode_addr = self.word(unicode_addr_addr);
-                if unicode_addr == 0 {
-                    UnicodeTable::default()
-                } else {
-                    let unicode_addr = ByteAddress::from(unicode_addr);
-                    let len = self[unicode_addr];
-                    let table = &self[(unicode_addr + 1)..(unicode_addr + 1 + len as usize)];
-                    UnicodeTable { table }
-                }
-            }
-        }
+    pub fn unicode_table(&self) -> UnicodeTable<'_> {
+        let unicode_addr = match self
+            .header_extension()
+            .and_then(|ext| ext.unicode_table_location())
+        {
+            Some(addr) => addr,
+            None => return UnicodeTable::default(),
+        };
+        let len = self[unicode_addr] as usize;
+        let table = &self[(unicode_addr + 1)..(unicode_addr + 1 + len * 2)];
+        UnicodeTable { table }
     }
     /// Returns the base address of the dictionary (i.e. the start of the table header).
     pub fn dictionary_base(&self) -> ByteAddress {
@@ -268,29 +166,45 @@ impl ZMachine {
             }
         }
     }
-    /// Converts a ZSCII character into a `char`.
+    /// Returns what a ZSCII character prints as.
+    pub fn zscii_output(&self, zscii: u16) -> ZsciiOutput {
+        ZsciiOutput::new(zscii, &self.unicode_table())
+    }
+    /// Converts a ZSCII character into a `char`, or returns [`None`] if it prints nothing or isn't
+    /// defined for output. Tab and sentence space are only defined in version 6, but are converted
+    /// in every version since most interpreters print them anyway.
     pub fn zscii_char(&self, zscii: u16) -> Option<char> {
-        match zscii {
-            9 => {
-                if self.version() == Version::V6 {
-                    Some('\t')
-                } else {
-                    None
-                }
-            }
-            11 => {
-                if self.version() == Version::V6 {
-                    Some(' ') // sentence space
-                } else {
-                    None
-                }
-            }
-            13 => Some('\n'),
-            32..=126 => Some(zscii as u8 as char),
-            155..=251 => Some(self.unicode_table().zscii_to_char(zscii as u8)),
-            _ => None,
+        self.zscii_output(zscii).to_char()
+    }
+    /// Converts a `char` into a ZSCII character, or returns [`None`] if it has no ZSCII code.
+    pub fn char_zscii(&self, ch: char) -> Option<u8> {
+        match ch {
+            '\n' => Some(13),
+            ' '..='~' => Some(ch as u8),
+            _ => self.unicode_table().char_to_zscii(ch),
         }
     }
+    /// Returns the word separators of a dictionary as ZSCII characters.
+    pub(crate) fn dictionary_separators(&self, dictionary: ByteAddress) -> Vec<u8> {
+        let len = self[dictionary] as usize;
+        self[(dictionary + 1)..(dictionary + 1 + len)].to_vec()
+    }
+    /// Returns the address of the dictionary entry that starts with an encoded word, or [`None`]
+    /// if there isn't one. The dictionary may be the story's or a user dictionary, whose entries
+    /// needn't be sorted.
+    pub fn dictionary_lookup(
+        &self,
+        dictionary: ByteAddress,
+        encoded: &[u8],
+    ) -> Option<ByteAddress> {
+        let separators = self[dictionary] as usize;
+        let entry_len = self[dictionary + separators + 1] as usize;
+        let count = self.word(dictionary + separators + 2) as i16;
+        let entries = dictionary + separators + 4;
+        (0..count.unsigned_abs() as usize)
+            .map(|x| entries + x * entry_len)
+            .find(|&entry| &self[entry..(entry + encoded.len())] == encoded)
+    }
     fn dictionary_words_base(&self) -> ByteAddress {
         self.dictionary_base() + self.word_separators_len() + 2
     }
@@ -318,17 +232,14 @@ impl ZMachine {
         );
         let word_sz = self.dictionary_entry_size();
         let offset = idx * word_sz + 2;
-        self.copy_zstring(
-            (ByteAddress::from(self.dictionary_words_base()) + offset).into(),
-            string,
-        );
+        self.copy_zstring(self.dictionary_words_base() + offset, string);
     }
     /// Returns a list of all words in the dictionary.
     pub fn dictionary_words(&self) -> Vec<String> {
         let len = self.dictionary_len();
         let mut vec = Vec::with_capacity(len);
         let word_sz = self.dictionary_entry_size();
-        let start = ByteAddress::from(self.dictionary_words_base() + 2);
+        let start = self.dictionary_words_base() + 2;
         for x in 0..len {
             vec.push(self.read_zstring(start + x * word_sz).0);
         }
@@ -336,6 +247,182 @@ impl ZMachine {
     }
 }
 
+/// An iterator over the characters of a Z-string, decoded as they are needed. Returned by
+/// [`ZMachine::zstring_chars`].
+///
+/// Characters that aren't defined for output are decoded as `?`. Abbreviations are expanded, except
+/// for abbreviations inside abbreviations, which the standard doesn't allow and which are skipped.
+#[derive(Clone)]
+pub struct ZStringChars<'a> {
+    machine: &'a ZMachine,
+    version: Version,
+    alphabet: Alphabet<'a>,
+    unicode_table: UnicodeTable<'a>,
+    /// The string being decoded, followed by the abbreviation being expanded if there is one.
+    stack: ArrayVec<[ZStringCursor; 2]>,
+    /// Whether Z-characters are extracted with [`zchars_bitslice`].
+    bitslice: bool,
+}
+
+/// A position in a Z-string, along with the decoding state at that point.
+#[derive(Debug, Copy, Clone)]
+struct ZStringCursor {
+    /// The address of the next word.
+    addr: ByteAddress,
+    zchars: [u8; 3],
+    /// The index of the next Z-character in `zchars`, or 3 if the next word needs reading.
+    idx: usize,
+    end: bool,
+    mode: AlphabetMode,
+    state: ZStringState,
+}
+
+impl ZStringCursor {
+    fn new(addr: ByteAddress) -> Self {
+        Self {
+            addr,
+            zchars: [0; 3],
+            idx: 3,
+            end: false,
+            mode: AlphabetMode::Lowercase,
+            state: ZStringState::Unset,
+        }
+    }
+    fn next_zchar(&mut self, machine: &ZMachine, bitslice: bool) -> Option<u8> {
+        if self.idx == 3 {
+            if self.end {
+                return None;
+            }
+            // a string that runs past the end of the story stops there
+            let word = machine.checked_word(self.addr)?;
+            self.addr += 2;
+            self.end = word & 0x8000 != 0;
+            self.zchars = if bitslice {
+                zchars_bitslice(word)
+            } else {
+                [
+                    (word >> 10) as u8 & 0b11111,
+                    (word >> 5) as u8 & 0b11111,
+                    word as u8 & 0b11111,
+                ]
+            };
+            self.idx = 0;
+        }
+        self.idx += 1;
+        Some(self.zchars[self.idx - 1])
+    }
+}
+
+/// Extracts the Z-characters from a word bit by bit, as Z-strings were decoded before shifts and
+/// masks were used.
+fn zchars_bitslice(word: Word) -> [u8; 3] {
+    let bits = BEBitSlice::<Word>::from_element(&word);
+    let zchar = |range: std::ops::Range<usize>| {
+        bits[range]
+            .iter()
+            .by_vals()
+            .fold(0, |zchar, bit| zchar << 1 | bit as u8)
+    };
+    [zchar(1..6), zchar(6..11), zchar(11..16)]
+}
+
+/// What a Z-character decodes to.
+enum Decoded {
+    Nothing,
+    Output(ZsciiOutput),
+    Abbreviation(ZStringAbbrv),
+}
+
+impl ZStringChars<'_> {
+    fn decode(&self, zchar: u8, cursor: &mut ZStringCursor) -> Decoded {
+        fn rotate_up(mode: AlphabetMode) -> AlphabetMode {
+            match mode {
+                AlphabetMode::Lowercase => AlphabetMode::Uppercase,
+                AlphabetMode::Uppercase => AlphabetMode::Symbol,
+                AlphabetMode::Symbol => AlphabetMode::Lowercase,
+            }
+        }
+        fn rotate_down(mode: AlphabetMode) -> AlphabetMode {
+            match mode {
+                AlphabetMode::Lowercase => AlphabetMode::Symbol,
+                AlphabetMode::Uppercase => AlphabetMode::Lowercase,
+                AlphabetMode::Symbol => AlphabetMode::Uppercase,
+            }
+        }
+        let early = self.version <= Version::V2;
+        let mode = match cursor.state {
+            ZStringState::TenBitHigh => {
+                cursor.state = ZStringState::TenBitLow(zchar);
+                return Decoded::Nothing;
+            }
+            ZStringState::TenBitLow(prev) => {
+                cursor.state = ZStringState::Unset;
+                let zscii = ((prev as u16) << 5) | zchar as u16;
+                return Decoded::Output(ZsciiOutput::new(zscii, &self.unicode_table));
+            }
+            ZStringState::Abbreviation(section) => {
+                cursor.state = ZStringState::Unset;
+                return Decoded::Abbreviation(ZStringAbbrv((section - 1) * 32 + zchar));
+            }
+            ZStringState::ModeShift(mode) => {
+                cursor.state = ZStringState::Unset;
+                mode
+            }
+            ZStringState::Unset => cursor.mode,
+        };
+        match zchar {
+            0 => return Decoded::Output(ZsciiOutput::Char(' ')),
+            1 if self.version == Version::V1 => return Decoded::Output(ZsciiOutput::Newline),
+            1 => cursor.state = ZStringState::Abbreviation(1),
+            2 | 3 if !early => cursor.state = ZStringState::Abbreviation(zchar),
+            2 => cursor.state = ZStringState::ModeShift(rotate_up(cursor.mode)),
+            3 => cursor.state = ZStringState::ModeShift(rotate_down(cursor.mode)),
+            4 if early => cursor.mode = rotate_up(cursor.mode),
+            5 if early => cursor.mode = rotate_down(cursor.mode),
+            4 => cursor.state = ZStringState::ModeShift(rotate_up(cursor.mode)),
+            5 => cursor.state = ZStringState::ModeShift(rotate_down(cursor.mode)),
+            6 if mode == AlphabetMode::Symbol => cursor.state = ZStringState::TenBitHigh,
+            7 if mode == AlphabetMode::Symbol && self.version > Version::V1 => {
+                return Decoded::Output(ZsciiOutput::Newline);
+            }
+            _ => {
+                let zscii = self.alphabet.table(mode)[zchar as usize - 6];
+                return Decoded::Output(ZsciiOutput::new(zscii as u16, &self.unicode_table));
+            }
+        }
+        Decoded::Nothing
+    }
+}
+
+impl Iterator for ZStringChars<'_> {
+    type Item = char;
+    fn next(&mut self) -> Option<char> {
+        loop {
+            let mut cursor = *self.stack.last()?;
+            let zchar = match cursor.next_zchar(self.machine, self.bitslice) {
+                Some(zchar) => zchar,
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+            };
+            let decoded = self.decode(zchar, &mut cursor);
+            *self.stack.last_mut().unwrap() = cursor;
+            match decoded {
+                Decoded::Nothing | Decoded::Output(ZsciiOutput::Nothing) => {}
+                Decoded::Output(ZsciiOutput::Invalid) => return Some('?'),
+                Decoded::Output(output) => return output.to_char(),
+                Decoded::Abbreviation(abbrv) => {
+                    if !self.stack.is_full() {
+                        let addr = self.machine.abbreviation_location(abbrv);
+                        self.stack.push(ZStringCursor::new(addr));
+                    }
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 enum ZStringState {
     TenBitHigh,
@@ -361,6 +448,50 @@ static DEFAULT_UNICODE_TABLE: [u8; 138] = [
     0x0, 0xbf,
 ];
 
+/// What a ZSCII character prints as.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ZsciiOutput {
+    /// Nothing is printed (ZSCII 0).
+    Nothing,
+    /// A newline (ZSCII 13, or Z-character 1 in version 1).
+    Newline,
+    /// A tab (ZSCII 9). Only defined in version 6.
+    Tab,
+    /// A space between sentences (ZSCII 11). Only defined in version 6.
+    SentenceSpace,
+    /// A character.
+    Char(char),
+    /// The character isn't defined for output. Codes 127-154 and 252-254 are only used for input,
+    /// and the extra characters 155-251 are only defined as far as the Unicode table goes.
+    Invalid,
+}
+
+impl ZsciiOutput {
+    fn new(zscii: u16, unicode_table: &UnicodeTable) -> Self {
+        match zscii {
+            0 => ZsciiOutput::Nothing,
+            9 => ZsciiOutput::Tab,
+            11 => ZsciiOutput::SentenceSpace,
+            13 => ZsciiOutput::Newline,
+            32..=126 => ZsciiOutput::Char(zscii as u8 as char),
+            155..=251 => unicode_table
+                .get(zscii as u8)
+                .map_or(ZsciiOutput::Invalid, ZsciiOutput::Char),
+            _ => ZsciiOutput::Invalid,
+        }
+    }
+    /// Returns the `char` printed, or [`None`] if there isn't one.
+    pub fn to_char(self) -> Option<char> {
+        match self {
+            ZsciiOutput::Nothing | ZsciiOutput::Invalid => None,
+            ZsciiOutput::Newline => Some('\n'),
+            ZsciiOutput::Tab => Some('\t'),
+            ZsciiOutput::SentenceSpace => Some(' '),
+            ZsciiOutput::Char(ch) => Some(ch),
+        }
+    }
+}
+
 /// The text mode used when indexing an [`Alphabet`].
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum AlphabetMode {
@@ -389,7 +520,43 @@ pub struct Alphabet<'a> {
     symbol: &'a [u8],
 }
 
-impl Alphabet<'_> {
+impl<'a> Alphabet<'a> {
+    /// Creates an alphabet from a table in the format stories use: 26 ZSCII characters for each of
+    /// the lowercase, uppercase and symbol alphabets. Returns [`None`] if the table isn't 78 bytes
+    /// long.
+    ///
+    /// The first two symbols are ignored, since Z-characters 6 and 7 in the symbol alphabet are
+    /// always the ZSCII escape and newline.
+    pub fn from_table(table: &'a [u8]) -> Option<Self> {
+        if table.len() != 78 {
+            return None;
+        }
+        Some(Self {
+            lower: &table[0..26],
+            upper: &table[26..52],
+            symbol: &table[52..78],
+        })
+    }
+    /// Returns the alphabet a version uses when the story doesn't provide its own.
+    pub fn for_version(version: Version) -> Self {
+        if version == Version::V1 {
+            Alphabet {
+                lower: &DEFAULT_LOWERCASE_ALPHABET,
+                upper: &DEFAULT_UPPERCASE_ALPHABET,
+                symbol: &DEFAULT_SYMBOL_ALPHABET_V1,
+            }
+        } else {
+            Alphabet::default()
+        }
+    }
+    /// Returns the ZSCII characters of one of the alphabets.
+    pub(crate) fn table(&self, mode: AlphabetMode) -> &'a [u8] {
+        match mode {
+            AlphabetMode::Lowercase => self.lower,
+            AlphabetMode::Uppercase => self.upper,
+            AlphabetMode::Symbol => self.symbol,
+        }
+    }
     /// Returns a letter at a particular index in a particular alphabet mode.
     pub fn letter_at_index(&self, idx: u8, mode: AlphabetMode) -> char {
         (match mode {
@@ -430,11 +597,28 @@ impl UnicodeTable<'_> {
     /// Returns a `char` at a particular index in the table.
     pub fn char_at_index(&self, idx: u8) -> char {
         let idx = idx as usize;
-        let high = self.table[idx];
-        let low = self.table[idx + 1];
+        let high = self.table[idx * 2];
+        let low = self.table[idx * 2 + 1];
         char::from_u32(u16::from_be_bytes([high, low]) as u32)
             .unwrap_or_else(|| panic!("Invalid char at unicode table index {}", idx))
     }
+    fn checked_char_at_index(&self, idx: u8) -> Option<char> {
+        let idx = idx as usize;
+        let bytes = self.table.get((idx * 2)..(idx * 2 + 2))?;
+        char::from_u32(u16::from_be_bytes([bytes[0], bytes[1]]) as u32)
+    }
+    /// Converts a ZSCII character to a `char`, or returns [`None`] if the table doesn't define it.
+    pub fn get(&self, zscii: u8) -> Option<char> {
+        self.checked_char_at_index(zscii.checked_sub(155)?)
+    }
+    /// Returns the number of characters in the table.
+    pub fn len(&self) -> usize {
+        self.table.len() / 2
+    }
+    /// Returns whether the table has no characters.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
     /// Converts a ZSCII character to a `char`.
     pub fn zscii_to_char(&self, zscii: u8) -> char {
         let idx = zscii.checked_sub(155).unwrap_or_else(|| {
@@ -444,13 +628,19 @@ impl UnicodeTable<'_> {
             )
         });
         assert!(
-            idx < self.table.len() as u8,
+            (idx as usize) < self.len(),
             "Invalid extended ZSCII char {} for this story, current unicode table spans 155..{}",
             zscii,
-            self.table.len()
+            155 + self.len()
         );
         self.char_at_index(idx)
     }
+    /// Converts a `char` to a ZSCII character, or returns [`None`] if it isn't in the table.
+    pub fn char_to_zscii(&self, ch: char) -> Option<u8> {
+        (0..self.len().min(97))
+            .find(|&idx| self.checked_char_at_index(idx as u8) == Some(ch))
+            .map(|idx| 155 + idx as u8)
+    }
 }
 
 impl Default for UnicodeTable<'_> {
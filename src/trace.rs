@@ -0,0 +1,242 @@
+use crate::*;
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Something that happened while a story was being traced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// An instruction was executed.
+    Instruction {
+        /// The instruction's address.
+        addr: ByteAddress,
+        /// The instruction, as [`ZMachine::format_instruction`] prints it.
+        text: String,
+        /// The values of the operands.
+        operands: Vec<Word>,
+        /// The value stored, for instructions that store one (except calls, which store when
+        /// the routine returns).
+        result: Option<Word>,
+        /// Whether the branch was taken, for instructions that branch. Neither this nor the
+        /// result is given for an instruction that failed.
+        branched: Option<bool>,
+        /// How many routines were running, counting the main routine.
+        depth: usize,
+    },
+    /// A routine was called.
+    Call {
+        /// The routine's address.
+        routine: ByteAddress,
+        /// The routine, as [`ZMachine::describe_routine`] describes it.
+        name: String,
+        /// The arguments.
+        args: Vec<Word>,
+        /// How many routines are running, including this one.
+        depth: usize,
+    },
+    /// A routine returned.
+    Return {
+        /// The routine's address.
+        routine: ByteAddress,
+        /// The return value.
+        value: Word,
+        /// How many routines were running, including this one.
+        depth: usize,
+    },
+}
+
+impl TraceEvent {
+    /// Returns how many routines were running.
+    pub fn depth(&self) -> usize {
+        match self {
+            TraceEvent::Instruction { depth, .. }
+            | TraceEvent::Call { depth, .. }
+            | TraceEvent::Return { depth, .. } => *depth,
+        }
+    }
+}
+
+/// Formats an event as one line, indented by its depth so that calls form a tree.
+impl Display for TraceEvent {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let indent = self.depth().saturating_sub(1) * 2;
+        write!(f, "{:indent$}", "", indent = indent)?;
+        match self {
+            TraceEvent::Instruction {
+                text,
+                operands,
+                result,
+                branched,
+                ..
+            } => {
+                write!(f, "{}", text)?;
+                if !operands.is_empty() {
+                    write!(f, "  ({:04x?})", operands)?;
+                }
+                if let Some(result) = result {
+                    write!(f, "  = {:#06x}", result)?;
+                }
+                match branched {
+                    Some(true) => write!(f, "  branched"),
+                    Some(false) => write!(f, "  not branched"),
+                    None => Ok(()),
+                }
+            }
+            TraceEvent::Call { name, args, .. } => write!(f, "-> {} {:04x?}", name, args),
+            TraceEvent::Return { routine, value, .. } => {
+                write!(f, "<- routine {:#x} returned {:#06x}", routine.0, value)
+            }
+        }
+    }
+}
+
+/// Somewhere to send a trace of execution.
+pub trait TraceSink {
+    /// Records an event.
+    fn record(&mut self, event: &TraceEvent);
+    /// Returns whether instructions should be traced, rather than only calls and returns. Tracing
+    /// instructions slows execution down much more.
+    fn instructions(&self) -> bool {
+        true
+    }
+}
+
+/// A [`TraceSink`] that writes each event as a line of text. Nothing more is written after an
+/// error.
+#[derive(Debug)]
+pub struct FileTrace<W: Write> {
+    writer: W,
+    calls_only: bool,
+    failed: bool,
+}
+
+impl FileTrace<BufWriter<File>> {
+    /// Creates a trace written to a file, replacing it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> FileTrace<W> {
+    /// Creates a trace written to a writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            calls_only: false,
+            failed: false,
+        }
+    }
+    /// Records only calls and returns, as a call tree, and not each instruction.
+    pub fn calls_only(self, calls_only: bool) -> Self {
+        Self { calls_only, ..self }
+    }
+}
+
+impl<W: Write> TraceSink for FileTrace<W> {
+    fn record(&mut self, event: &TraceEvent) {
+        if !self.failed {
+            self.failed = writeln!(self.writer, "{}", event).is_err();
+        }
+    }
+    fn instructions(&self) -> bool {
+        !self.calls_only
+    }
+}
+
+/// A [`TraceSink`] that keeps only the most recent events, for attaching to bug reports. Clones
+/// share the same buffer, so one can be given to the machine and another kept to read it.
+#[derive(Debug, Clone)]
+pub struct RingTrace {
+    inner: Arc<Mutex<VecDeque<TraceEvent>>>,
+    capacity: usize,
+}
+
+impl RingTrace {
+    /// Creates a buffer that keeps the last `capacity` events.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+    /// Returns the events kept, oldest first.
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.inner.lock().unwrap().iter().cloned().collect()
+    }
+    /// Writes the events kept as lines of text, oldest first.
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        for event in self.inner.lock().unwrap().iter() {
+            writeln!(writer, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl TraceSink for RingTrace {
+    fn record(&mut self, event: &TraceEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = self.inner.lock().unwrap();
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event.clone());
+    }
+}
+
+impl ZMachine {
+    /// Starts tracing execution to a sink, replacing any sink already set.
+    pub fn set_trace_sink(&mut self, sink: impl TraceSink + Send + 'static) {
+        self.trace = Some(Box::new(sink));
+    }
+    /// Stops tracing execution, returning the sink.
+    pub fn take_trace_sink(&mut self) -> Option<Box<dyn TraceSink + Send>> {
+        self.trace.take()
+    }
+    pub(crate) fn trace(&mut self, event: TraceEvent) {
+        if let Some(sink) = &mut self.trace {
+            sink.record(&event);
+        }
+    }
+    /// Traces an instruction that has just been executed, given its address, the values of its
+    /// operands and what it did, which is [`None`] if it failed.
+    pub(crate) fn trace_instruction(
+        &mut self,
+        addr: ByteAddress,
+        values: &[Word],
+        action: Option<&Action>,
+    ) {
+        match &self.trace {
+            Some(sink) if sink.instructions() => {}
+            _ => return,
+        }
+        let instruction = match self.decode_instruction(addr) {
+            Ok(instruction) => instruction,
+            Err(_) => return,
+        };
+        let result = match (instruction.store, action) {
+            (Some(var), Some(Action::Continue)) => Some(self.peek_variable(var)),
+            _ => None,
+        };
+        let branched = match action {
+            Some(action) => instruction.branch.map(|_| match action {
+                Action::Return(_) => true,
+                _ => self.pc != instruction.next,
+            }),
+            None => None,
+        };
+        let event = TraceEvent::Instruction {
+            addr,
+            text: self.format_instruction(&instruction),
+            operands: values.to_vec(),
+            result,
+            branched,
+            depth: self.frames.len(),
+        };
+        self.trace(event);
+    }
+}
@@ -2,7 +2,7 @@
 
 use crate::*;
 use arrayvec::ArrayVec;
-use failure::Fail;
+use thiserror::Error;
 
 mod op0;
 pub mod opcodes;
@@ -15,11 +15,212 @@ mod var;
 pub use self::var::*;
 mod ext;
 pub use self::ext::*;
+mod fault;
+pub use self::fault::*;
+mod cache;
+pub use self::cache::*;
+
+/// A routine's call frame.
+#[derive(Debug, Clone)]
+pub(crate) struct Frame {
+    pub(crate) routine: ByteAddress,
+    pub(crate) return_pc: ByteAddress,
+    pub(crate) store: Option<u8>,
+    pub(crate) locals: ArrayVec<[Word; 15]>,
+    pub(crate) arg_count: usize,
+    pub(crate) stack_base: usize,
+}
+
+impl Frame {
+    /// Returns the frame that the main routine runs in before version 6, which has no locals and
+    /// can't be returned from.
+    fn main(pc: ByteAddress) -> Self {
+        Self {
+            routine: pc,
+            return_pc: ByteAddress::ZERO,
+            store: None,
+            locals: ArrayVec::new(),
+            arg_count: 0,
+            stack_base: 0,
+        }
+    }
+}
 
 impl ZMachine {
-    fn execute(&mut self, addr: &mut ByteAddress) -> ExecuteResult {
-        let opcode_byte = self[*addr];
-        let (form, operands_desc, opcode) = match opcode_byte & 0b_11_000000 {
+    /// Clears the stack and call frames, and starts execution again from the story's initial
+    /// program counter (or its main routine in version 6).
+    pub(crate) fn reset_execution(&mut self) {
+        self.stack.clear();
+        self.frames.clear();
+        self.has_quit = false;
+        self.last_return = 0;
+        self.screen = Screen::default();
+        self.streams.memory.clear();
+        self.sound = SoundState {
+            player: self.sound.player.take(),
+            ..SoundState::default()
+        };
+        let initial = self.word(ByteAddress::INITIAL_PC_LOCATION);
+        if self.version() == Version::V6 {
+            // the main routine is called like any other, and its frame is the bottom one
+            let main = self.resolve_packed_address(initial as usize, true);
+            self.pc = main;
+            if main.0 < self.len_bytes() {
+                self.call_routine(main, &[], None);
+            } else {
+                self.frames.push(Frame::main(main));
+            }
+        } else {
+            self.pc = initial.into();
+            self.frames.push(Frame::main(self.pc));
+        }
+    }
+    /// Runs the story until it quits, or until it's suspended for input after
+    /// [`resume`](ZMachine::resume) has been used.
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        self.running = !self.has_quit;
+        while self.running && self.suspend.waiting_for.is_none() {
+            self.step()?;
+        }
+        Ok(())
+    }
+    /// Executes a single instruction. Any routines that are due to be called, such as the routine
+    /// for a sound effect that has just finished, are run to completion first. Does nothing once
+    /// the story has quit.
+    pub fn step(&mut self) -> Result<(), RuntimeError> {
+        if self.has_quit {
+            return Ok(());
+        }
+        self.running = true;
+        if let Some(routine) = self.finished_sound_routine() {
+            self.invoke_routine(routine, &[])?;
+        }
+        self.step_instruction()
+    }
+    fn step_instruction(&mut self) -> Result<(), RuntimeError> {
+        let start = self.pc;
+        let mut addr = self.pc;
+        let mut values = ArrayVec::new();
+        let action = match self.execute(&mut addr, &mut values) {
+            Ok(action) => action,
+            Err(error) => {
+                if self.trace.is_some() {
+                    self.trace_instruction(start, &values, None);
+                }
+                return Err(self.runtime_error(error, start));
+            }
+        };
+        if let Action::Wait(input) = action {
+            self.suspend.waiting_for = Some(input);
+            return Ok(());
+        }
+        // returning moves the program counter itself, and leaves it alone if it fails
+        if !matches!(action, Action::Return(_)) {
+            self.pc = addr;
+        }
+        if self.trace.is_some() {
+            self.trace_instruction(start, &values, Some(&action));
+        }
+        match action {
+            Action::Continue => {}
+            Action::Return(value) => {
+                if let Err(error) = self.return_from_routine(value) {
+                    return Err(self.runtime_error(error, start));
+                }
+            }
+            Action::Call { addr, retvar, args } => self.call_routine(addr, &args, retvar),
+            Action::Wait(_) => unreachable!(),
+        }
+        Ok(())
+    }
+    /// Returns the address of the next instruction to be executed.
+    pub fn pc(&self) -> ByteAddress {
+        self.pc
+    }
+    /// Returns whether the story is running, i.e. it hasn't quit.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+    /// Returns whether the story has quit. It can't be run again until it's restarted.
+    pub fn has_quit(&self) -> bool {
+        self.has_quit
+    }
+    /// Executes the instruction at `addr`, leaving it pointing at the next instruction. The values
+    /// of the operands are put in `values`. The instruction's writes to memory are checked.
+    fn execute(
+        &mut self,
+        addr: &mut ByteAddress,
+        values: &mut ArrayVec<[Word; 8]>,
+    ) -> ExecuteResult {
+        self.protect_memory = true;
+        let result = self.execute_unprotected(addr, values);
+        self.protect_memory = false;
+        match self.write_error.take() {
+            Some(error) => Err(error),
+            None => result,
+        }
+    }
+    fn execute_unprotected(
+        &mut self,
+        addr: &mut ByteAddress,
+        values: &mut ArrayVec<[Word; 8]>,
+    ) -> ExecuteResult {
+        let DecodedOpcode {
+            form,
+            desc,
+            opcode,
+            operands,
+        } = self.decode_opcode_cached(addr)?;
+        if let Some(input) = self.input_needed(form, desc, opcode) {
+            return Ok(Action::Wait(input));
+        }
+        for operand in operands {
+            values.push(
+                operand
+                    .resolve(self)?
+                    .ok_or(ExecuteError::InvalidInstructionFormat(addr.0))?,
+            );
+        }
+        match desc {
+            OperandsDesc::Op0 => self.execute_op0(addr, desc, opcode),
+            OperandsDesc::Op1 => self.execute_op1(addr, values[0], opcode),
+            OperandsDesc::Op2 => self.execute_op2(addr, values, opcode),
+            OperandsDesc::Var if form == OpcodeForm::Extended => {
+                self.execute_ext(addr, values, opcode)
+            }
+            OperandsDesc::Var => self.execute_var(addr, values, opcode),
+        }
+    }
+    /// Reads a byte of an instruction. Running past the end of memory can't be tolerated, since
+    /// there's no instruction to carry on with.
+    fn code_byte(&self, addr: ByteAddress) -> Result<u8, ExecuteError> {
+        self.memory
+            .get(addr.0)
+            .copied()
+            .ok_or(ExecuteError::MemoryOutOfRange(addr.0))
+    }
+    /// Reads a word of an instruction, as [`code_byte`](ZMachine::code_byte) does.
+    fn code_word(&self, addr: ByteAddress) -> Result<Word, ExecuteError> {
+        self.checked_word(addr)
+            .ok_or(ExecuteError::MemoryOutOfRange(addr.0))
+    }
+    /// Reads an instruction's opcode and operands, leaving `addr` pointing at whatever follows
+    /// them (a store variable, branch or inline string, if the instruction has one). Returns an
+    /// error if the instruction runs past the end of memory.
+    pub(crate) fn decode_opcode(
+        &self,
+        addr: &mut ByteAddress,
+    ) -> Result<DecodedOpcode, ExecuteError> {
+        let opcode_byte = self.code_byte(*addr)?;
+        let (form, desc, opcode) = match opcode_byte & 0b_11_000000 {
+            _ if opcode_byte == 190 && self.version() >= Version::V5 => {
+                *addr += 1;
+                (
+                    OpcodeForm::Extended,
+                    OperandsDesc::Var,
+                    self.code_byte(*addr)?,
+                )
+            }
             0b_11_000000 => (
                 OpcodeForm::Variable,
                 if opcode_byte & 0b00_1_00000 == 0b00_1_00000 {
@@ -38,127 +239,430 @@ impl ZMachine {
                 },
                 opcode_byte & 0b0000_1111,
             ),
-            _ => {
-                if opcode_byte == 190 && self.version() >= Version::V5 {
-                    *addr += 1;
-                    (OpcodeForm::Extended, OperandsDesc::Var, self[*addr])
+            _ => (
+                OpcodeForm::Long,
+                OperandsDesc::Op2,
+                opcode_byte & 0b000_11111,
+            ),
+        };
+        *addr += 1;
+        let mut operands = ArrayVec::<[Operand; 8]>::new();
+        match form {
+            OpcodeForm::Short => {
+                if let OperandsDesc::Op1 = desc {
+                    operands.push(self.read_operand((opcode_byte >> 4) & 0b11, addr)?);
+                }
+            }
+            OpcodeForm::Long => {
+                for &bit in &[0b0_1_000000, 0b00_1_00000] {
+                    let kind = if opcode_byte & bit == 0 { 0b01 } else { 0b10 };
+                    operands.push(self.read_operand(kind, addr)?);
+                }
+            }
+            OpcodeForm::Variable | OpcodeForm::Extended => {
+                let double = form == OpcodeForm::Variable
+                    && (opcode_byte == 0xEC || opcode_byte == 0xFA)
+                    && self.version() >= Version::V4;
+                let kinds = if double {
+                    let kinds = self.code_word(*addr)?;
+                    *addr += 2;
+                    kinds
                 } else {
-                    (
-                        OpcodeForm::Long,
-                        OperandsDesc::Op2,
-                        opcode_byte & 0b000_11111,
-                    )
+                    let kinds = self.code_byte(*addr)?;
+                    *addr += 1;
+                    (kinds as u16) << 8 | 0xFF
+                };
+                for x in 0..8 {
+                    let kind = (kinds >> (14 - x * 2)) as u8 & 0b11;
+                    if kind == 0b11 {
+                        break;
+                    }
+                    operands.push(self.read_operand(kind, addr)?);
                 }
             }
-        };
-        *addr += 1;
-        match operands_desc {
-            OperandsDesc::Op0 => self.execute_op0(addr, operands_desc, opcode),
-            _ => unimplemented!(),
+        }
+        Ok(DecodedOpcode {
+            form,
+            desc,
+            opcode,
+            operands,
+        })
+    }
+    fn read_operand(&self, kind: u8, addr: &mut ByteAddress) -> Result<Operand, ExecuteError> {
+        Ok(match kind {
+            0b00 => {
+                let word = self.code_word(*addr)?;
+                *addr += 2;
+                Operand::LargeConstant(word)
+            }
+            0b01 => {
+                let byte = self.code_byte(*addr)?;
+                *addr += 1;
+                Operand::SmallConstant(byte)
+            }
+            0b10 => {
+                let byte = self.code_byte(*addr)?;
+                *addr += 1;
+                Operand::Variable(byte)
+            }
+            _ => Operand::Omitted,
+        })
+    }
+    fn branch(&mut self, success: bool, addr: &mut ByteAddress) -> ExecuteResult {
+        let (on, offset) = self.read_branch(addr)?;
+        if success != on {
+            return Ok(Action::Continue);
+        }
+        match offset {
+            0 => Ok(Action::Return(0)),
+            1 => Ok(Action::Return(1)),
+            _ => {
+                self.jump(addr, offset)?;
+                Ok(Action::Continue)
+            }
         }
     }
-    fn branch(&mut self, success: bool, addr: &mut ByteAddress) {
-        let top = self[*addr];
+    /// Reads an instruction's branch: whether it branches on success or failure, and the offset.
+    pub(crate) fn read_branch(&self, addr: &mut ByteAddress) -> Result<(bool, i16), ExecuteError> {
+        let top = self.code_byte(*addr)?;
         *addr += 1;
-        let branch = if top & 0b1_0000000 == 0 {
-            !success
-        } else {
-            success
-        };
-        let addr = if top & 0b0_1_000000 == 0b0_1_000000 {
-            *addr + (top & 0b00_111111) as usize
+        let on = top & 0b1_0000000 != 0;
+        let offset = if top & 0b0_1_000000 == 0b0_1_000000 {
+            (top & 0b00_111111) as i16
         } else {
-            let bottom = self[*addr];
+            let bottom = self.code_byte(*addr)?;
             *addr += 1;
-            let signed = (((top & 0b00_111111) as u16) << 8 | bottom as u16) as i16;
-            if signed >= 0 {
-                *addr + (signed as usize)
-            } else {
-                *addr - ((-signed) as usize)
-            }
+            // sign-extend the 14-bit offset
+            ((((top & 0b00_111111) as u16) << 8 | bottom as u16) << 2) as i16 >> 2
         };
-        if branch {
-            self.jump(addr - 2);
-        }
+        Ok((on, offset))
     }
-    fn store(&mut self, value: u16, addr: &mut ByteAddress) {
-        let var = self[*addr];
-        *addr += 1;
-        self.set_variable(var, value);
+    fn store(&mut self, value: u16, addr: &mut ByteAddress) -> Result<(), ExecuteError> {
+        let var = self.read_store(addr)?;
+        self.write_variable(var, value)
     }
-    fn read_store(&self, addr: &mut ByteAddress) -> u8 {
-        let var = self[*addr];
+    pub(crate) fn read_store(&self, addr: &mut ByteAddress) -> Result<u8, ExecuteError> {
+        let var = self.code_byte(*addr)?;
         *addr += 1;
-        var
+        Ok(var)
+    }
+    /// Moves an address by a jump or branch offset, which is relative to the address after the
+    /// instruction, minus 2. Returns an error if that's before the start of memory; a target past
+    /// the end is found when it's decoded.
+    fn jump(&self, addr: &mut ByteAddress, offset: i16) -> Result<(), ExecuteError> {
+        *addr = jump_target(*addr, offset).ok_or(ExecuteError::MemoryOutOfRange(addr.0))?;
+        Ok(())
+    }
+    /// Returns an [`Action`] calling the routine at a packed address. Calling address 0 does
+    /// nothing and returns false.
+    fn call(&mut self, packed: Word, args: &[Word], retvar: Option<u8>) -> ExecuteResult {
+        let routine = self.resolve_packed_address(packed as usize, true);
+        if packed == 0 || !self.check_memory(routine.0, 1)? {
+            if let Some(var) = retvar {
+                self.write_variable(var, 0)?;
+            }
+            return Ok(Action::Continue);
+        }
+        if self[routine] > 15 {
+            return Err(ExecuteError::TooManyLocals(routine.0));
+        }
+        Ok(Action::Call {
+            addr: routine,
+            retvar,
+            args: args.iter().copied().take(7).collect(),
+        })
     }
-    /// Performs an execution jump to a particular address.
-    pub fn jump(&mut self, addr: ByteAddress) {
-        unimplemented!();
+    /// Enters the routine at an address, to return to the current program counter. Routines that
+    /// instructions call have been checked to have at most 15 locals; any more are left out.
+    pub(crate) fn call_routine(&mut self, routine: ByteAddress, args: &[Word], store: Option<u8>) {
+        let count = (self[routine] as usize).min(15);
+        let mut addr = routine + 1;
+        let mut locals = ArrayVec::new();
+        for x in 0..count {
+            let initial = if self.version() < Version::V5 {
+                let word = self.word(addr);
+                addr += 2;
+                word
+            } else {
+                0
+            };
+            locals.push(args.get(x).copied().unwrap_or(initial));
+        }
+        self.frames.push(Frame {
+            routine,
+            return_pc: self.pc,
+            store,
+            locals,
+            arg_count: args.len(),
+            stack_base: self.stack.len(),
+        });
+        self.pc = addr;
+        if self.trace.is_some() {
+            let depth = self.frames.len();
+            let name = self.describe_routine(routine, routine);
+            self.trace(TraceEvent::Call {
+                routine,
+                name,
+                args: args.to_vec(),
+                depth,
+            });
+        }
     }
-    /// Prints a string to the screen.
-    pub fn print(&mut self, string: &str) {
-        unimplemented!();
+    /// Leaves the current routine, storing its return value if the caller asked for it.
+    pub(crate) fn return_from_routine(&mut self, value: Word) -> Result<(), ExecuteError> {
+        if self.frames.len() <= 1 {
+            self.fault(ExecuteError::ReturnFromMain)?;
+            self.quit();
+            return Ok(());
+        }
+        if self.trace.is_some() {
+            let routine = self.frames.last().unwrap().routine;
+            let depth = self.frames.len();
+            self.trace(TraceEvent::Return {
+                routine,
+                value,
+                depth,
+            });
+        }
+        let frame = self.frames.pop().unwrap();
+        self.stack.truncate(frame.stack_base);
+        self.pc = frame.return_pc;
+        self.last_return = value;
+        match frame.store {
+            Some(var) => self.write_variable(var, value),
+            None => Ok(()),
+        }
     }
-    /// Prints a newline to the screen.
-    pub fn print_newline(&mut self) {
-        unimplemented!();
+    /// Calls a routine and runs it until it returns, then carries on from where execution was.
+    /// Returns the routine's return value.
+    ///
+    /// The routine can't be suspended partway through for input, since nothing would carry it on
+    /// afterwards: if the story is suspending for input and the routine asks for some, it's
+    /// abandoned and an error is returned.
+    pub fn invoke_routine(&mut self, addr: ByteAddress, args: &[Word]) -> RoutineResult {
+        let waiting_for = self.suspend.waiting_for.take();
+        let depth = self.frames.len();
+        self.call_routine(addr, args, None);
+        while self.running && self.frames.len() > depth {
+            self.step_instruction()?;
+            if self.suspend.waiting_for.is_some() {
+                let error = self.runtime_error(ExecuteError::InputInRoutine(addr.0), self.pc);
+                let frame = &self.frames[depth];
+                self.pc = frame.return_pc;
+                self.stack.truncate(frame.stack_base);
+                self.frames.truncate(depth);
+                self.suspend.waiting_for = waiting_for;
+                return Err(error);
+            }
+        }
+        self.suspend.waiting_for = waiting_for;
+        Ok(self.last_return)
     }
-    /// Asks the user whether they want to save the game. Returns whether or not they did.
+    /// Asks the user whether they want to save the game. Returns whether or not they did. There's
+    /// no saved game format yet, so saving always fails.
     pub fn request_save(&mut self) -> bool {
-        unimplemented!()
+        false
     }
     /// Asks the user whether they want to restore the game from a save. Returns whether or not they
-    /// did.
+    /// did. There's no saved game format yet, so restoring always fails.
     pub fn request_restore(&mut self) -> bool {
-        unimplemented!()
+        false
+    }
+    pub(crate) fn global_address(&self, var: u8) -> ByteAddress {
+        let base: ByteAddress = self
+            .word(ByteAddress::GLOBAL_VARIABLE_TABLE_LOCATION)
+            .into();
+        base + (var as usize - 16) * 2
+    }
+    fn locals_mut(&mut self) -> &mut ArrayVec<[Word; 15]> {
+        &mut self
+            .frames
+            .last_mut()
+            .expect("No routine is running")
+            .locals
+    }
+    /// Returns the value of a variable: the top of the stack (popping it), a local variable in the
+    /// current routine, or a global variable.
+    pub fn variable(&mut self, var: u8) -> u16 {
+        if var == 0 {
+            self.pop_stack()
+        } else {
+            self.peek_variable(var)
+        }
     }
-    /// Returns the value of a global variable or local variable in the current routine.
-    pub fn variable(&self, var: u8) -> u16 {
-        unimplemented!()
+    /// Returns the value of a variable without popping the stack.
+    pub fn peek_variable(&self, var: u8) -> u16 {
+        match var {
+            0 => self.peek_stack(),
+            1..=15 => {
+                let locals = &self.frames.last().expect("No routine is running").locals;
+                *locals
+                    .get(var as usize - 1)
+                    .unwrap_or_else(|| panic!("Local variable {} doesn't exist", var))
+            }
+            _ => self.word(self.global_address(var)),
+        }
     }
-    /// Sets a global variable or local variable in the current routine to a value.
+    /// Sets a variable to a value: pushing it to the stack, or setting a local variable in the
+    /// current routine or a global variable.
     pub fn set_variable(&mut self, var: u8, value: u16) {
-        unimplemented!()
+        if var == 0 {
+            self.push_stack(value);
+        } else {
+            self.replace_variable(var, value);
+        }
+    }
+    /// Sets a variable to a value, replacing the top of the stack rather than pushing to it. This
+    /// is how variables given by number (e.g. to `inc` or `store`) are written.
+    pub(crate) fn replace_variable(&mut self, var: u8, value: u16) {
+        match var {
+            0 => {
+                self.pop_stack();
+                self.push_stack(value);
+            }
+            1..=15 => {
+                let local = self
+                    .locals_mut()
+                    .get_mut(var as usize - 1)
+                    .unwrap_or_else(|| panic!("Local variable {} doesn't exist", var));
+                *local = value;
+            }
+            _ => self.write_word(self.global_address(var), value),
+        }
     }
     /// Restarts the game. The only surviving information is the transcription mode and the fixed
     /// pitch font mode.
     pub fn restart(&mut self) {
-        unimplemented!()
+        let dynamic_end = self.word(ByteAddress::STATIC_MEMORY_LOCATION) as usize;
+        let end = dynamic_end.min(self.original.len());
+        // the header is kept: the interpreter's fields stay filled in, and the game may only
+        // change the transcripting and fixed pitch bits in it
+        if end > 64 {
+            self.memory[64..end].copy_from_slice(&self.original[64..end]);
+        }
+        self.reset_execution();
+    }
+    /// Pushes a value onto the stack.
+    pub fn push_stack(&mut self, value: u16) {
+        self.stack.push(value);
     }
     /// Pops the top value off of the stack and returns it.
     pub fn pop_stack(&mut self) -> u16 {
-        unimplemented!()
+        let base = self.frames.last().map_or(0, |frame| frame.stack_base);
+        assert!(self.stack.len() > base, "Stack underflow");
+        self.stack.pop().unwrap()
+    }
+    /// Returns the top value of the stack without popping it.
+    pub fn peek_stack(&self) -> u16 {
+        let base = self.frames.last().map_or(0, |frame| frame.stack_base);
+        assert!(self.stack.len() > base, "Stack underflow");
+        *self.stack.last().unwrap()
     }
     /// Returns the current stack frame.
     pub fn stack_frame(&self) -> u16 {
-        unimplemented!()
-    }
-    /// Stops execution immediately. Returns from [`run`](ZMachine::run).
-    pub fn quit(&mut self) {
-        unimplemented!()
+        self.frames.len() as u16
     }
-    /// Updates the status line without waiting for keyboard input.
-    pub fn update_status_line(&mut self) {
-        unimplemented!()
+    /// Returns a value from the routine that was running when
+    /// [`stack_frame`](ZMachine::stack_frame) returned `frame`, discarding any routines it called.
+    pub(crate) fn throw(&mut self, value: Word, frame: u16) -> ExecuteResult {
+        if frame as usize <= 1 || frame as usize > self.frames.len() {
+            return Err(ExecuteError::InvalidStackFrame(frame));
+        }
+        self.frames.truncate(frame as usize);
+        Ok(Action::Return(value))
     }
-    pub fn invoke_routine(&mut self, addr: ByteAddress, retvar: u8) -> RoutineResult {
-        unimplemented!()
+    /// Stops execution immediately. Returns from [`run`](ZMachine::run), and nothing more is
+    /// executed until the story is restarted.
+    pub fn quit(&mut self) {
+        self.running = false;
+        self.has_quit = true;
     }
 }
 
-#[derive(Debug, Clone, Fail)]
+#[derive(Debug, Clone, Error)]
 pub enum ExecuteError {
-    #[fail(display = "Invalid opcode {}", _0)]
+    /// The opcode doesn't exist in the story's version.
+    #[error("Invalid opcode {0}")]
     InvalidOpcode(u8),
-    #[fail(display = "Invalid instruction format at address {}", _0)]
+    #[error("Invalid instruction format at address {0}")]
     InvalidInstructionFormat(usize),
+    #[error("ZSCII {0} is not defined for output")]
+    InvalidZscii(u16),
+    #[error("Object 0 was used")]
+    ObjectZero,
+    #[error("Object {0} doesn't exist")]
+    InvalidObject(Word),
+    #[error("Write outside dynamic memory at address {0}")]
+    StaticMemoryWrite(usize),
+    #[error("Write to read-only header byte {0}")]
+    HeaderWrite(usize),
+    #[error("Stack underflow")]
+    StackUnderflow,
+    #[error("Variable {0} doesn't exist")]
+    InvalidVariable(u8),
+    #[error("Division by zero")]
+    DivisionByZero,
+    /// Output stream 3 was selected while already nested as deeply as it can be.
+    #[error("Output stream 3 nested too deeply")]
+    StreamsTooDeep,
+    #[error("Invalid property {1} of object {0}")]
+    InvalidProperty(Word, Word),
+    #[error("Attribute {0} doesn't exist")]
+    InvalidAttribute(Word),
+    #[error("Read outside memory at address {0}")]
+    MemoryOutOfRange(usize),
+    #[error("Returned from the main routine")]
+    ReturnFromMain,
+    #[error("Routine at address {0} has more than 15 locals")]
+    TooManyLocals(usize),
+    /// `throw` was given a stack frame that isn't running.
+    #[error("Invalid stack frame {0}")]
+    InvalidStackFrame(Word),
+    /// A routine run by [`ZMachine::invoke_routine`] asked for input while the story was
+    /// suspending for it.
+    #[error("Routine at address {0} asked for input, but can't be suspended")]
+    InputInRoutine(usize),
+}
+
+impl ExecuteError {
+    /// Returns the fault that this error is, if it can be tolerated.
+    pub fn fault(&self) -> Option<Fault> {
+        match self {
+            ExecuteError::ObjectZero => Some(Fault::ObjectZero),
+            ExecuteError::InvalidObject(_) => Some(Fault::InvalidObject),
+            ExecuteError::StaticMemoryWrite(_) => Some(Fault::StaticMemoryWrite),
+            ExecuteError::HeaderWrite(_) => Some(Fault::HeaderWrite),
+            ExecuteError::StackUnderflow => Some(Fault::StackUnderflow),
+            ExecuteError::InvalidVariable(_) => Some(Fault::InvalidVariable),
+            ExecuteError::DivisionByZero => Some(Fault::DivisionByZero),
+            ExecuteError::InvalidProperty(..) => Some(Fault::InvalidProperty),
+            ExecuteError::InvalidAttribute(_) => Some(Fault::InvalidAttribute),
+            ExecuteError::MemoryOutOfRange(_) => Some(Fault::MemoryOutOfRange),
+            ExecuteError::ReturnFromMain => Some(Fault::ReturnFromMain),
+            ExecuteError::InvalidOpcode(_)
+            | ExecuteError::InvalidInstructionFormat(_)
+            | ExecuteError::InvalidZscii(_)
+            | ExecuteError::StreamsTooDeep
+            | ExecuteError::TooManyLocals(_)
+            | ExecuteError::InvalidStackFrame(_)
+            | ExecuteError::InputInRoutine(_) => None,
+        }
+    }
 }
 
 type ExecuteResult = Result<Action, ExecuteError>;
-type RoutineResult = Result<u16, ExecuteError>;
+type RoutineResult = Result<u16, RuntimeError>;
+
+/// Returns where a jump or branch offset goes from the address after the instruction, or [`None`]
+/// if that's before the start of memory.
+pub(crate) fn jump_target(next: ByteAddress, offset: i16) -> Option<ByteAddress> {
+    next.0
+        .checked_add_signed(offset as isize - 2)
+        .map(ByteAddress)
+}
 
-#[derive(Debug)]
-enum OpcodeForm {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum OpcodeForm {
     Long,
     Short,
     Extended,
@@ -166,7 +670,7 @@ enum OpcodeForm {
 }
 
 #[derive(Clone)]
-crate enum Action {
+pub(crate) enum Action {
     Continue,
     Return(u16),
     Call {
@@ -174,31 +678,47 @@ crate enum Action {
         retvar: Option<u8>,
         args: ArrayVec<[u16; 7]>,
     },
+    /// Stop before the instruction, without having read its operands, until input is sent.
+    Wait(WaitingFor),
 }
 
-#[derive(Debug)]
-crate enum OperandsDesc {
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum OperandsDesc {
     Op0,
     Op1,
     Op2,
     Var,
 }
 
-#[derive(Debug, Copy, Clone)]
-crate enum Operand {
+/// An instruction's opcode and operands, before the operands are resolved.
+#[derive(Debug, Clone)]
+pub(crate) struct DecodedOpcode {
+    pub(crate) form: OpcodeForm,
+    pub(crate) desc: OperandsDesc,
+    pub(crate) opcode: u8,
+    pub(crate) operands: ArrayVec<[Operand; 8]>,
+}
+
+/// An operand of an instruction, as written in the story.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operand {
+    /// A constant stored in a word.
     LargeConstant(u16),
+    /// A constant stored in a byte.
     SmallConstant(u8),
+    /// The value of a variable.
     Variable(u8),
+    /// No operand.
     Omitted,
 }
 
 impl Operand {
-    crate fn resolve(self, z: &mut ZMachine) -> Option<Word> {
-        match self {
+    pub(crate) fn resolve(self, z: &mut ZMachine) -> Result<Option<Word>, ExecuteError> {
+        Ok(match self {
             Operand::LargeConstant(constant) => Some(constant),
             Operand::SmallConstant(constant) => Some(constant as Word),
-            Operand::Variable(var) => Some(z.variable(var)),
+            Operand::Variable(var) => Some(z.read_variable(var)?),
             Operand::Omitted => None,
-        }
+        })
     }
 }
@@ -0,0 +1,78 @@
+use super::*;
+use crate::*;
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+
+/// Instructions that have already been decoded, by address. Only instructions outside dynamic
+/// memory are kept, since the story can't change them.
+#[derive(Debug)]
+pub(crate) struct DecodeCache {
+    enabled: bool,
+    instructions: HashMap<usize, (DecodedOpcode, ByteAddress), BuildHasherDefault<AddressHasher>>,
+}
+
+/// Hashes an address by multiplying it, which is much faster than the default hasher. Addresses
+/// are bounded by the story's size, so there's no need to resist collisions.
+#[derive(Debug, Default)]
+struct AddressHasher(u64);
+
+impl Hasher for AddressHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0.rotate_left(8) ^ byte as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        }
+    }
+    fn write_usize(&mut self, value: usize) {
+        self.0 = (value as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    }
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            instructions: HashMap::default(),
+        }
+    }
+}
+
+impl DecodeCache {
+    /// Forgets every decoded instruction, after memory outside dynamic memory has changed.
+    pub(crate) fn clear(&mut self) {
+        self.instructions.clear();
+    }
+}
+
+impl ZMachine {
+    /// Turns the cache of decoded instructions on or off. It's on by default; turning it off is
+    /// only useful to measure how much it helps.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.decode_cache.enabled = enabled;
+        self.decode_cache.clear();
+    }
+    /// Does what [`decode_opcode`](ZMachine::decode_opcode) does, using the cache if the
+    /// instruction is in it.
+    pub(crate) fn decode_opcode_cached(
+        &mut self,
+        addr: &mut ByteAddress,
+    ) -> Result<DecodedOpcode, ExecuteError> {
+        if !self.decode_cache.enabled {
+            return self.decode_opcode(addr);
+        }
+        if let Some((opcode, next)) = self.decode_cache.instructions.get(&addr.0) {
+            *addr = *next;
+            return Ok(opcode.clone());
+        }
+        let start = *addr;
+        let opcode = self.decode_opcode(addr)?;
+        if start >= self.dynamic_memory_range().end {
+            self.decode_cache
+                .instructions
+                .insert(start.0, (opcode.clone(), *addr));
+        }
+        Ok(opcode)
+    }
+}
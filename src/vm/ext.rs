@@ -1,14 +1,71 @@
 use super::*;
 use crate::*;
-use opcodes::ext::*;
+use opcodes::ext;
 
 impl ZMachine {
-    fn execute_ext(
+    pub(crate) fn execute_ext(
         &mut self,
         addr: &mut ByteAddress,
-        desc: OperandsDesc,
+        operands: &[Word],
         opcode: u8,
     ) -> ExecuteResult {
-        unimplemented!()
+        match opcode {
+            ext::log_shift | ext::art_shift => {
+                if operands.len() < 2 {
+                    return Err(ExecuteError::InvalidInstructionFormat(addr.0));
+                }
+                let (number, places) = (operands[0], operands[1] as i16);
+                let value = if places >= 0 {
+                    number.checked_shl(places as u32).unwrap_or(0)
+                } else if opcode == ext::log_shift {
+                    number.checked_shr(-places as u32).unwrap_or(0)
+                } else {
+                    (number as i16 >> (-places as u32).min(15)) as u16
+                };
+                self.store(value, addr)?;
+            }
+            ext::print_unicode => {
+                let ch = operands
+                    .first()
+                    .and_then(|&code| std::char::from_u32(code as u32))
+                    .ok_or(ExecuteError::InvalidInstructionFormat(addr.0))?;
+                let mut buf = [0; 4];
+                self.print(ch.encode_utf8(&mut buf));
+            }
+            ext::check_unicode => {
+                let ch = operands
+                    .first()
+                    .ok_or(ExecuteError::InvalidInstructionFormat(addr.0))?;
+                let result = std::char::from_u32(*ch as u32).map_or(0, |ch| self.check_unicode(ch));
+                self.store(result, addr)?;
+            }
+            ext::save => {
+                let saved = self.request_save();
+                self.store(saved as u16, addr)?;
+            }
+            ext::restore => {
+                let restored = self.request_restore();
+                self.store(restored as u16, addr)?;
+            }
+            ext::set_font => {
+                let font = operands
+                    .first()
+                    .ok_or(ExecuteError::InvalidInstructionFormat(addr.0))?;
+                let previous = self.set_font(*font);
+                self.store(previous, addr)?;
+            }
+            // undo isn't supported, which -1 tells the story
+            ext::save_undo => self.store(0xFFFF, addr)?,
+            // and so restoring always fails
+            ext::restore_undo => self.store(0, addr)?,
+            ext::set_true_color => {
+                if operands.len() < 2 {
+                    return Err(ExecuteError::InvalidInstructionFormat(addr.0));
+                }
+                self.set_true_colors(operands[0], operands[1]);
+            }
+            _ => return Err(ExecuteError::InvalidOpcode(opcode)),
+        }
+        Ok(Action::Continue)
     }
 }
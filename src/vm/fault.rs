@@ -0,0 +1,254 @@
+use super::*;
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// A way for a story to break the rules of the Z-machine that the interpreter can be told to put
+/// up with, as with Frotz's `-Z` option.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Fault {
+    /// Object 0, which doesn't exist, was used.
+    ObjectZero,
+    /// An object past the end of the object table was used. It's treated as object 0 if
+    /// tolerated.
+    InvalidObject,
+    /// An instruction wrote outside dynamic memory. The write is skipped if tolerated.
+    StaticMemoryWrite,
+    /// An instruction changed a header bit that only the interpreter may change. The write is
+    /// skipped if tolerated.
+    HeaderWrite,
+    /// The current routine's part of the stack was popped when empty.
+    StackUnderflow,
+    /// A local variable that the current routine doesn't have, or a global past the end of memory,
+    /// was used.
+    InvalidVariable,
+    /// `div` or `mod` divided by zero.
+    DivisionByZero,
+    /// An object's property that it doesn't have was used, or a property longer than 2 bytes was
+    /// set with `put_prop`. Nothing is changed if tolerated, and 0 is stored.
+    InvalidProperty,
+    /// An attribute past the last one in the story's version was used. Nothing is changed if
+    /// tolerated, and the attribute reads as clear.
+    InvalidAttribute,
+    /// An instruction read, or called a routine, past the end of memory. 0 is read if tolerated,
+    /// and calling the routine does nothing as calling address 0 does.
+    MemoryOutOfRange,
+    /// The main routine returned, which it has nowhere to return to. The story quits if tolerated.
+    ReturnFromMain,
+}
+
+/// What to do when a [`Fault`] happens.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum FaultPolicy {
+    /// Stop with an error.
+    #[default]
+    Fatal,
+    /// Pass a warning to the frontend the first time, and carry on.
+    WarnOnce,
+    /// Carry on silently.
+    Ignore,
+}
+
+/// The policy for each [`Fault`], and which have been warned about.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Faults {
+    policies: HashMap<Fault, FaultPolicy>,
+    warned: HashSet<Fault>,
+}
+
+/// An error that stopped execution, with where it happened.
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    /// What went wrong.
+    pub error: ExecuteError,
+    /// The address of the instruction that failed.
+    pub pc: ByteAddress,
+    /// The routines that were running, innermost first.
+    pub backtrace: Vec<BacktraceFrame>,
+}
+
+/// A routine that was running when a [`RuntimeError`] happened.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BacktraceFrame {
+    /// The routine's address, or where execution started for the main routine before version 6.
+    pub routine: ByteAddress,
+    /// Where the routine was: the failed instruction for the innermost routine, and otherwise
+    /// where it carries on after the call.
+    pub pc: ByteAddress,
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} at {:#x}", self.error, self.pc.0)
+    }
+}
+
+impl Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl ZMachine {
+    /// Sets what happens when a fault happens. Every fault is fatal by default.
+    pub fn set_fault_policy(&mut self, fault: Fault, policy: FaultPolicy) {
+        self.faults.policies.insert(fault, policy);
+    }
+    /// Returns what happens when a fault happens.
+    pub fn fault_policy(&self, fault: Fault) -> FaultPolicy {
+        self.faults
+            .policies
+            .get(&fault)
+            .copied()
+            .unwrap_or_default()
+    }
+    /// Handles a fault according to its policy. Returns the error if it's fatal; otherwise the
+    /// instruction should carry on as best it can.
+    pub(crate) fn fault(&mut self, error: ExecuteError) -> Result<(), ExecuteError> {
+        let fault = error.fault().expect("Not a fault");
+        match self.fault_policy(fault) {
+            FaultPolicy::Fatal => return Err(error),
+            FaultPolicy::WarnOnce => {
+                if self.faults.warned.insert(fault) {
+                    self.warn(&error.to_string());
+                }
+            }
+            FaultPolicy::Ignore => {}
+        }
+        Ok(())
+    }
+    /// Checks that an object ID isn't 0 or past the end of the object table. Returns whether the
+    /// object can be used.
+    pub(crate) fn check_object(&mut self, id: Word) -> Result<bool, ExecuteError> {
+        if id == 0 {
+            self.fault(ExecuteError::ObjectZero)?;
+            return Ok(false);
+        }
+        if id as usize > self.objects_count() {
+            self.fault(ExecuteError::InvalidObject(id))?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+    /// Checks that an attribute exists in the story's version. Returns whether it can be used.
+    pub(crate) fn check_attribute(&mut self, attribute: Word) -> Result<bool, ExecuteError> {
+        if attribute as usize >= self.object_attribute_count() {
+            self.fault(ExecuteError::InvalidAttribute(attribute))?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+    /// Checks that `len` bytes from an address are all in memory. Returns whether they can be
+    /// read.
+    pub(crate) fn check_memory(&mut self, addr: usize, len: usize) -> Result<bool, ExecuteError> {
+        if addr + len > self.len_bytes() {
+            self.fault(ExecuteError::MemoryOutOfRange(addr))?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+    /// Checks that a Z-string ends before the end of memory. If the fault is tolerated, as much of
+    /// the string as there is can still be printed.
+    pub(crate) fn check_zstring(&mut self, addr: ByteAddress) -> Result<(), ExecuteError> {
+        if self.checked_zstring_end(addr).is_none() {
+            self.fault(ExecuteError::MemoryOutOfRange(addr.0))?;
+        }
+        Ok(())
+    }
+    /// Checks that a variable exists, and for the stack that it isn't empty if `reading`. Returns
+    /// whether the variable can be used.
+    pub(crate) fn check_variable(&mut self, var: u8, reading: bool) -> Result<bool, ExecuteError> {
+        let error = match var {
+            0 => {
+                let base = self.frames.last().map_or(0, |frame| frame.stack_base);
+                if !reading || self.stack.len() > base {
+                    return Ok(true);
+                }
+                ExecuteError::StackUnderflow
+            }
+            1..=15 => {
+                let count = self.frames.last().map_or(0, |frame| frame.locals.len());
+                if var as usize <= count {
+                    return Ok(true);
+                }
+                ExecuteError::InvalidVariable(var)
+            }
+            _ => {
+                if self.global_address(var).0 + 2 <= self.len_bytes() {
+                    return Ok(true);
+                }
+                ExecuteError::InvalidVariable(var)
+            }
+        };
+        self.fault(error)?;
+        Ok(false)
+    }
+    /// Reads a variable as an instruction does, popping the stack for variable 0. A tolerated
+    /// fault reads 0.
+    pub(crate) fn read_variable(&mut self, var: u8) -> Result<Word, ExecuteError> {
+        Ok(if self.check_variable(var, true)? {
+            self.variable(var)
+        } else {
+            0
+        })
+    }
+    /// Reads a variable given by number, without popping the stack. A tolerated fault reads 0.
+    pub(crate) fn read_variable_in_place(&mut self, var: u8) -> Result<Word, ExecuteError> {
+        Ok(if self.check_variable(var, true)? {
+            self.peek_variable(var)
+        } else {
+            0
+        })
+    }
+    /// Writes a variable as an instruction's result is stored, pushing to the stack for variable
+    /// 0. A tolerated fault writes nothing.
+    pub(crate) fn write_variable(&mut self, var: u8, value: Word) -> Result<(), ExecuteError> {
+        if self.check_variable(var, false)? {
+            self.set_variable(var, value);
+        }
+        Ok(())
+    }
+    /// Writes a variable given by number, replacing the top of the stack. A tolerated fault writes
+    /// nothing.
+    pub(crate) fn write_variable_in_place(
+        &mut self,
+        var: u8,
+        value: Word,
+    ) -> Result<(), ExecuteError> {
+        if self.check_variable(var, true)? {
+            self.replace_variable(var, value);
+        }
+        Ok(())
+    }
+    /// Adds where execution was to an error.
+    pub(crate) fn runtime_error(&self, error: ExecuteError, pc: ByteAddress) -> RuntimeError {
+        let mut backtrace = Vec::with_capacity(self.frames.len());
+        let mut at = pc;
+        for frame in self.frames.iter().rev() {
+            backtrace.push(BacktraceFrame {
+                routine: frame.routine,
+                pc: at,
+            });
+            at = frame.return_pc;
+        }
+        RuntimeError {
+            error,
+            pc,
+            backtrace,
+        }
+    }
+    /// Describes a runtime error over several lines: the error, then each routine in its
+    /// backtrace as [`describe_routine`](ZMachine::describe_routine) does.
+    pub fn describe_error(&self, error: &RuntimeError) -> String {
+        let mut text = error.to_string();
+        for frame in &error.backtrace {
+            text.push_str(&format!(
+                "\n  in {} at {:#x}",
+                self.describe_routine(frame.routine, frame.pc),
+                frame.pc.0
+            ));
+        }
+        text
+    }
+}
@@ -3,7 +3,13 @@ use crate::*;
 use opcodes::op0;
 
 impl ZMachine {
-    crate fn execute_op0(
+    /// Returns the address after the text of a `print` or `print_ret` instruction, which is an
+    /// error if it runs past the end of memory, as with the rest of the instruction.
+    fn inline_zstring_end(&self, addr: ByteAddress) -> Result<ByteAddress, ExecuteError> {
+        self.checked_zstring_end(addr)
+            .ok_or(ExecuteError::MemoryOutOfRange(addr.0))
+    }
+    pub(crate) fn execute_op0(
         &mut self,
         addr: &mut ByteAddress,
         desc: OperandsDesc,
@@ -13,15 +19,14 @@ impl ZMachine {
             op0::rtrue => return Ok(Action::Return(1)),
             op0::rfalse => return Ok(Action::Return(0)),
             op0::print => {
-                let (str, bytes) = self.read_zstring(*addr);
-                self.print(&str);
-                *addr += bytes;
+                let end = self.inline_zstring_end(*addr)?;
+                self.print_zstring(*addr);
+                *addr = end;
             }
             op0::print_ret => {
-                let (str, bytes) = self.read_zstring(*addr);
-                self.print(&str);
+                self.inline_zstring_end(*addr)?;
+                self.print_zstring(*addr);
                 self.print_newline();
-                *addr += bytes;
                 return Ok(Action::Return(1));
             }
             op0::nop => {}
@@ -32,9 +37,9 @@ impl ZMachine {
                 }
                 let saved = self.request_save();
                 if ver < Version::V4 {
-                    self.branch(saved, addr);
+                    return self.branch(saved, addr);
                 } else {
-                    self.store(saved as u16, addr);
+                    self.store(saved as u16, addr)?;
                 }
             }
             op0::restore => {
@@ -44,20 +49,23 @@ impl ZMachine {
                 }
                 let restored = self.request_restore();
                 if ver < Version::V4 {
-                    self.branch(restored, addr);
+                    return self.branch(restored, addr);
                 } else {
-                    self.store(restored as u16, addr);
+                    self.store(restored as u16, addr)?;
                 }
             }
-            op0::restart => self.restart(),
-            op0::ret_popped => return Ok(Action::Return(self.pop_stack())),
+            op0::restart => {
+                self.restart();
+                *addr = self.pc;
+            }
+            op0::ret_popped => return Ok(Action::Return(self.read_variable(0)?)),
             op0::pop => {
                 if self.version() < Version::V5 {
-                    self.pop_stack();
+                    self.read_variable(0)?;
                 } else {
                     // also `catch`
                     let frame = self.stack_frame();
-                    self.store(frame, addr);
+                    self.store(frame, addr)?;
                 }
             }
             op0::quit => self.quit(),
@@ -66,10 +74,10 @@ impl ZMachine {
             op0::verify => {
                 let checksum = self.calculate_checksum();
                 let expected = self.word(ByteAddress::FILE_CHECKSUM);
-                self.branch(checksum == expected, addr);
+                return self.branch(checksum == expected, addr);
             }
             op0::extended => unreachable!(),
-            op0::piracy => self.branch(true, addr),
+            op0::piracy => return self.branch(true, addr),
             _ => return Err(ExecuteError::InvalidOpcode(opcode)),
         }
         Ok(Action::Continue)
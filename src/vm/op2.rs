@@ -1 +1,156 @@
+use super::*;
+use crate::*;
+use opcodes::op2;
 
+impl ZMachine {
+    pub(crate) fn execute_op2(
+        &mut self,
+        addr: &mut ByteAddress,
+        operands: &[Word],
+        opcode: u8,
+    ) -> ExecuteResult {
+        if operands.len() < 2 {
+            return Err(ExecuteError::InvalidInstructionFormat(addr.0));
+        }
+        let (a, b) = (operands[0], operands[1]);
+        match opcode {
+            op2::je => return self.branch(operands[1..].contains(&a), addr),
+            op2::jl => return self.branch((a as i16) < (b as i16), addr),
+            op2::jg => return self.branch((a as i16) > (b as i16), addr),
+            op2::dec_chk => {
+                let value = self.read_variable_in_place(a as u8)?.wrapping_sub(1);
+                self.write_variable_in_place(a as u8, value)?;
+                return self.branch((value as i16) < (b as i16), addr);
+            }
+            op2::inc_chk => {
+                let value = self.read_variable_in_place(a as u8)?.wrapping_add(1);
+                self.write_variable_in_place(a as u8, value)?;
+                return self.branch((value as i16) > (b as i16), addr);
+            }
+            op2::jin => {
+                let inside = self.check_object(a)?
+                    && self.object_unchecked(a as usize).parent_id().unwrap_or(0) == b as usize;
+                return self.branch(inside, addr);
+            }
+            op2::test => return self.branch(a & b == b, addr),
+            op2::or => self.store(a | b, addr)?,
+            op2::and => self.store(a & b, addr)?,
+            op2::test_attr => {
+                let set = self.check_object(a)?
+                    && self.check_attribute(b)?
+                    && self.object_unchecked(a as usize).attribute(b as usize);
+                return self.branch(set, addr);
+            }
+            op2::set_attr => {
+                if self.check_object(a)? && self.check_attribute(b)? {
+                    self.set_attribute(a as usize, b as usize, true);
+                }
+            }
+            op2::clear_attr => {
+                if self.check_object(a)? && self.check_attribute(b)? {
+                    self.set_attribute(a as usize, b as usize, false);
+                }
+            }
+            op2::store => self.write_variable_in_place(a as u8, b)?,
+            op2::insert_obj => {
+                if self.check_object(a)? && self.check_object(b)? {
+                    self.insert_object(a as usize, b as usize);
+                }
+            }
+            op2::loadw => {
+                let array = ByteAddress::from(a.wrapping_add(b.wrapping_mul(2)));
+                let value = if self.check_memory(array.0, 2)? {
+                    self.word(array)
+                } else {
+                    0
+                };
+                self.store(value, addr)?;
+            }
+            op2::loadb => {
+                let array = ByteAddress::from(a.wrapping_add(b));
+                let value = if self.check_memory(array.0, 1)? {
+                    self[array]
+                } else {
+                    0
+                };
+                self.store(value as u16, addr)?;
+            }
+            op2::get_prop => {
+                if !self.check_object(a)? {
+                    self.store(0, addr)?;
+                    return Ok(Action::Continue);
+                }
+                if b == 0 || b as usize > self.object_property_count() {
+                    self.fault(ExecuteError::InvalidProperty(a, b))?;
+                    self.store(0, addr)?;
+                    return Ok(Action::Continue);
+                }
+                let obj = self.object_unchecked(a as usize);
+                let value = obj.property_value_or_default(b as usize);
+                let value = if value.len() == 1 {
+                    value[0] as u16
+                } else {
+                    u16::from_be_bytes([value[0], value[1]])
+                };
+                self.store(value, addr)?;
+            }
+            op2::get_prop_addr => {
+                let entry = if self.check_object(a)? {
+                    self.object_unchecked(a as usize).property_entry(b as usize)
+                } else {
+                    None
+                };
+                self.store(entry.map_or(0, |entry| entry.data.0 as u16), addr)?;
+            }
+            op2::get_next_prop => {
+                if !self.check_object(a)? {
+                    self.store(0, addr)?;
+                    return Ok(Action::Continue);
+                }
+                let (found, next) = {
+                    let mut entries = self.object_unchecked(a as usize).property_entries();
+                    let found = b == 0 || entries.any(|entry| entry.id == b as usize);
+                    (found, entries.next().map_or(0, |entry| entry.id as u16))
+                };
+                let next = if found {
+                    next
+                } else {
+                    self.fault(ExecuteError::InvalidProperty(a, b))?;
+                    0
+                };
+                self.store(next, addr)?;
+            }
+            op2::add => self.store((a as i16).wrapping_add(b as i16) as u16, addr)?,
+            op2::sub => self.store((a as i16).wrapping_sub(b as i16) as u16, addr)?,
+            op2::mul => self.store((a as i16).wrapping_mul(b as i16) as u16, addr)?,
+            // a tolerated division by zero gives 0
+            op2::div => {
+                let value = if b == 0 {
+                    self.fault(ExecuteError::DivisionByZero)?;
+                    0
+                } else {
+                    (a as i16).wrapping_div(b as i16) as u16
+                };
+                self.store(value, addr)?;
+            }
+            op2::_mod => {
+                let value = if b == 0 {
+                    self.fault(ExecuteError::DivisionByZero)?;
+                    0
+                } else {
+                    (a as i16).wrapping_rem(b as i16) as u16
+                };
+                self.store(value, addr)?;
+            }
+            op2::call_2s if self.version() >= Version::V4 => {
+                let var = self.read_store(addr)?;
+                return self.call(a, &[b], Some(var));
+            }
+            op2::call_2n if self.version() >= Version::V5 => return self.call(a, &[b], None),
+            op2::set_color if self.version() >= Version::V5 => self.set_colors(a, b),
+            op2::throw if self.version() >= Version::V5 => return self.throw(a, b),
+            _ => return Err(ExecuteError::InvalidOpcode(opcode)),
+        }
+        Ok(Action::Continue)
+    }
+}
@@ -1 +1,216 @@
+use super::*;
+use crate::*;
+use opcodes::var;
 
+impl ZMachine {
+    pub(crate) fn execute_var(
+        &mut self,
+        addr: &mut ByteAddress,
+        operands: &[Word],
+        opcode: u8,
+    ) -> ExecuteResult {
+        let start = addr.0;
+        let arg = |idx: usize| operands.get(idx).copied();
+        let required = |idx: usize| {
+            operands
+                .get(idx)
+                .copied()
+                .ok_or(ExecuteError::InvalidInstructionFormat(start))
+        };
+        match opcode {
+            var::call_vs => {
+                let routine = required(0)?;
+                let var = self.read_store(addr)?;
+                return self.call(routine, &operands[1..], Some(var));
+            }
+            var::storew => {
+                let (array, idx, value) = (required(0)?, required(1)?, required(2)?);
+                self.write_word(array.wrapping_add(idx.wrapping_mul(2)).into(), value);
+            }
+            var::storeb => {
+                let (array, idx, value) = (required(0)?, required(1)?, required(2)?);
+                self.write_byte(array.wrapping_add(idx).into(), value as u8);
+            }
+            var::put_prop => {
+                let (obj, prop, value) = (required(0)?, required(1)?, required(2)?);
+                if self.check_object(obj)? {
+                    let entry = self
+                        .object_unchecked(obj as usize)
+                        .property_entry(prop as usize);
+                    match entry {
+                        Some(entry) if entry.len <= 2 => {
+                            self.put_property(obj as usize, prop as usize, value)
+                        }
+                        _ => self.fault(ExecuteError::InvalidProperty(obj, prop))?,
+                    }
+                }
+            }
+            var::sread => {
+                let text = required(0)?.into();
+                let parse = arg(1).unwrap_or(0).into();
+                match self.read_line(text, parse) {
+                    Some(terminator) if self.version() >= Version::V5 => {
+                        self.store(terminator as u16, addr)?;
+                    }
+                    Some(_) => {}
+                    None => self.quit(),
+                }
+            }
+            var::print_char => self.print_zscii(required(0)?)?,
+            var::print_num => self.print(&(required(0)? as i16).to_string()),
+            var::random => {
+                let value = self.random(required(0)? as i16);
+                self.store(value, addr)?;
+            }
+            var::push => self.push_stack(required(0)?),
+            var::pull => {
+                let value = self.read_variable(0)?;
+                if self.version() == Version::V6 {
+                    self.store(value, addr)?;
+                } else {
+                    self.write_variable_in_place(required(0)? as u8, value)?;
+                }
+            }
+            var::call_vs2 if self.version() >= Version::V4 => {
+                let routine = required(0)?;
+                let var = self.read_store(addr)?;
+                return self.call(routine, &operands[1..], Some(var));
+            }
+            var::sound_effect if self.version() >= Version::V3 => {
+                let number = arg(0).unwrap_or(1);
+                let effect = arg(1).unwrap_or(2);
+                let volume = arg(2).unwrap_or(0xFF);
+                let routine = arg(3).filter(|&routine| routine != 0);
+                let routine =
+                    routine.map(|routine| self.resolve_packed_address(routine as usize, true));
+                self.sound_effect(number, effect, volume, routine);
+            }
+            var::read_char if self.version() >= Version::V4 => match self.read_key() {
+                Some(key) => self.store(key as u16, addr)?,
+                None => self.quit(),
+            },
+            var::scan_table if self.version() >= Version::V4 => {
+                let (x, table, len) = (required(0)?, required(1)?, required(2)?);
+                let form = arg(3).unwrap_or(0x82);
+                let field_len = (form & 0b0_1111111) as usize;
+                let words = form & 0b1_0000000 != 0;
+                let size = if words { 2 } else { 1 };
+                let table_len = (len as usize).saturating_sub(1) * field_len + size;
+                // a tolerated table past the end of memory has nothing in it
+                let len = if len == 0 || self.check_memory(table as usize, table_len)? {
+                    len
+                } else {
+                    0
+                };
+                let found = (0..len as usize)
+                    .map(|idx| ByteAddress::from(table) + idx * field_len)
+                    .find(|&entry| {
+                        if words {
+                            self.word(entry) == x
+                        } else {
+                            self[entry] as u16 == x
+                        }
+                    });
+                self.store(found.map_or(0, |entry| entry.0 as u16), addr)?;
+                return self.branch(found.is_some(), addr);
+            }
+            var::not if self.version() >= Version::V5 => self.store(!required(0)?, addr)?,
+            var::call_vn if self.version() >= Version::V5 => {
+                return self.call(required(0)?, &operands[1..], None);
+            }
+            var::call_vn2 if self.version() >= Version::V5 => {
+                return self.call(required(0)?, &operands[1..], None);
+            }
+            var::tokenize if self.version() >= Version::V5 => {
+                let (text, parse) = (required(0)?.into(), required(1)?.into());
+                let dictionary = match arg(2).unwrap_or(0) {
+                    0 => self.dictionary_base(),
+                    dictionary => dictionary.into(),
+                };
+                let skip_unknown = arg(3).unwrap_or(0) != 0;
+                self.tokenise_with(text, parse, dictionary, skip_unknown);
+            }
+            var::encode_text if self.version() >= Version::V5 => {
+                let (text, len, from, coded) =
+                    (required(0)?, required(1)?, required(2)?, required(3)?);
+                let start = ByteAddress::from(text) + from as usize;
+                let word: String = self[start..(start + len as usize)]
+                    .iter()
+                    .map(|&zscii| self.zscii_char(zscii as u16).unwrap_or('?'))
+                    .collect();
+                let encoded = self.zstring_encoder().encode_dictionary_word(&word);
+                for (x, &byte) in encoded.iter().enumerate() {
+                    self.write_byte(ByteAddress::from(coded) + x, byte);
+                }
+            }
+            var::copy_table if self.version() >= Version::V5 => {
+                let (first, second, size) = (required(0)?, required(1)?, required(2)?);
+                let (first, second) = (ByteAddress::from(first), ByteAddress::from(second));
+                let len = (size as i16).unsigned_abs() as usize;
+                if second.0 == 0 {
+                    self.write_bytes(first, &vec![0; len]);
+                } else if !self.check_memory(first.0, len)? {
+                    // a tolerated table past the end of memory isn't copied
+                } else if (size as i16) < 0 || second < first {
+                    // copying forwards, even if that corrupts an overlapping table
+                    for x in 0..len {
+                        self.write_byte(second + x, self[first + x]);
+                    }
+                } else {
+                    let table = self[first..(first + len)].to_vec();
+                    self.write_bytes(second, &table);
+                }
+            }
+            var::check_arg_count if self.version() >= Version::V5 => {
+                let arg_count = self.frames.last().map_or(0, |frame| frame.arg_count);
+                return self.branch(required(0)? as usize <= arg_count, addr);
+            }
+            var::split_window if self.version() >= Version::V3 => self.split_window(required(0)?),
+            var::set_window if self.version() >= Version::V3 => self.set_window(required(0)?),
+            var::erase_window if self.version() >= Version::V4 => {
+                self.erase_window(required(0)? as i16)
+            }
+            var::erase_line if self.version() >= Version::V4 => self.erase_line(required(0)?),
+            var::set_cursor if self.version() >= Version::V4 => {
+                self.set_cursor(required(0)?, required(1)?)
+            }
+            var::get_cursor if self.version() >= Version::V4 => {
+                let array = ByteAddress::from(required(0)?);
+                let (line, column) = self.cursor();
+                self.write_word(array, line);
+                self.write_word(array + 2, column);
+            }
+            var::set_text_style if self.version() >= Version::V4 => {
+                self.set_text_style(required(0)?)
+            }
+            var::buffer_mode if self.version() >= Version::V4 => {
+                self.buffer_mode(required(0)? != 0)
+            }
+            var::output_stream if self.version() >= Version::V3 => {
+                self.output_stream(required(0)? as i16, arg(1))?
+            }
+            var::input_stream if self.version() >= Version::V3 => self.input_stream(required(0)?),
+            var::print_table if self.version() >= Version::V5 => {
+                let (text, width) = (ByteAddress::from(required(0)?), required(1)? as usize);
+                let height = arg(2).unwrap_or(1) as usize;
+                let skip = arg(3).unwrap_or(0) as usize;
+                let table_len = height.saturating_sub(1) * (width + skip) + width;
+                if height == 0 || !self.check_memory(text.0, table_len)? {
+                    return Ok(Action::Continue);
+                }
+                for row in 0..height {
+                    if row > 0 {
+                        self.print_newline();
+                    }
+                    let start = text + row * (width + skip);
+                    for x in 0..width {
+                        let zscii = self[start + x] as u16;
+                        self.print_zscii(zscii)?;
+                    }
+                }
+            }
+            _ => return Err(ExecuteError::InvalidOpcode(opcode)),
+        }
+        Ok(Action::Continue)
+    }
+}
@@ -1,9 +1,10 @@
 use crate::*;
 
 impl ZMachine {
-    /// Returns the value of a window property.
-    pub fn window_property(&self, property_id: WindowProperty) {
-        unimplemented!()
+    /// Returns the value of a window property, or [`None`] if there's no such window. Version 6
+    /// windows aren't supported yet, so there never is.
+    pub fn window_property(&self, _property: WindowProperty) -> Option<Word> {
+        None
     }
 }
 
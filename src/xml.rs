@@ -0,0 +1,235 @@
+//! A minimal XML reader, sufficient for the metadata and debugging formats used by interactive
+//! fiction tools. Doctypes, processing instructions and namespaces are skipped rather than
+//! understood.
+
+use std::char;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Element {
+    pub(crate) name: String,
+    pub(crate) attributes: Vec<(String, String)>,
+    pub(crate) children: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    /// Returns the value of an attribute.
+    pub(crate) fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(attr, _)| attr == name)
+            .map(|(_, value)| &value[..])
+    }
+    /// Returns all direct child elements.
+    pub(crate) fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+    /// Returns the first direct child element with a particular name.
+    pub(crate) fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+    /// Returns all direct child elements with a particular name.
+    pub(crate) fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.elements().filter(move |element| element.name == name)
+    }
+    /// Returns the text content of this element and its descendants. `<br/>` is read as a newline.
+    pub(crate) fn text(&self) -> String {
+        let mut string = String::new();
+        self.copy_text(&mut string);
+        string
+    }
+    fn copy_text(&self, string: &mut String) {
+        for node in &self.children {
+            match node {
+                Node::Text(text) => string.push_str(text),
+                Node::Element(element) if element.name == "br" => string.push('\n'),
+                Node::Element(element) => element.copy_text(string),
+            }
+        }
+    }
+    /// Returns the trimmed text content of the first child element with a particular name.
+    pub(crate) fn child_text(&self, name: &str) -> Option<String> {
+        self.child(name)
+            .map(|element| element.text().trim().to_string())
+    }
+}
+
+/// Parses a document, returning its root element, or [`None`] if it isn't well-formed.
+pub(crate) fn parse(document: &str) -> Option<Element> {
+    let mut parser = Parser {
+        input: document,
+        pos: 0,
+    };
+    parser.skip_prolog();
+    let root = parser.element()?;
+    Some(root)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+    fn skip_past(&mut self, end: &str) -> Option<()> {
+        let idx = self.rest().find(end)?;
+        self.pos += idx + end.len();
+        Some(())
+    }
+    fn skip_prolog(&mut self) {
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            let end = if rest.starts_with("<?") {
+                "?>"
+            } else if rest.starts_with("<!--") {
+                "-->"
+            } else if rest.starts_with("<!") {
+                ">"
+            } else {
+                return;
+            };
+            if self.skip_past(end).is_none() {
+                return;
+            }
+        }
+    }
+    fn name(&mut self) -> Option<String> {
+        let rest = self.rest();
+        let len = rest
+            .find(|ch: char| ch.is_whitespace() || ch == '>' || ch == '/' || ch == '=')
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return None;
+        }
+        self.pos += len;
+        Some(rest[..len].to_string())
+    }
+    fn element(&mut self) -> Option<Element> {
+        if !self.rest().starts_with('<') {
+            return None;
+        }
+        self.pos += 1;
+        let name = self.name()?;
+        let mut attributes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("/>") {
+                self.pos += 2;
+                return Some(Element {
+                    name,
+                    attributes,
+                    children: Vec::new(),
+                });
+            } else if rest.starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let attr = self.name()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return None;
+            }
+            self.pos += 1;
+            self.skip_whitespace();
+            let quote = self
+                .rest()
+                .chars()
+                .next()
+                .filter(|&ch| ch == '"' || ch == '\'')?;
+            self.pos += 1;
+            let len = self.rest().find(quote)?;
+            let value = unescape(&self.rest()[..len]);
+            self.pos += len + 1;
+            attributes.push((attr, value));
+        }
+        let mut children = Vec::new();
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return None;
+            } else if rest.starts_with("</") {
+                self.pos += 2;
+                if self.name()? != name {
+                    return None;
+                }
+                self.skip_past(">")?;
+                return Some(Element {
+                    name,
+                    attributes,
+                    children,
+                });
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<![CDATA[") {
+                self.pos += 9;
+                let len = self.rest().find("]]>")?;
+                children.push(Node::Text(self.rest()[..len].to_string()));
+                self.pos += len + 3;
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with('<') {
+                children.push(Node::Element(self.element()?));
+            } else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                children.push(Node::Text(unescape(&rest[..len])));
+                self.pos += len;
+            }
+        }
+    }
+}
+
+/// Replaces entity and character references with the characters they stand for.
+pub(crate) fn unescape(text: &str) -> String {
+    let mut string = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find('&') {
+        string.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let entity = &rest[1..end];
+        let ch = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16)
+                .ok()
+                .and_then(char::from_u32),
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match ch {
+            Some(ch) => {
+                string.push(ch);
+                rest = &rest[(end + 1)..];
+            }
+            None => {
+                string.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    string.push_str(rest);
+    string
+}